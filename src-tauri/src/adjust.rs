@@ -0,0 +1,300 @@
+//! Image pre-adjustment before quantization.
+//!
+//! Photos straight off a phone are often dull, tinted or soft, and a cast that the eye
+//! ignores pulls every pixel toward the wrong threads. The adjustments run on the RGBA
+//! buffer in the order an editor would apply them: white balance, auto-levels,
+//! brightness and contrast, gamma, saturation, then sharpening. Alpha is left untouched
+//! and fully transparent pixels are ignored when measuring the image.
+
+use image::RgbaImage;
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// Share of the darkest and brightest pixels auto-levels clips
+pub const AUTO_LEVELS_CLIP: f32 = 0.005;
+
+/// Blur radius of the unsharp mask
+const SHARPEN_SIGMA: f32 = 1.0;
+
+/// Rec. 709 luma weights
+const LUMA: [f32; 3] = [0.2126, 0.7152, 0.0722];
+
+/// Tonal and color corrections applied before quantization
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ImageAdjustments {
+    /// Stretch the tonal range so the darkest and brightest pixels reach black and white
+    #[serde(default)]
+    pub auto_levels: bool,
+    /// -1 to 1; added to every channel
+    #[serde(default)]
+    pub brightness: f32,
+    /// -1 to 1; scales distance from mid-gray
+    #[serde(default)]
+    pub contrast: f32,
+    /// -1 (grayscale) to 1 (double saturation)
+    #[serde(default)]
+    pub saturation: f32,
+    /// Above 1 lightens midtones, below 1 darkens them
+    #[serde(default = "default_gamma")]
+    pub gamma: f32,
+    /// Neutralize the average color before any manual white balance
+    #[serde(default)]
+    pub auto_white_balance: bool,
+    /// -1 (cooler) to 1 (warmer)
+    #[serde(default)]
+    pub temperature: f32,
+    /// -1 (greener) to 1 (more magenta)
+    #[serde(default)]
+    pub tint: f32,
+    /// Unsharp mask strength, 0 to 2
+    #[serde(default)]
+    pub sharpen: f32,
+}
+
+fn default_gamma() -> f32 {
+    1.0
+}
+
+impl Default for ImageAdjustments {
+    fn default() -> Self {
+        Self {
+            auto_levels: false,
+            brightness: 0.0,
+            contrast: 0.0,
+            saturation: 0.0,
+            gamma: default_gamma(),
+            auto_white_balance: false,
+            temperature: 0.0,
+            tint: 0.0,
+            sharpen: 0.0,
+        }
+    }
+}
+
+impl ImageAdjustments {
+    /// Whether applying these adjustments would leave every pixel as it is.
+    pub fn is_identity(&self) -> bool {
+        *self == ImageAdjustments::default()
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        let signed = [
+            ("Brightness", self.brightness),
+            ("Contrast", self.contrast),
+            ("Saturation", self.saturation),
+            ("Temperature", self.temperature),
+            ("Tint", self.tint),
+        ];
+        for (name, value) in signed {
+            if !(-1.0..=1.0).contains(&value) {
+                return Err(format!("{} must be between -1 and 1", name));
+            }
+        }
+        if !(self.gamma.is_finite() && (0.1..=10.0).contains(&self.gamma)) {
+            return Err("Gamma must be between 0.1 and 10".to_string());
+        }
+        if !(0.0..=2.0).contains(&self.sharpen) {
+            return Err("Sharpen must be between 0 and 2".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// Apply `adjustments` to `image` in place.
+pub fn apply_adjustments(
+    image: &mut RgbaImage,
+    adjustments: &ImageAdjustments,
+) -> Result<(), String> {
+    adjustments.validate()?;
+    if adjustments.is_identity() {
+        return Ok(());
+    }
+
+    // Per-channel gains from gray-world and manual white balance
+    let mut gains = if adjustments.auto_white_balance {
+        gray_world_gains(image)
+    } else {
+        [1.0; 3]
+    };
+    let warm = adjustments.temperature * 0.3;
+    let magenta = adjustments.tint * 0.2;
+    gains[0] *= 1.0 + warm;
+    gains[1] *= 1.0 - magenta;
+    gains[2] *= 1.0 - warm;
+
+    let (low, high) = if adjustments.auto_levels {
+        level_bounds(image, gains)
+    } else {
+        (0.0, 1.0)
+    };
+    let range = (high - low).max(1.0 / 255.0);
+    let contrast = 1.0 + adjustments.contrast;
+    let saturation = 1.0 + adjustments.saturation;
+    let inverse_gamma = 1.0 / adjustments.gamma;
+
+    image.par_chunks_mut(4).for_each(|pixel| {
+        let mut rgb = [0.0f32; 3];
+        for c in 0..3 {
+            let mut v = pixel[c] as f32 / 255.0 * gains[c];
+            v = (v - low) / range;
+            v = (v - 0.5) * contrast + 0.5 + adjustments.brightness;
+            rgb[c] = v.clamp(0.0, 1.0).powf(inverse_gamma);
+        }
+        let luma: f32 = rgb.iter().zip(LUMA).map(|(v, w)| v * w).sum();
+        for c in 0..3 {
+            let v = luma + (rgb[c] - luma) * saturation;
+            pixel[c] = (v.clamp(0.0, 1.0) * 255.0).round() as u8;
+        }
+    });
+
+    if adjustments.sharpen > 0.0 {
+        sharpen(image, adjustments.sharpen);
+    }
+    Ok(())
+}
+
+/// Gains that bring the average of the visible pixels to neutral gray.
+fn gray_world_gains(image: &RgbaImage) -> [f32; 3] {
+    let (sum, count) = image
+        .par_chunks(4)
+        .filter(|pixel| pixel[3] > 0)
+        .map(|pixel| ([pixel[0] as f64, pixel[1] as f64, pixel[2] as f64], 1u64))
+        .reduce(
+            || ([0.0; 3], 0),
+            |a, b| {
+                (
+                    [a.0[0] + b.0[0], a.0[1] + b.0[1], a.0[2] + b.0[2]],
+                    a.1 + b.1,
+                )
+            },
+        );
+    if count == 0 || sum.iter().any(|&s| s <= 0.0) {
+        return [1.0; 3];
+    }
+    let gray = (sum[0] + sum[1] + sum[2]) / 3.0;
+    [
+        (gray / sum[0]) as f32,
+        (gray / sum[1]) as f32,
+        (gray / sum[2]) as f32,
+    ]
+}
+
+/// Darkest and brightest white-balanced channel values after clipping `AUTO_LEVELS_CLIP`
+/// of the pixels at each end. One stretch for all channels keeps hues intact.
+fn level_bounds(image: &RgbaImage, gains: [f32; 3]) -> (f32, f32) {
+    let mut darkest = [0u32; 256];
+    let mut brightest = [0u32; 256];
+    let mut count = 0u32;
+    for pixel in image.pixels().filter(|p| p[3] > 0) {
+        let balanced = (0..3).map(|c| (pixel[c] as f32 * gains[c]).clamp(0.0, 255.0) as usize);
+        let (min, max) = balanced.fold((255, 0), |(lo, hi), v| (lo.min(v), hi.max(v)));
+        darkest[min] += 1;
+        brightest[max] += 1;
+        count += 1;
+    }
+    if count == 0 {
+        return (0.0, 1.0);
+    }
+
+    let clip = (count as f32 * AUTO_LEVELS_CLIP) as u32;
+    let percentile = |histogram: &[u32; 256], from_top: bool| {
+        let mut seen = 0;
+        for i in 0..256 {
+            let bin = if from_top { 255 - i } else { i };
+            seen += histogram[bin];
+            if seen > clip {
+                return bin as f32 / 255.0;
+            }
+        }
+        if from_top {
+            0.0
+        } else {
+            1.0
+        }
+    };
+    let low = percentile(&darkest, false);
+    let high = percentile(&brightest, true);
+    if high - low < 1.0 / 255.0 {
+        // A flat image has no range to stretch
+        (0.0, 1.0)
+    } else {
+        (low, high)
+    }
+}
+
+/// Unsharp mask: push each pixel away from its blurred neighbourhood by `amount`.
+fn sharpen(image: &mut RgbaImage, amount: f32) {
+    let blurred = image::imageops::blur(image, SHARPEN_SIGMA);
+    image
+        .par_chunks_mut(4)
+        .zip(blurred.par_chunks(4))
+        .for_each(|(pixel, soft)| {
+            for c in 0..3 {
+                let v = pixel[c] as f32 + (pixel[c] as f32 - soft[c] as f32) * amount;
+                pixel[c] = v.clamp(0.0, 255.0).round() as u8;
+            }
+        });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn image(pixels: &[[u8; 4]]) -> RgbaImage {
+        RgbaImage::from_raw(pixels.len() as u32, 1, pixels.concat()).expect("image")
+    }
+
+    #[test]
+    fn identity_leaves_pixels_alone() {
+        let pixels = [[12, 200, 90, 255], [250, 3, 40, 128]];
+        let mut adjusted = image(&pixels);
+        apply_adjustments(&mut adjusted, &ImageAdjustments::default()).expect("apply");
+        assert_eq!(adjusted, image(&pixels));
+
+        let invalid = ImageAdjustments {
+            gamma: 0.0,
+            ..ImageAdjustments::default()
+        };
+        assert!(apply_adjustments(&mut adjusted, &invalid).is_err());
+    }
+
+    #[test]
+    fn auto_corrections_stretch_and_neutralize() {
+        // A dull, warm ramp: levels reach black and white, and the middle turns gray.
+        let mut ramp = image(&[
+            [80, 60, 50, 255],
+            [130, 110, 100, 255],
+            [180, 160, 150, 255],
+            [0, 0, 255, 0],
+        ]);
+        let adjustments = ImageAdjustments {
+            auto_levels: true,
+            auto_white_balance: true,
+            ..ImageAdjustments::default()
+        };
+        apply_adjustments(&mut ramp, &adjustments).expect("apply");
+
+        let darkest = ramp.get_pixel(0, 0);
+        let middle = ramp.get_pixel(1, 0);
+        assert!(darkest.0[..3].iter().min().unwrap() <= &2);
+        assert!(ramp.get_pixel(2, 0).0[..3].iter().max().unwrap() >= &253);
+        let spread = middle.0[..3].iter().max().unwrap() - middle.0[..3].iter().min().unwrap();
+        assert!(spread < 30, "middle {:?}", middle);
+        // The transparent pixel keeps its alpha
+        assert_eq!(ramp.get_pixel(3, 0).0[3], 0);
+    }
+
+    #[test]
+    fn saturation_and_contrast_move_away_from_gray() {
+        let mut swatch = image(&[[160, 100, 100, 255], [100, 100, 100, 255]]);
+        let adjustments = ImageAdjustments {
+            saturation: 0.5,
+            contrast: 0.5,
+            ..ImageAdjustments::default()
+        };
+        apply_adjustments(&mut swatch, &adjustments).expect("apply");
+        let red = swatch.get_pixel(0, 0).0;
+        assert!(red[0] as i32 - red[1] as i32 > 60);
+        assert!(swatch.get_pixel(1, 0).0[0] < 100);
+    }
+}
@@ -0,0 +1,368 @@
+//! Backstitch outlines along color boundaries.
+//!
+//! Backstitch runs along the edges between cells, so lines live on grid corners: point
+//! (x, y) is the top-left corner of cell (x, y). Cell edges come from the same walk the
+//! region tracer uses; qualifying ones are chained into polylines that break at junctions,
+//! and collinear runs are merged so every pair of consecutive points is one straight line
+//! of stitches.
+
+use crate::color_metric::ColorMetric;
+use crate::embroidery::{hex_to_rgb, rgb_to_lab, LabelGrid, FABRIC_LABEL};
+use crate::regions::{for_each_cell_edge, GridPoint};
+use crate::threads::ThreadCatalog;
+use palette::{white_point::D65, Lab};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Delta-E above which `Contrast` outlines a boundary
+pub const DEFAULT_BACKSTITCH_DELTA_E: f32 = 20.0;
+
+/// Which cell edges get a backstitch line
+#[derive(Debug, Copy, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum BackstitchBoundaries {
+    /// Every edge between two different threads, or between a thread and fabric
+    #[default]
+    All,
+    /// Edges whose two sides differ by at least `min_delta_e`, fabric included
+    Contrast,
+    /// The outline of the stitched area against fabric and the chart edge
+    MaskOutline,
+}
+
+/// Backstitch layer options
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct BackstitchConfig {
+    #[serde(default)]
+    pub boundaries: BackstitchBoundaries,
+    /// Threshold for `Contrast`; defaults to `DEFAULT_BACKSTITCH_DELTA_E`
+    #[serde(default)]
+    pub min_delta_e: Option<f32>,
+    /// Thread to stitch the lines with; defaults to the darkest thread in the pattern
+    #[serde(default)]
+    pub thread_code: Option<String>,
+}
+
+/// One continuous backstitch line
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct BackstitchLine {
+    pub dmc_code: String,
+    pub hex: String,
+    /// Grid corners; each consecutive pair is one straight run along cell edges
+    pub points: Vec<GridPoint>,
+}
+
+#[derive(Copy, Clone, PartialEq)]
+enum Side {
+    /// Outside the chart
+    Off,
+    Fabric,
+    Thread(u16),
+}
+
+/// Backstitch lines for `grid`, stitched in the configured thread.
+///
+/// `thread_code` is looked up in `catalog`; without one the darkest thread used in the
+/// grid is taken, so outlines read as ink lines over the filled stitches. `fabric` is the
+/// color `Contrast` measures unstitched cells by.
+pub fn build_backstitch(
+    grid: &LabelGrid,
+    width: u32,
+    height: u32,
+    config: &BackstitchConfig,
+    fabric: Lab<D65, f32>,
+    metric: ColorMetric,
+    catalog: &ThreadCatalog,
+) -> Result<Vec<BackstitchLine>, String> {
+    let (dmc_code, hex) = match config.thread_code.as_deref().map(str::trim) {
+        Some(code) if !code.is_empty() => {
+            let thread = catalog.find_code(code).ok_or_else(|| {
+                format!(
+                    "Backstitch thread {} is not in the {} catalog",
+                    code,
+                    catalog.brand.label()
+                )
+            })?;
+            (thread.code.clone(), thread.hex.clone())
+        }
+        _ => {
+            let mut used = vec![false; grid.colors.len()];
+            for &label in &grid.labels {
+                if let Some(flag) = used.get_mut(label as usize) {
+                    *flag = true;
+                }
+            }
+            let darkest = grid
+                .colors
+                .iter()
+                .zip(&used)
+                .filter(|(_, used)| **used)
+                .map(|(color, _)| color)
+                .min_by(|a, b| {
+                    let l = |hex: &str| rgb_to_lab(hex_to_rgb(hex)).l;
+                    l(&a.hex).total_cmp(&l(&b.hex))
+                });
+            match darkest {
+                Some(color) => (color.dmc_code.clone(), color.hex.clone()),
+                None => return Ok(Vec::new()),
+            }
+        }
+    };
+
+    Ok(
+        trace_backstitch(grid, width, height, config, fabric, metric)
+            .into_iter()
+            .map(|points| BackstitchLine {
+                dmc_code: dmc_code.clone(),
+                hex: hex.clone(),
+                points,
+            })
+            .collect(),
+    )
+}
+
+/// Polylines along the boundaries selected by `config`.
+pub fn trace_backstitch(
+    grid: &LabelGrid,
+    width: u32,
+    height: u32,
+    config: &BackstitchConfig,
+    fabric: Lab<D65, f32>,
+    metric: ColorMetric,
+) -> Vec<Vec<GridPoint>> {
+    let side = |cell: Option<usize>| -> Side {
+        let Some(idx) = cell else {
+            return Side::Off;
+        };
+        match grid.labels.get(idx) {
+            Some(&label) if label != FABRIC_LABEL && (label as usize) < grid.colors.len() => {
+                Side::Thread(label)
+            }
+            _ => Side::Fabric,
+        }
+    };
+
+    let labs: Vec<Lab<D65, f32>> = grid
+        .colors
+        .iter()
+        .map(|color| rgb_to_lab(hex_to_rgb(&color.hex)))
+        .collect();
+    let min_delta_e = config.min_delta_e.unwrap_or(DEFAULT_BACKSTITCH_DELTA_E);
+    let mut contrast_cache: HashMap<(Option<u16>, Option<u16>), bool> = HashMap::new();
+
+    let mut qualifies = |a: Side, b: Side| -> bool {
+        let same_thread =
+            |x: u16, y: u16| grid.colors[x as usize].dmc_code == grid.colors[y as usize].dmc_code;
+        match config.boundaries {
+            BackstitchBoundaries::MaskOutline => matches!(
+                (a, b),
+                (Side::Thread(_), Side::Fabric | Side::Off)
+                    | (Side::Fabric | Side::Off, Side::Thread(_))
+            ),
+            BackstitchBoundaries::All | BackstitchBoundaries::Contrast => {
+                let (x, y) = match (a, b) {
+                    (Side::Off, _) | (_, Side::Off) | (Side::Fabric, Side::Fabric) => return false,
+                    (Side::Thread(x), Side::Thread(y)) if same_thread(x, y) => return false,
+                    (Side::Thread(x), Side::Thread(y)) => (Some(x), Some(y)),
+                    (Side::Thread(x), Side::Fabric) | (Side::Fabric, Side::Thread(x)) => {
+                        (Some(x), None)
+                    }
+                };
+                if config.boundaries == BackstitchBoundaries::All {
+                    return true;
+                }
+                *contrast_cache.entry((x, y)).or_insert_with(|| {
+                    let lab =
+                        |label: Option<u16>| label.map(|l| labs[l as usize]).unwrap_or(fabric);
+                    metric.distance(lab(x), lab(y)) >= min_delta_e
+                })
+            }
+        }
+    };
+
+    let mut edges: Vec<(GridPoint, GridPoint)> = Vec::new();
+    for_each_cell_edge(
+        width as usize,
+        height as usize,
+        |start, end, before, after| {
+            if qualifies(side(before), side(after)) {
+                edges.push((start, end));
+            }
+        },
+    );
+
+    chain_edges(&edges)
+}
+
+/// Join unit edges into polylines, breaking at junctions and merging straight runs.
+fn chain_edges(edges: &[(GridPoint, GridPoint)]) -> Vec<Vec<GridPoint>> {
+    let mut incident: HashMap<GridPoint, Vec<usize>> = HashMap::new();
+    for (idx, (a, b)) in edges.iter().enumerate() {
+        incident.entry(*a).or_default().push(idx);
+        incident.entry(*b).or_default().push(idx);
+    }
+    let mut nodes: Vec<GridPoint> = incident.keys().copied().collect();
+    nodes.sort();
+
+    let mut used = vec![false; edges.len()];
+    let mut lines: Vec<Vec<GridPoint>> = Vec::new();
+    let mut walk = |start: GridPoint, first_edge: usize, used: &mut Vec<bool>| {
+        let mut points = vec![start];
+        let mut current = start;
+        let mut edge = first_edge;
+        loop {
+            used[edge] = true;
+            let (a, b) = edges[edge];
+            current = if a == current { b } else { a };
+            points.push(current);
+            // Only continue through plain path points; junctions end the line.
+            if incident[&current].len() != 2 {
+                break;
+            }
+            match incident[&current].iter().copied().find(|e| !used[*e]) {
+                Some(next_edge) => edge = next_edge,
+                None => break,
+            }
+        }
+        lines.push(merge_collinear(points));
+    };
+
+    // Open lines start at ends and junctions; what is left are closed loops.
+    for node in &nodes {
+        if incident[node].len() == 2 {
+            continue;
+        }
+        for edge in incident[node].clone() {
+            if !used[edge] {
+                walk(*node, edge, &mut used);
+            }
+        }
+    }
+    for node in &nodes {
+        for edge in incident[node].clone() {
+            if !used[edge] {
+                walk(*node, edge, &mut used);
+            }
+        }
+    }
+
+    lines
+}
+
+fn merge_collinear(points: Vec<GridPoint>) -> Vec<GridPoint> {
+    let mut merged: Vec<GridPoint> = Vec::with_capacity(points.len());
+    for point in points {
+        if merged.len() >= 2 {
+            let a = merged[merged.len() - 2];
+            let b = merged[merged.len() - 1];
+            let straight = (a.x == b.x && b.x == point.x) || (a.y == b.y && b.y == point.y);
+            if straight {
+                merged.pop();
+            }
+        }
+        merged.push(point);
+    }
+    merged
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::embroidery::GridColor;
+
+    fn color(code: &str, hex: &str) -> GridColor {
+        GridColor {
+            dmc_code: code.to_string(),
+            marker: String::new(),
+            hex: hex.to_string(),
+            blend: None,
+        }
+    }
+
+    /// 4x3 chart: black | near-black columns over one row of fabric.
+    fn chart() -> LabelGrid {
+        let f = FABRIC_LABEL;
+        LabelGrid {
+            labels: vec![0, 0, 1, 1, 0, 0, 1, 1, f, f, f, f],
+            colors: vec![color("310", "#000000"), color("3371", "#1E1108")],
+            partials: Vec::new(),
+        }
+    }
+
+    fn white() -> Lab<D65, f32> {
+        rgb_to_lab([255, 255, 255])
+    }
+
+    fn config(boundaries: BackstitchBoundaries) -> BackstitchConfig {
+        BackstitchConfig {
+            boundaries,
+            ..BackstitchConfig::default()
+        }
+    }
+
+    #[test]
+    fn all_boundaries_split_at_the_junction() {
+        let lines = trace_backstitch(
+            &chart(),
+            4,
+            3,
+            &config(BackstitchBoundaries::All),
+            white(),
+            ColorMetric::Ciede2000,
+        );
+        // The thread/thread edge meets the thread/fabric edge in a T at (2, 2).
+        let vertical = vec![GridPoint { x: 2, y: 0 }, GridPoint { x: 2, y: 2 }];
+        assert!(lines.contains(&vertical));
+        let total: i32 = lines
+            .iter()
+            .flat_map(|line| line.windows(2))
+            .map(|pair| (pair[1].x - pair[0].x).abs() + (pair[1].y - pair[0].y).abs())
+            .sum();
+        assert_eq!(total, 2 + 4);
+        assert_eq!(lines.len(), 3);
+    }
+
+    #[test]
+    fn contrast_and_mask_outline_select_edges() {
+        let contrast = trace_backstitch(
+            &chart(),
+            4,
+            3,
+            &config(BackstitchBoundaries::Contrast),
+            white(),
+            ColorMetric::Ciede2000,
+        );
+        assert_eq!(
+            contrast,
+            vec![vec![GridPoint { x: 0, y: 2 }, GridPoint { x: 4, y: 2 }]]
+        );
+        // On black fabric the dark threads blend in instead
+        let on_black = trace_backstitch(
+            &chart(),
+            4,
+            3,
+            &config(BackstitchBoundaries::Contrast),
+            rgb_to_lab([0, 0, 0]),
+            ColorMetric::Ciede2000,
+        );
+        assert!(on_black.is_empty());
+
+        let outline = trace_backstitch(
+            &chart(),
+            4,
+            3,
+            &config(BackstitchBoundaries::MaskOutline),
+            white(),
+            ColorMetric::Ciede2000,
+        );
+        assert_eq!(outline.len(), 1);
+        let ring = &outline[0];
+        assert_eq!(ring.first(), ring.last());
+        for corner in [(0, 0), (4, 0), (4, 2), (0, 2)] {
+            assert!(ring.contains(&GridPoint {
+                x: corner.0,
+                y: corner.1
+            }));
+        }
+    }
+}
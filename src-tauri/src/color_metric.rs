@@ -0,0 +1,129 @@
+//! Selectable color-difference metrics.
+//!
+//! CIEDE2000 is the most perceptually faithful choice but also the slowest, which matters in
+//! the k-means inner loop. CIE94 and CMC are the textile industry's traditional formulas
+//! and are asymmetric: the first argument is the reference (the pixel or target color).
+//! OKLab distances are scaled by 100 so every metric lands in roughly Delta-E units.
+
+use palette::{color_difference::Ciede2000, white_point::D65, FromColor, Lab, Oklab};
+use serde::{Deserialize, Serialize};
+
+/// Color-difference formula used for clustering, thread matching and region merging
+#[derive(Debug, Copy, Clone, Default, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum ColorMetric {
+    /// Euclidean distance in LAB
+    Cie76,
+    /// CIE94 with graphic-arts weights
+    Cie94,
+    /// CMC l:c with the 2:1 acceptability weighting
+    Cmc,
+    #[default]
+    Ciede2000,
+    /// Euclidean distance in OKLab, times 100
+    Oklab,
+}
+
+impl ColorMetric {
+    /// Difference between `reference` and `sample`.
+    pub fn distance(self, reference: Lab<D65, f32>, sample: Lab<D65, f32>) -> f32 {
+        match self {
+            ColorMetric::Cie76 => cie76(reference, sample),
+            ColorMetric::Cie94 => cie94(reference, sample),
+            ColorMetric::Cmc => cmc(reference, sample, 2.0, 1.0),
+            ColorMetric::Ciede2000 => reference.difference(sample),
+            ColorMetric::Oklab => {
+                let a = Oklab::from_color(reference);
+                let b = Oklab::from_color(sample);
+                100.0 * ((a.l - b.l).powi(2) + (a.a - b.a).powi(2) + (a.b - b.b).powi(2)).sqrt()
+            }
+        }
+    }
+}
+
+fn cie76(a: Lab<D65, f32>, b: Lab<D65, f32>) -> f32 {
+    ((a.l - b.l).powi(2) + (a.a - b.a).powi(2) + (a.b - b.b).powi(2)).sqrt()
+}
+
+/// Lightness, chroma and squared hue differences shared by CIE94 and CMC
+fn lch_deltas(reference: Lab<D65, f32>, sample: Lab<D65, f32>) -> (f32, f32, f32, f32) {
+    let c1 = reference.a.hypot(reference.b);
+    let c2 = sample.a.hypot(sample.b);
+    let delta_l = reference.l - sample.l;
+    let delta_c = c1 - c2;
+    let delta_h_sq = ((reference.a - sample.a).powi(2) + (reference.b - sample.b).powi(2)
+        - delta_c.powi(2))
+    .max(0.0);
+    (c1, delta_l, delta_c, delta_h_sq)
+}
+
+fn cie94(reference: Lab<D65, f32>, sample: Lab<D65, f32>) -> f32 {
+    let (c1, delta_l, delta_c, delta_h_sq) = lch_deltas(reference, sample);
+    let s_c = 1.0 + 0.045 * c1;
+    let s_h = 1.0 + 0.015 * c1;
+    (delta_l.powi(2) + (delta_c / s_c).powi(2) + delta_h_sq / s_h.powi(2)).sqrt()
+}
+
+fn cmc(reference: Lab<D65, f32>, sample: Lab<D65, f32>, l: f32, c: f32) -> f32 {
+    let (c1, delta_l, delta_c, delta_h_sq) = lch_deltas(reference, sample);
+    let hue = reference
+        .b
+        .atan2(reference.a)
+        .to_degrees()
+        .rem_euclid(360.0);
+
+    let s_l = if reference.l < 16.0 {
+        0.511
+    } else {
+        0.040975 * reference.l / (1.0 + 0.01765 * reference.l)
+    };
+    let s_c = 0.0638 * c1 / (1.0 + 0.0131 * c1) + 0.638;
+    let f = (c1.powi(4) / (c1.powi(4) + 1900.0)).sqrt();
+    let t = if (164.0..=345.0).contains(&hue) {
+        0.56 + (0.2 * (hue + 168.0).to_radians().cos()).abs()
+    } else {
+        0.36 + (0.4 * (hue + 35.0).to_radians().cos()).abs()
+    };
+    let s_h = s_c * (f * t + 1.0 - f);
+
+    ((delta_l / (l * s_l)).powi(2) + (delta_c / (c * s_c)).powi(2) + delta_h_sq / s_h.powi(2))
+        .sqrt()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ALL: [ColorMetric; 5] = [
+        ColorMetric::Cie76,
+        ColorMetric::Cie94,
+        ColorMetric::Cmc,
+        ColorMetric::Ciede2000,
+        ColorMetric::Oklab,
+    ];
+
+    #[test]
+    fn metrics_agree_on_identity_and_ordering() {
+        let red = Lab::new(53.2, 80.1, 67.2);
+        let near_red = Lab::new(52.0, 76.0, 64.0);
+        let blue = Lab::new(32.3, 79.2, -107.9);
+        for metric in ALL {
+            assert!(metric.distance(red, red).abs() < 1e-3, "{:?}", metric);
+            assert!(
+                metric.distance(red, near_red) < metric.distance(red, blue),
+                "{:?}",
+                metric
+            );
+        }
+    }
+
+    #[test]
+    fn textbook_reference_values() {
+        // Sharma et al. pair 1 for CIE76 and CIE94 (graphic arts).
+        let a = Lab::new(50.0, 2.6772, -79.7751);
+        let b = Lab::new(50.0, 0.0, -82.7485);
+        assert!((ColorMetric::Cie76.distance(a, b) - 4.0011).abs() < 1e-3);
+        assert!((ColorMetric::Cie94.distance(a, b) - 1.3950).abs() < 1e-3);
+        assert!((ColorMetric::Ciede2000.distance(a, b) - 2.0425).abs() < 1e-3);
+    }
+}
@@ -0,0 +1,322 @@
+//! Confetti analysis and reduction.
+//!
+//! Confetti is a stitch with no neighbour in the same thread, counting diagonal neighbours:
+//! each one means starting and ending a thread for a single cross. The report counts them
+//! per thread and maps their density over the chart. The reduction pass recolors a confetti
+//! stitch to its perceptually closest neighbouring thread, but only within a Delta-E budget,
+//! so deliberate accents such as eye highlights are kept.
+
+use crate::color_metric::ColorMetric;
+use crate::embroidery::{hex_to_rgb, rgb_to_lab, LabelGrid, FABRIC_LABEL};
+use palette::{white_point::D65, Lab};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Widest Delta-E a confetti stitch may move when no budget is given
+pub const DEFAULT_CONFETTI_DELTA_E: f32 = 10.0;
+
+/// Side of the square blocks the density map is measured over, matching the chart's
+/// major grid lines
+pub const CONFETTI_BLOCK: u32 = 10;
+
+const NEIGHBORS: [(i32, i32); 8] = [
+    (-1, -1),
+    (0, -1),
+    (1, -1),
+    (-1, 0),
+    (1, 0),
+    (-1, 1),
+    (0, 1),
+    (1, 1),
+];
+
+/// Confetti reduction options
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct ConfettiConfig {
+    /// Widest Delta-E a stitch may be recolored by; defaults to `DEFAULT_CONFETTI_DELTA_E`
+    #[serde(default)]
+    pub max_delta_e: Option<f32>,
+}
+
+/// Isolated stitches of one thread
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ConfettiCount {
+    pub dmc_code: String,
+    pub isolated: u32,
+}
+
+/// Share of isolated stitches per `block` x `block` area of the chart
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct ConfettiDensity {
+    pub block: u32,
+    /// Blocks across and down
+    pub width: u32,
+    pub height: u32,
+    /// Isolated / stitched cells per block in row-major order; 0 for bare blocks
+    pub values: Vec<f32>,
+}
+
+/// Confetti left in a pattern, and how many stitches the reduction pass changed
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct ConfettiReport {
+    pub total: u32,
+    /// Threads with at least one isolated stitch, most confetti first
+    pub by_color: Vec<ConfettiCount>,
+    pub density: ConfettiDensity,
+    pub reduced: u32,
+}
+
+/// Thread identity per label, so labels that share a code count as one thread
+fn thread_ids(grid: &LabelGrid) -> Vec<usize> {
+    let mut ids: HashMap<&str, usize> = HashMap::new();
+    grid.colors
+        .iter()
+        .map(|color| {
+            let next = ids.len();
+            *ids.entry(color.dmc_code.as_str()).or_insert(next)
+        })
+        .collect()
+}
+
+fn is_isolated(grid: &LabelGrid, threads: &[usize], width: u32, height: u32, idx: usize) -> bool {
+    let Some(&thread) = threads.get(grid.labels[idx] as usize) else {
+        return false;
+    };
+    let (x, y) = ((idx as u32 % width) as i32, (idx as u32 / width) as i32);
+    !NEIGHBORS.iter().any(|(dx, dy)| {
+        let (nx, ny) = (x + dx, y + dy);
+        nx >= 0
+            && ny >= 0
+            && nx < width as i32
+            && ny < height as i32
+            && threads.get(grid.labels[(ny * width as i32 + nx) as usize] as usize) == Some(&thread)
+    })
+}
+
+/// Count isolated stitches per thread and per density block.
+pub fn analyze_confetti(grid: &LabelGrid, width: u32, height: u32) -> ConfettiReport {
+    let threads = thread_ids(grid);
+    let blocks_x = width.div_ceil(CONFETTI_BLOCK);
+    let blocks_y = height.div_ceil(CONFETTI_BLOCK);
+    let mut stitched = vec![0u32; (blocks_x * blocks_y) as usize];
+    let mut isolated = vec![0u32; stitched.len()];
+    let mut by_code: HashMap<&str, u32> = HashMap::new();
+
+    for idx in 0..grid.labels.len() {
+        let label = grid.labels[idx];
+        if label == FABRIC_LABEL || label as usize >= grid.colors.len() {
+            continue;
+        }
+        let block = ((idx as u32 / width) / CONFETTI_BLOCK * blocks_x
+            + (idx as u32 % width) / CONFETTI_BLOCK) as usize;
+        stitched[block] += 1;
+        if is_isolated(grid, &threads, width, height, idx) {
+            isolated[block] += 1;
+            *by_code
+                .entry(grid.colors[label as usize].dmc_code.as_str())
+                .or_insert(0) += 1;
+        }
+    }
+
+    let mut by_color: Vec<ConfettiCount> = by_code
+        .into_iter()
+        .map(|(code, count)| ConfettiCount {
+            dmc_code: code.to_string(),
+            isolated: count,
+        })
+        .collect();
+    by_color.sort_by(|a, b| {
+        b.isolated
+            .cmp(&a.isolated)
+            .then_with(|| a.dmc_code.cmp(&b.dmc_code))
+    });
+
+    ConfettiReport {
+        total: isolated.iter().sum(),
+        by_color,
+        density: ConfettiDensity {
+            block: CONFETTI_BLOCK,
+            width: blocks_x,
+            height: blocks_y,
+            values: isolated
+                .iter()
+                .zip(&stitched)
+                .map(|(&i, &s)| if s == 0 { 0.0 } else { i as f32 / s as f32 })
+                .collect(),
+        },
+        reduced: 0,
+    }
+}
+
+/// Recolor isolated stitches to their closest neighbouring thread within `max_delta_e`.
+///
+/// Sweeps in raster order until a pass changes nothing, since a recolor can bring an
+/// earlier skipped stitch within budget of its new neighbour. Every recolor removes an
+/// isolated stitch without isolating another, so the sweeps terminate. Cells split into
+/// fractional stitches are left alone. Returns the number of stitches changed.
+pub fn reduce_confetti(
+    grid: &mut LabelGrid,
+    width: u32,
+    height: u32,
+    max_delta_e: f32,
+    metric: ColorMetric,
+) -> u32 {
+    let threads = thread_ids(grid);
+    let labs: Vec<Lab<D65, f32>> = grid
+        .colors
+        .iter()
+        .map(|color| rgb_to_lab(hex_to_rgb(&color.hex)))
+        .collect();
+    let mut changed = 0u32;
+    loop {
+        let swept = reduce_pass(grid, &threads, &labs, width, height, max_delta_e, metric);
+        if swept == 0 {
+            return changed;
+        }
+        changed += swept;
+    }
+}
+
+/// One raster-order sweep of [`reduce_confetti`]
+fn reduce_pass(
+    grid: &mut LabelGrid,
+    threads: &[usize],
+    labs: &[Lab<D65, f32>],
+    width: u32,
+    height: u32,
+    max_delta_e: f32,
+    metric: ColorMetric,
+) -> u32 {
+    let (w, h) = (width as i32, height as i32);
+    let mut changed = 0u32;
+
+    for idx in 0..grid.labels.len() {
+        let label = grid.labels[idx];
+        if label as usize >= grid.colors.len()
+            || grid.partial(idx).is_some()
+            || !is_isolated(grid, threads, width, height, idx)
+        {
+            continue;
+        }
+
+        // Closest neighbouring thread, ties going to the one with more neighbours.
+        let (x, y) = ((idx as u32 % width) as i32, (idx as u32 / width) as i32);
+        let mut candidates: Vec<(u16, u32)> = Vec::new();
+        for (dx, dy) in NEIGHBORS {
+            let (nx, ny) = (x + dx, y + dy);
+            if nx < 0 || ny < 0 || nx >= w || ny >= h {
+                continue;
+            }
+            let neighbor = grid.labels[(ny * w + nx) as usize];
+            if neighbor as usize >= grid.colors.len() {
+                continue;
+            }
+            match candidates.iter_mut().find(|(l, _)| *l == neighbor) {
+                Some(entry) => entry.1 += 1,
+                None => candidates.push((neighbor, 1)),
+            }
+        }
+        let best = candidates
+            .into_iter()
+            .map(|(candidate, count)| {
+                let de = metric.distance(labs[label as usize], labs[candidate as usize]);
+                (candidate, count, de)
+            })
+            .min_by(|a, b| a.2.total_cmp(&b.2).then_with(|| b.1.cmp(&a.1)));
+
+        if let Some((candidate, _, de)) = best {
+            if de <= max_delta_e {
+                grid.labels[idx] = candidate;
+                changed += 1;
+            }
+        }
+    }
+
+    changed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::embroidery::GridColor;
+
+    fn grid(hexes: &[(&str, &str)], labels: Vec<u16>) -> LabelGrid {
+        LabelGrid {
+            labels,
+            colors: hexes
+                .iter()
+                .map(|(code, hex)| GridColor {
+                    dmc_code: code.to_string(),
+                    marker: String::new(),
+                    hex: hex.to_string(),
+                    blend: None,
+                })
+                .collect(),
+            partials: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn report_counts_isolated_stitches_per_thread_and_block() {
+        // 12x2: a lone "B" stitch, a diagonal "B" pair, and fabric in the last column.
+        let f = FABRIC_LABEL;
+        let mut labels = vec![0u16; 24];
+        labels[2] = 1;
+        labels[6] = 1;
+        labels[19] = 1;
+        labels[11] = f;
+        labels[23] = f;
+        let pattern = grid(&[("A", "#808080"), ("B", "#000000")], labels);
+
+        let report = analyze_confetti(&pattern, 12, 2);
+        assert_eq!(report.total, 1);
+        assert_eq!(
+            report.by_color,
+            vec![ConfettiCount {
+                dmc_code: "B".to_string(),
+                isolated: 1
+            }]
+        );
+        assert_eq!((report.density.width, report.density.height), (2, 1));
+        assert!((report.density.values[0] - 0.05).abs() < 1e-6);
+        assert_eq!(report.density.values[1], 0.0);
+    }
+
+    #[test]
+    fn reduction_merges_close_confetti_and_keeps_highlights() {
+        // 5x3 mid-gray field with a near-gray speck and a white highlight.
+        let mut labels = vec![0u16; 15];
+        labels[6] = 1;
+        labels[8] = 2;
+        let mut pattern = grid(
+            &[("A", "#808080"), ("B", "#858585"), ("W", "#FFFFFF")],
+            labels,
+        );
+
+        let changed = reduce_confetti(&mut pattern, 5, 3, 10.0, ColorMetric::Ciede2000);
+        assert_eq!(changed, 1);
+        assert_eq!(pattern.labels[6], 0);
+        assert_eq!(pattern.labels[8], 2);
+        assert_eq!(analyze_confetti(&pattern, 5, 3).total, 1);
+    }
+
+    #[test]
+    fn reduction_revisits_stitches_a_recolor_brings_within_budget() {
+        // White, then a dark gray that is too far from its light-gray neighbour until that
+        // neighbour joins the mid-gray pair beside it.
+        let mut pattern = grid(
+            &[
+                ("W", "#FFFFFF"),
+                ("D", "#777777"),
+                ("L", "#9E9E9E"),
+                ("M", "#8A8A8A"),
+            ],
+            vec![0, 1, 2, 3, 3],
+        );
+
+        let changed = reduce_confetti(&mut pattern, 5, 1, 10.0, ColorMetric::Cie76);
+        assert_eq!(changed, 2);
+        assert_eq!(pattern.labels, vec![0, 3, 3, 3, 3]);
+        assert_eq!(analyze_confetti(&pattern, 5, 1).total, 1);
+    }
+}
@@ -0,0 +1,382 @@
+//! Dithering for palette assignment.
+//!
+//! Nearest-color assignment bands badly on smooth photographic gradients. These modes
+//! trade a little stitch noise for tone: error diffusion spreads each pixel's LAB error to
+//! unvisited neighbors, ordered dithering alternates between the two nearest palette
+//! entries using a Bayer threshold. Fabric pixels (mask == 0) neither receive nor spread
+//! error, so stitched areas do not bleed into the background.
+
+use crate::color_metric::ColorMetric;
+use palette::{white_point::D65, Lab};
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// How pixels are assigned to palette entries
+#[derive(Debug, Copy, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum DitherMode {
+    /// Plain nearest-color assignment
+    #[default]
+    None,
+    FloydSteinberg,
+    Atkinson,
+    /// Ordered 8x8 Bayer dithering between the two nearest palette entries
+    Bayer,
+    /// Floyd-Steinberg followed by removal of isolated single stitches
+    ConfettiLimited,
+}
+
+/// (dx, dy, weight) taps for error diffusion, for left-to-right rows
+const FLOYD_STEINBERG: &[(i32, i32, f32)] = &[
+    (1, 0, 7.0 / 16.0),
+    (-1, 1, 3.0 / 16.0),
+    (0, 1, 5.0 / 16.0),
+    (1, 1, 1.0 / 16.0),
+];
+
+/// Atkinson deliberately propagates only 6/8 of the error, keeping highlights clean
+const ATKINSON: &[(i32, i32, f32)] = &[
+    (1, 0, 1.0 / 8.0),
+    (2, 0, 1.0 / 8.0),
+    (-1, 1, 1.0 / 8.0),
+    (0, 1, 1.0 / 8.0),
+    (1, 1, 1.0 / 8.0),
+    (0, 2, 1.0 / 8.0),
+];
+
+/// Assign every pixel to a palette index using `mode`.
+pub fn dither_labels(
+    pixels: &[Lab<D65, f32>],
+    width: u32,
+    height: u32,
+    palette: &[Lab<D65, f32>],
+    mask: Option<&[u8]>,
+    mode: DitherMode,
+    metric: ColorMetric,
+) -> Vec<u16> {
+    if palette.is_empty() {
+        return vec![0; pixels.len()];
+    }
+
+    match mode {
+        DitherMode::None => pixels
+            .par_iter()
+            .map(|pixel| nearest(palette, *pixel, metric) as u16)
+            .collect(),
+        DitherMode::FloydSteinberg => diffuse_error(
+            pixels,
+            width,
+            height,
+            palette,
+            mask,
+            FLOYD_STEINBERG,
+            metric,
+        ),
+        DitherMode::Atkinson => {
+            diffuse_error(pixels, width, height, palette, mask, ATKINSON, metric)
+        }
+        DitherMode::Bayer => ordered_dither(pixels, width, palette, metric),
+        DitherMode::ConfettiLimited => {
+            let mut labels = diffuse_error(
+                pixels,
+                width,
+                height,
+                palette,
+                mask,
+                FLOYD_STEINBERG,
+                metric,
+            );
+            remove_isolated_stitches(&mut labels, width, height, mask);
+            labels
+        }
+    }
+}
+
+fn nearest(palette: &[Lab<D65, f32>], lab: Lab<D65, f32>, metric: ColorMetric) -> usize {
+    let mut best_idx = 0;
+    let mut best_dist = f32::MAX;
+    for (i, center) in palette.iter().enumerate() {
+        let dist = metric.distance(lab, *center);
+        if dist < best_dist {
+            best_dist = dist;
+            best_idx = i;
+        }
+    }
+    best_idx
+}
+
+/// Serpentine error diffusion; odd rows run right-to-left with mirrored taps.
+fn diffuse_error(
+    pixels: &[Lab<D65, f32>],
+    width: u32,
+    height: u32,
+    palette: &[Lab<D65, f32>],
+    mask: Option<&[u8]>,
+    kernel: &[(i32, i32, f32)],
+    metric: ColorMetric,
+) -> Vec<u16> {
+    let w = width as i32;
+    let h = height as i32;
+    let stitched = |idx: usize| mask.map(|m| m[idx] > 0).unwrap_or(true);
+    let mut work: Vec<[f32; 3]> = pixels.iter().map(|p| [p.l, p.a, p.b]).collect();
+    let mut labels = vec![0u16; pixels.len()];
+
+    for y in 0..h {
+        let reverse = y % 2 == 1;
+        for step in 0..w {
+            let x = if reverse { w - 1 - step } else { step };
+            let idx = (y * w + x) as usize;
+            let [l, a, b] = work[idx];
+            let current = Lab::new(
+                l.clamp(0.0, 100.0),
+                a.clamp(-128.0, 127.0),
+                b.clamp(-128.0, 127.0),
+            );
+            let label = nearest(palette, current, metric);
+            labels[idx] = label as u16;
+            if !stitched(idx) {
+                continue;
+            }
+
+            let chosen = palette[label];
+            let error = [
+                current.l - chosen.l,
+                current.a - chosen.a,
+                current.b - chosen.b,
+            ];
+            for &(dx, dy, weight) in kernel {
+                let nx = if reverse { x - dx } else { x + dx };
+                let ny = y + dy;
+                if nx < 0 || nx >= w || ny >= h {
+                    continue;
+                }
+                let nidx = (ny * w + nx) as usize;
+                if !stitched(nidx) {
+                    continue;
+                }
+                for (channel, err) in work[nidx].iter_mut().zip(error) {
+                    *channel += err * weight;
+                }
+            }
+        }
+    }
+
+    labels
+}
+
+/// Pick between the two nearest entries, favouring the second by how close it is.
+fn ordered_dither(
+    pixels: &[Lab<D65, f32>],
+    width: u32,
+    palette: &[Lab<D65, f32>],
+    metric: ColorMetric,
+) -> Vec<u16> {
+    pixels
+        .par_iter()
+        .enumerate()
+        .map(|(idx, pixel)| {
+            let mut first = (0usize, f32::MAX);
+            let mut second = (0usize, f32::MAX);
+            for (i, center) in palette.iter().enumerate() {
+                let dist = metric.distance(*pixel, *center);
+                if dist < first.1 {
+                    second = first;
+                    first = (i, dist);
+                } else if dist < second.1 {
+                    second = (i, dist);
+                }
+            }
+            if second.1 == f32::MAX {
+                return first.0 as u16;
+            }
+
+            // 0 when the pixel sits on `first`, 0.5 when it is halfway to `second`.
+            let mix = first.1 / (first.1 + second.1).max(f32::EPSILON);
+            let x = idx as u32 % width;
+            let y = idx as u32 / width;
+            if bayer_threshold(x, y) < mix {
+                second.0 as u16
+            } else {
+                first.0 as u16
+            }
+        })
+        .collect()
+}
+
+/// Normalized 8x8 Bayer matrix value in (0, 1).
+fn bayer_threshold(x: u32, y: u32) -> f32 {
+    let xy = x ^ y;
+    let mut value = 0u32;
+    for bit in 0..3 {
+        value = (value << 2) | (((xy >> bit) & 1) << 1) | ((y >> bit) & 1);
+    }
+    (value as f32 + 0.5) / 64.0
+}
+
+/// Replace stitches with no same-colored 4-neighbor by the most common neighbor color.
+///
+/// Runs in place in raster order so a checkerboard resolves instead of flipping wholesale.
+fn remove_isolated_stitches(labels: &mut [u16], width: u32, height: u32, mask: Option<&[u8]>) {
+    let w = width as i32;
+    let h = height as i32;
+    let stitched = |idx: usize| mask.map(|m| m[idx] > 0).unwrap_or(true);
+
+    for y in 0..h {
+        for x in 0..w {
+            let idx = (y * w + x) as usize;
+            if !stitched(idx) {
+                continue;
+            }
+            let mut neighbor_counts: HashMap<u16, u32> = HashMap::new();
+            let mut isolated = true;
+            for (dx, dy) in [(-1i32, 0i32), (1, 0), (0, -1), (0, 1)] {
+                let nx = x + dx;
+                let ny = y + dy;
+                if nx < 0 || nx >= w || ny < 0 || ny >= h {
+                    continue;
+                }
+                let nidx = (ny * w + nx) as usize;
+                if !stitched(nidx) {
+                    continue;
+                }
+                if labels[nidx] == labels[idx] {
+                    isolated = false;
+                    break;
+                }
+                *neighbor_counts.entry(labels[nidx]).or_insert(0) += 1;
+            }
+            if !isolated {
+                continue;
+            }
+            if let Some((label, _)) = neighbor_counts
+                .into_iter()
+                .max_by(|a, b| a.1.cmp(&b.1).then_with(|| b.0.cmp(&a.0)))
+            {
+                labels[idx] = label;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn gray_ramp(width: u32) -> Vec<Lab<D65, f32>> {
+        (0..width)
+            .map(|x| Lab::new(100.0 * x as f32 / (width - 1) as f32, 0.0, 0.0))
+            .collect::<Vec<_>>()
+            .repeat(4)
+    }
+
+    #[test]
+    fn error_diffusion_mixes_tones_that_nearest_color_bands() {
+        let width = 32;
+        let pixels = gray_ramp(width);
+        let palette = [Lab::new(0.0, 0.0, 0.0), Lab::new(100.0, 0.0, 0.0)];
+
+        let plain = dither_labels(
+            &pixels,
+            width,
+            4,
+            &palette,
+            None,
+            DitherMode::None,
+            ColorMetric::Ciede2000,
+        );
+        let switches = |labels: &[u16]| {
+            labels[..width as usize]
+                .windows(2)
+                .filter(|w| w[0] != w[1])
+                .count()
+        };
+        assert_eq!(switches(&plain), 1);
+
+        for mode in [
+            DitherMode::FloydSteinberg,
+            DitherMode::Atkinson,
+            DitherMode::Bayer,
+        ] {
+            let dithered = dither_labels(
+                &pixels,
+                width,
+                4,
+                &palette,
+                None,
+                mode,
+                ColorMetric::Ciede2000,
+            );
+            assert!(switches(&dithered) > 2, "{:?} did not dither", mode);
+        }
+    }
+
+    #[test]
+    fn fabric_pixels_do_not_spread_error() {
+        // Two rows of near-white fabric above one row of mid-gray stitches.
+        let width = 8usize;
+        let mut pixels = vec![Lab::new(90.0, 0.0, 0.0); width * 2];
+        pixels.extend(vec![Lab::new(50.0, 0.0, 0.0); width]);
+        let palette = [Lab::new(0.0, 0.0, 0.0), Lab::new(100.0, 0.0, 0.0)];
+        let mut mask = vec![0u8; pixels.len()];
+        mask[width * 2..].fill(255);
+
+        let with_fabric = dither_labels(
+            &pixels,
+            width as u32,
+            3,
+            &palette,
+            Some(&mask),
+            DitherMode::FloydSteinberg,
+            ColorMetric::Ciede2000,
+        );
+        let stitched_only = dither_labels(
+            &pixels[width * 2..],
+            width as u32,
+            1,
+            &palette,
+            None,
+            DitherMode::FloydSteinberg,
+            ColorMetric::Ciede2000,
+        );
+        assert_eq!(&with_fabric[width * 2..], stitched_only.as_slice());
+    }
+
+    #[test]
+    fn confetti_limited_leaves_no_isolated_stitches() {
+        let width = 16;
+        let pixels = gray_ramp(width);
+        let palette = [Lab::new(0.0, 0.0, 0.0), Lab::new(100.0, 0.0, 0.0)];
+        let labels = dither_labels(
+            &pixels,
+            width,
+            4,
+            &palette,
+            None,
+            DitherMode::ConfettiLimited,
+            ColorMetric::Ciede2000,
+        );
+
+        let mut bayer: Vec<u32> = (0..64)
+            .map(|i| (bayer_threshold(i % 8, i / 8) * 64.0) as u32)
+            .collect();
+        bayer.sort_unstable();
+        assert_eq!(bayer, (0..64).collect::<Vec<_>>());
+
+        for y in 0..4i32 {
+            for x in 0..width as i32 {
+                let idx = (y * width as i32 + x) as usize;
+                let has_twin = [(-1i32, 0i32), (1, 0), (0, -1), (0, 1)]
+                    .iter()
+                    .any(|(dx, dy)| {
+                        let (nx, ny) = (x + dx, y + dy);
+                        nx >= 0
+                            && nx < width as i32
+                            && (0..4).contains(&ny)
+                            && labels[(ny * width as i32 + nx) as usize] == labels[idx]
+                    });
+                assert!(has_twin, "isolated stitch at {},{}", x, y);
+            }
+        }
+    }
+}
@@ -3,21 +3,33 @@
 //! This module offloads CPU-intensive image processing from the browser to native Rust,
 //! leveraging rayon for parallel processing across all CPU cores.
 
-use palette::{color_difference::Ciede2000, white_point::D65, FromColor, Lab, Srgb};
+use crate::adjust::{apply_adjustments, ImageAdjustments};
+use crate::backstitch::{build_backstitch, BackstitchConfig, BackstitchLine};
+use crate::color_metric::ColorMetric;
+use crate::confetti::{
+    analyze_confetti, reduce_confetti, ConfettiConfig, ConfettiReport, DEFAULT_CONFETTI_DELTA_E,
+};
+use crate::dither::{dither_labels, DitherMode};
+use crate::fabric::{alpha_mask, drop_fabric_matches, FabricConfig};
+use crate::fractional::{split_diagonal_cells, PartialCell, StitchCorner, StitchKind};
+use crate::grid::{resample_lab, resample_mask, PhysicalSize, StitchGridConfig};
+use crate::pixel_art::{
+    detect_scale, downsample, downsample_image, exact_colors, PixelArtConfig, PixelArtReport,
+    ThreadCollision,
+};
+use crate::quantize::{median_cut, octree, thread_direct, wu, DirectThreads, Quantizer};
+use crate::saliency::{training_weights, weighted_sample, TrainingWeight};
+use crate::symbols::assign_symbols;
+use crate::threads::{
+    match_inventory, InventoryMatch, ThreadBrand, ThreadCatalog, ThreadColor,
+    DEFAULT_MAX_BLEND_DELTA_E,
+};
+use palette::{white_point::D65, FromColor, Lab, Srgb};
 use rayon::prelude::*;
 use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
 use std::collections::HashMap;
-use std::sync::OnceLock;
-
-/// DMC thread color entry with precomputed LAB values
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct DmcThread {
-    pub code: String,
-    pub name: String,
-    pub hex: String,
-    pub rgb: [u8; 3],
-    pub lab: [f32; 3],
-}
+use std::sync::Arc;
 
 /// A single stitch in the pattern grid
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -27,6 +39,211 @@ pub struct Stitch {
     pub dmc_code: String,
     pub marker: String,
     pub hex: String,
+    /// Both component codes when the stitch uses one strand each of two threads
+    #[serde(default)]
+    pub blend: Option<[String; 2]>,
+    #[serde(default)]
+    pub kind: StitchKind,
+    /// Corner a partial stitch fills toward; `None` for whole crosses
+    #[serde(default)]
+    pub corner: Option<StitchCorner>,
+}
+
+/// Largest palette `process_pattern` will quantize to
+pub const MAX_COLOR_COUNT: u32 = 256;
+
+/// Label of a [`LabelGrid`] cell left as bare fabric
+pub const FABRIC_LABEL: u16 = u16::MAX;
+
+/// Thread drawn for one label of a [`LabelGrid`]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GridColor {
+    pub dmc_code: String,
+    pub marker: String,
+    pub hex: String,
+    #[serde(default)]
+    pub blend: Option<[String; 2]>,
+}
+
+/// Compact stitch grid: one label per cell in row-major order, indexing `colors`
+///
+/// Fabric cells hold [`FABRIC_LABEL`]. A 300x300 grid is 180KB of labels instead of
+/// 90,000 [`Stitch`] objects.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct LabelGrid {
+    pub labels: Vec<u16>,
+    pub colors: Vec<GridColor>,
+    /// Cells split into fractional stitches, sorted by cell
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub partials: Vec<PartialCell>,
+}
+
+/// How much one label of a [`LabelGrid`] is stitched
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct LabelUsage {
+    /// Stitches of any kind
+    pub stitches: u32,
+    /// Of `stitches`, how many are fractional
+    pub partial: u32,
+    /// Area in whole-cross equivalents
+    pub cells: f32,
+}
+
+impl LabelGrid {
+    /// Build a grid from per-cell stitches, one label per distinct code and hex.
+    pub fn from_stitches(width: u32, height: u32, stitches: &[Stitch]) -> Self {
+        let mut labels = vec![FABRIC_LABEL; (width * height) as usize];
+        let mut colors: Vec<GridColor> = Vec::new();
+        let mut label_by_key: HashMap<(&str, &str), u16> = HashMap::new();
+        let mut corners: HashMap<u32, StitchCorner> = HashMap::new();
+        let mut quarters: HashMap<u32, u16> = HashMap::new();
+        for stitch in stitches {
+            if stitch.x >= width
+                || stitch.y >= height
+                || stitch.dmc_code.eq_ignore_ascii_case("fabric")
+            {
+                continue;
+            }
+            let label = *label_by_key
+                .entry((stitch.dmc_code.as_str(), stitch.hex.as_str()))
+                .or_insert_with(|| {
+                    colors.push(GridColor {
+                        dmc_code: stitch.dmc_code.clone(),
+                        marker: stitch.marker.clone(),
+                        hex: stitch.hex.clone(),
+                        blend: stitch.blend.clone(),
+                    });
+                    (colors.len() - 1) as u16
+                });
+            let cell = stitch.y * width + stitch.x;
+            match (stitch.kind, stitch.corner) {
+                (StitchKind::Quarter, _) => {
+                    quarters.insert(cell, label);
+                    continue;
+                }
+                (StitchKind::Half | StitchKind::ThreeQuarter, Some(corner)) => {
+                    corners.insert(cell, corner);
+                }
+                _ => {}
+            }
+            labels[cell as usize] = label;
+        }
+
+        let mut partials: Vec<PartialCell> = corners
+            .into_iter()
+            .map(|(cell, corner)| PartialCell {
+                cell,
+                corner,
+                secondary: quarters.get(&cell).copied().unwrap_or(FABRIC_LABEL),
+            })
+            .collect();
+        partials.sort_by_key(|partial| partial.cell);
+        Self {
+            labels,
+            colors,
+            partials,
+        }
+    }
+
+    /// Split of cell `idx`, if it holds fractional stitches.
+    pub fn partial(&self, idx: usize) -> Option<&PartialCell> {
+        self.partials
+            .binary_search_by_key(&(idx as u32), |partial| partial.cell)
+            .ok()
+            .map(|pos| &self.partials[pos])
+    }
+
+    /// Stitch counts and area per label, fractional stitches included.
+    pub fn usage(&self) -> Vec<LabelUsage> {
+        let mut usage = vec![LabelUsage::default(); self.colors.len()];
+        for &label in &self.labels {
+            if let Some(entry) = usage.get_mut(label as usize) {
+                entry.stitches += 1;
+                entry.cells += 1.0;
+            }
+        }
+        for partial in &self.partials {
+            let Some(&primary) = self.labels.get(partial.cell as usize) else {
+                continue;
+            };
+            if let Some(entry) = usage.get_mut(primary as usize) {
+                entry.partial += 1;
+                entry.cells -= 1.0 - partial.primary_kind().cell_fraction();
+            }
+            if let Some(entry) = usage.get_mut(partial.secondary as usize) {
+                entry.stitches += 1;
+                entry.partial += 1;
+                entry.cells += StitchKind::Quarter.cell_fraction();
+            }
+        }
+        usage
+    }
+
+    /// Stitches of every thread, fractional stitches included; the legend counts sum to this.
+    pub fn stitch_count(&self) -> u32 {
+        self.usage().iter().map(|usage| usage.stitches).sum()
+    }
+
+    /// Thread at cell `idx`, or `None` for fabric.
+    pub fn color(&self, idx: usize) -> Option<&GridColor> {
+        self.colors.get(*self.labels.get(idx)? as usize)
+    }
+
+    /// Expand into one [`Stitch`] per cell in row-major order.
+    ///
+    /// The quarter stitches of split cells follow after the last cell, so index `y * width + x`
+    /// still addresses cell (x, y). Fabric cells are drawn in `fabric_hex`.
+    pub fn to_stitches(&self, width: u32, fabric_hex: &str) -> Vec<Stitch> {
+        let stitch = |idx: usize, color: Option<&GridColor>, kind, corner| {
+            let (x, y) = ((idx as u32) % width, (idx as u32) / width);
+            match color {
+                Some(color) => Stitch {
+                    x,
+                    y,
+                    dmc_code: color.dmc_code.clone(),
+                    marker: color.marker.clone(),
+                    hex: color.hex.clone(),
+                    blend: color.blend.clone(),
+                    kind,
+                    corner,
+                },
+                None => Stitch {
+                    x,
+                    y,
+                    dmc_code: "Fabric".to_string(),
+                    marker: String::new(),
+                    hex: fabric_hex.to_string(),
+                    blend: None,
+                    kind: StitchKind::Full,
+                    corner: None,
+                },
+            }
+        };
+
+        let mut stitches: Vec<Stitch> = (0..self.labels.len())
+            .into_par_iter()
+            .map(|i| match self.partial(i) {
+                Some(partial) => stitch(
+                    i,
+                    self.color(i),
+                    partial.primary_kind(),
+                    Some(partial.corner),
+                ),
+                None => stitch(i, self.color(i), StitchKind::Full, None),
+            })
+            .collect();
+        for partial in &self.partials {
+            if let Some(color) = self.colors.get(partial.secondary as usize) {
+                stitches.push(stitch(
+                    partial.cell as usize,
+                    Some(color),
+                    StitchKind::Quarter,
+                    Some(partial.corner.opposite()),
+                ));
+            }
+        }
+        stitches
+    }
 }
 
 /// DMC metadata for legend entries
@@ -49,10 +266,37 @@ pub struct ColorMapping {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct LegendEntry {
     pub dmc_code: String,
+    /// Catalog the code belongs to; `None` for raw quantized colors
+    #[serde(default)]
+    pub brand: Option<ThreadBrand>,
     pub name: String,
     pub hex: String,
+    /// Stitches of any kind, each fractional stitch counting as one
     pub stitch_count: u32,
+    /// Share of the stitched area, fractional stitches weighted by the cell they cover
     pub coverage: f32,
+    /// Of `stitch_count`, how many are half, quarter or three-quarter stitches
+    #[serde(default)]
+    pub partial_count: u32,
+    /// Chart symbol; unique across the legend
+    #[serde(default)]
+    pub marker: String,
+    /// Both component codes for a blended (tweeded) entry
+    #[serde(default)]
+    pub blend: Option<[String; 2]>,
+}
+
+/// Distinct threads `process_pattern` was asked for and produced
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct ThreadCountReport {
+    /// `ProcessingConfig::color_count`
+    pub requested: u32,
+    /// Threads in the legend
+    pub achieved: u32,
+    /// Extra quantization passes run to replace clusters that matched the same thread
+    pub refits: u32,
+    /// Why fewer threads than requested came out
+    pub shortfall: Option<String>,
 }
 
 /// Complete pattern result returned to the frontend
@@ -60,15 +304,78 @@ pub struct LegendEntry {
 pub struct PatternResult {
     pub width: u32,
     pub height: u32,
+    /// Per-cell stitches; empty unless `ProcessingConfig::expand_stitches` was set
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub stitches: Vec<Stitch>,
+    /// Compact form of the stitch grid
+    #[serde(default)]
+    pub grid: LabelGrid,
     pub palette: Vec<String>,
     pub dmc_palette: Vec<String>,
+    /// Catalog that `dmc_palette` and the legend codes were matched against
+    #[serde(default)]
+    pub brand: ThreadBrand,
+    /// Codes outside `ProcessingConfig::owned_threads` that the pattern uses
+    #[serde(default)]
+    pub suggested_purchases: Vec<String>,
+    /// Finished size on fabric when `ProcessingConfig::grid` was set
+    #[serde(default)]
+    pub physical_size: Option<PhysicalSize>,
+    /// Outline lines drawn over the stitches when `ProcessingConfig::backstitch` was set
+    #[serde(default)]
+    pub backstitch: Vec<BackstitchLine>,
+    /// Isolated single stitches left in the pattern
+    #[serde(default)]
+    pub confetti: ConfettiReport,
+    /// Fabric color unstitched cells show
+    #[serde(default = "default_fabric_hex")]
+    pub fabric_hex: String,
+    /// Distinct threads asked for and produced when the pattern was processed
+    #[serde(default)]
+    pub thread_count: ThreadCountReport,
+    /// Detected scale and thread collisions when `ProcessingConfig::pixel_art` was set
+    #[serde(default)]
+    pub pixel_art: Option<PixelArtReport>,
     pub legend: Vec<LegendEntry>,
     pub color_mappings: Vec<ColorMapping>,
     pub total_stitches: u32,
     pub processing_time_ms: u64,
 }
 
+impl PatternResult {
+    /// Re-run symbol allocation and copy the symbols into the legend and any stitches.
+    pub fn reassign_symbols(&mut self) {
+        self.grid = self.label_grid().into_owned();
+        assign_symbols(&mut self.grid, self.width);
+        for entry in &mut self.legend {
+            if let Some(color) = self
+                .grid
+                .colors
+                .iter()
+                .find(|c| c.dmc_code == entry.dmc_code)
+            {
+                entry.marker = color.marker.clone();
+            }
+        }
+        if !self.stitches.is_empty() {
+            self.stitches = self.grid.to_stitches(self.width, &self.fabric_hex);
+        }
+    }
+
+    /// The label grid, rebuilt from `stitches` when the pattern only carries those.
+    pub fn label_grid(&self) -> Cow<'_, LabelGrid> {
+        if !self.grid.labels.is_empty() || self.stitches.is_empty() {
+            Cow::Borrowed(&self.grid)
+        } else {
+            Cow::Owned(LabelGrid::from_stitches(
+                self.width,
+                self.height,
+                &self.stitches,
+            ))
+        }
+    }
+}
+
 /// Processing configuration
 #[derive(Debug, Clone, Deserialize)]
 pub struct ProcessingConfig {
@@ -77,6 +384,92 @@ pub struct ProcessingConfig {
     pub smoothing_amount: f32,
     pub simplify_amount: f32,
     pub min_region_size: u32,
+    /// Thread catalog used for palette matching
+    #[serde(default)]
+    pub thread_brand: ThreadBrand,
+    /// Imported thread library to match against instead of `thread_brand`
+    #[serde(default)]
+    pub custom_library_id: Option<String>,
+    /// Thread codes the user owns; when non-empty, matching is restricted to them
+    #[serde(default)]
+    pub owned_threads: Vec<String>,
+    /// With `owned_threads`, allow up to this many threads outside the stash
+    #[serde(default)]
+    pub max_purchases: Option<u32>,
+    /// Threads that must be in the palette; they seed k-means and keep their exact color
+    #[serde(default)]
+    pub locked_threads: Vec<String>,
+    /// Threads that must never be chosen
+    #[serde(default)]
+    pub excluded_threads: Vec<String>,
+    /// Also match against two-thread blends mixed one strand each
+    #[serde(default)]
+    pub enable_blends: bool,
+    /// Widest Delta-E between blend components; defaults to `DEFAULT_MAX_BLEND_DELTA_E`
+    #[serde(default)]
+    pub max_blend_delta_e: Option<f32>,
+    /// Pixel-to-palette assignment; anything but `None` skips small-region cleanup
+    #[serde(default)]
+    pub dither: DitherMode,
+    /// Color difference used for clustering, region cleanup and thread matching
+    #[serde(default)]
+    pub color_metric: ColorMetric,
+    /// Resample to a stitch grid sized for the fabric; `None` keeps one stitch per pixel
+    #[serde(default)]
+    pub grid: Option<StitchGridConfig>,
+    /// Also return the legacy one-`Stitch`-per-cell form in `PatternResult::stitches`
+    #[serde(default)]
+    pub expand_stitches: bool,
+    /// Generate backstitch lines along color boundaries
+    #[serde(default)]
+    pub backstitch: Option<BackstitchConfig>,
+    /// Split cells on diagonal color edges into fractional stitches; needs `grid` so each
+    /// stitch covers several source pixels
+    #[serde(default)]
+    pub fractional_stitches: bool,
+    /// Recolor isolated stitches to close neighbouring threads; `None` only reports them
+    #[serde(default)]
+    pub confetti: Option<ConfettiConfig>,
+    /// Levels, color and sharpening applied to the decoded image before quantization
+    #[serde(default)]
+    pub adjustments: Option<ImageAdjustments>,
+    /// Fabric color, transparency handling and fabric-matching threads
+    #[serde(default)]
+    pub fabric: FabricConfig,
+    /// How pixels are reduced to the palette
+    #[serde(default)]
+    pub quantizer: Quantizer,
+    /// Automatic weighting of palette training samples, on top of any mask weights
+    #[serde(default)]
+    pub training_weight: TrainingWeight,
+    /// Masked parts of the image quantized with their own budget; the settings above cover
+    /// everything else
+    #[serde(default)]
+    pub regions: Vec<RegionBudget>,
+    /// Stitch pixel art as drawn: one stitch per art pixel and one thread per exact color,
+    /// with no quantization, dithering, region cleanup or fractional stitches. `grid` then
+    /// only sets the fabric for the finished size, `color_count` and locked threads are
+    /// unused, and `regions` are not supported.
+    #[serde(default)]
+    pub pixel_art: Option<PixelArtConfig>,
+}
+
+/// Palette budget, quantizer and dither for one masked region of the image.
+///
+/// Each region is quantized on its own and the results share one legend, so a thread two
+/// regions both pick is listed once. Locked threads are available in every region.
+#[derive(Debug, Clone, Deserialize)]
+pub struct RegionBudget {
+    /// Same layout as the `mask` argument of [`process_pattern`]; values above zero belong to
+    /// the region and weight its palette training
+    pub mask: Vec<u8>,
+    pub color_count: u32,
+    /// Defaults to `ProcessingConfig::quantizer`
+    #[serde(default)]
+    pub quantizer: Option<Quantizer>,
+    /// Defaults to `ProcessingConfig::dither`
+    #[serde(default)]
+    pub dither: Option<DitherMode>,
 }
 
 impl Default for ProcessingConfig {
@@ -87,289 +480,59 @@ impl Default for ProcessingConfig {
             smoothing_amount: 0.3,
             simplify_amount: 0.2,
             min_region_size: 4,
+            thread_brand: ThreadBrand::Dmc,
+            custom_library_id: None,
+            owned_threads: Vec::new(),
+            max_purchases: None,
+            locked_threads: Vec::new(),
+            excluded_threads: Vec::new(),
+            enable_blends: false,
+            max_blend_delta_e: None,
+            dither: DitherMode::None,
+            color_metric: ColorMetric::Ciede2000,
+            grid: None,
+            expand_stitches: false,
+            backstitch: None,
+            fractional_stitches: false,
+            confetti: None,
+            adjustments: None,
+            fabric: FabricConfig::default(),
+            quantizer: Quantizer::Kmeans,
+            training_weight: TrainingWeight::Uniform,
+            regions: Vec::new(),
+            pixel_art: None,
         }
     }
 }
 
-/// Complete DMC thread palette (~500 official colors)
-/// Each entry: (code, name, hex)
-const DMC_PALETTE: &[(&str, &str, &str)] = &[
-    // Whites & Neutrals
-    ("B5200", "Snow White", "#FFFFFF"),
-    ("White", "White", "#FEFEFE"),
-    ("Ecru", "Ecru", "#F0EBD5"),
-    ("822", "Light Beige Gray", "#E7DECC"),
-    ("644", "Medium Beige Gray", "#D9D3C3"),
-    ("642", "Dark Beige Gray", "#C2B9A6"),
-    ("640", "Very Dark Beige Gray", "#9B8F7E"),
-    ("3072", "Very Light Beaver Gray", "#E1E5DE"),
-    ("648", "Light Beaver Gray", "#BCC3BB"),
-    ("647", "Medium Beaver Gray", "#A9B0A8"),
-    ("646", "Dark Beaver Gray", "#8D9691"),
-    ("645", "Very Dark Beaver Gray", "#6C7670"),
-    // Blacks & Grays
-    ("310", "Black", "#000000"),
-    ("3799", "Very Dark Pewter Gray", "#5B5F5F"),
-    ("413", "Dark Pewter Gray", "#656666"),
-    ("3787", "Dark Brown Gray", "#6B675E"),
-    ("762", "Very Light Pearl Gray", "#E6E6E6"),
-    ("415", "Pearl Gray", "#D3D3D3"),
-    ("318", "Light Steel Gray", "#ADB0AE"),
-    ("414", "Dark Steel Gray", "#8A8A8A"),
-    ("317", "Pewter Gray", "#6B6D6D"),
-    ("535", "Very Light Ash Gray", "#696959"),
-    ("3024", "Very Light Brown Gray", "#D0CCBE"),
-    ("3023", "Light Brown Gray", "#B5A588"),
-    // Reds
-    ("666", "Bright Red", "#EC2130"),
-    ("321", "Red", "#CE1938"),
-    ("304", "Medium Red", "#B11731"),
-    ("498", "Dark Red", "#A81428"),
-    ("816", "Garnet", "#91182E"),
-    ("815", "Medium Garnet", "#7C1D2B"),
-    ("814", "Dark Garnet", "#6D1329"),
-    ("760", "Salmon", "#F5BEC2"),
-    ("3712", "Medium Salmon", "#EA9CA3"),
-    ("3328", "Dark Salmon", "#E07681"),
-    ("347", "Very Dark Salmon", "#BF1733"),
-    ("353", "Peach", "#FECDCD"),
-    ("352", "Light Coral", "#FBB9AA"),
-    ("351", "Coral", "#EA8579"),
-    ("350", "Medium Coral", "#E34948"),
-    ("349", "Dark Coral", "#C81732"),
-    ("817", "Very Dark Coral Red", "#BA1730"),
-    // Pinks
-    ("818", "Baby Pink", "#FFD9DB"),
-    ("963", "Ultra Very Light Dusty Rose", "#FFCCD1"),
-    ("3716", "Very Light Dusty Rose", "#FFBAC7"),
-    ("962", "Medium Dusty Rose", "#E97D8B"),
-    ("961", "Dark Dusty Rose", "#CE486E"),
-    ("3833", "Light Raspberry", "#E95077"),
-    ("3832", "Medium Raspberry", "#D13D6F"),
-    ("3831", "Dark Raspberry", "#B0194B"),
-    ("3350", "Ultra Dark Dusty Rose", "#B52D5C"),
-    ("150", "Ultra Very Light Dusty Rose", "#F8D5D8"),
-    ("151", "Very Light Dusty Rose", "#EFB1BA"),
-    ("152", "Medium Light Shell Pink", "#DD88A0"),
-    ("3354", "Light Dusty Rose", "#D887A6"),
-    ("3733", "Dusty Rose", "#CD5E8D"),
-    ("3731", "Very Dark Dusty Rose", "#C0476C"),
-    // Oranges
-    ("3824", "Light Apricot", "#FECABE"),
-    ("3341", "Apricot", "#FFAB8A"),
-    ("3340", "Medium Apricot", "#FF8262"),
-    ("608", "Bright Orange", "#FF6F30"),
-    ("606", "Bright Orange-Red", "#FA3F1B"),
-    ("970", "Light Pumpkin", "#FF901F"),
-    ("971", "Pumpkin", "#FF8600"),
-    ("972", "Deep Canary", "#FFB900"),
-    ("3853", "Dark Autumn Gold", "#F59B5A"),
-    ("3854", "Medium Autumn Gold", "#F68A5C"),
-    ("3855", "Light Autumn Gold", "#FBBF99"),
-    ("722", "Light Orange Spice", "#F6A667"),
-    ("720", "Dark Orange Spice", "#E94A07"),
-    ("721", "Medium Orange Spice", "#F25D3D"),
-    ("947", "Burnt Orange", "#FF5F01"),
-    // Yellows
-    ("445", "Light Lemon", "#FFFDDB"),
-    ("307", "Lemon", "#FFE600"),
-    ("973", "Bright Canary", "#FFE529"),
-    ("444", "Dark Lemon", "#FFE00B"),
-    ("3078", "Very Light Golden Yellow", "#FFF8DC"),
-    ("727", "Very Light Topaz", "#FFF785"),
-    ("726", "Light Topaz", "#FFD747"),
-    ("725", "Topaz", "#FFC723"),
-    ("3820", "Dark Straw", "#DDB900"),
-    ("783", "Medium Topaz", "#D68700"),
-    ("782", "Dark Topaz", "#CB7800"),
-    ("781", "Very Dark Topaz", "#985F00"),
-    ("780", "Ultra Very Dark Topaz", "#8C5400"),
-    ("676", "Light Old Gold", "#ECBB5C"),
-    ("729", "Medium Old Gold", "#D1A140"),
-    ("680", "Dark Old Gold", "#B98C27"),
-    ("3829", "Very Dark Old Gold", "#9F6F00"),
-    ("3822", "Light Straw", "#F0DE9C"),
-    ("3821", "Straw", "#E0C47A"),
-    // Greens
-    ("704", "Bright Chartreuse", "#CCF500"),
-    ("703", "Chartreuse", "#A6D700"),
-    ("702", "Kelly Green", "#86B500"),
-    ("701", "Light Green", "#5D9F00"),
-    ("700", "Bright Green", "#2E7D09"),
-    ("699", "Green", "#136C00"),
-    ("907", "Light Parrot Green", "#D0F200"),
-    ("906", "Medium Parrot Green", "#9DB700"),
-    ("905", "Dark Parrot Green", "#6F9800"),
-    ("904", "Very Dark Parrot Green", "#4B7800"),
-    ("164", "Light Forest Green", "#C7D9AD"),
-    ("989", "Forest Green", "#88A84C"),
-    ("988", "Medium Forest Green", "#77923C"),
-    ("987", "Dark Forest Green", "#5F7D2D"),
-    ("986", "Very Dark Forest Green", "#466B28"),
-    ("3348", "Light Yellow Green", "#D8E79E"),
-    ("3347", "Medium Yellow Green", "#A3C85E"),
-    ("3346", "Hunter Green", "#77A058"),
-    ("3345", "Dark Hunter Green", "#66834A"),
-    ("772", "Very Light Yellow Green", "#E4F3CC"),
-    ("3364", "Pine Green", "#546E4D"),
-    ("320", "Medium Pistachio Green", "#8D9E57"),
-    ("367", "Dark Pistachio Green", "#6B7B3C"),
-    ("319", "Very Dark Pistachio Green", "#40502C"),
-    // Teals & Aquas
-    ("964", "Light Seagreen", "#C1E2DC"),
-    ("959", "Medium Seagreen", "#89C9BC"),
-    ("958", "Dark Seagreen", "#52B5A3"),
-    ("3812", "Very Dark Seagreen", "#2E917F"),
-    ("3811", "Very Light Turquoise", "#C2E3DF"),
-    ("598", "Light Turquoise", "#9FCECE"),
-    ("597", "Turquoise", "#6CB5BD"),
-    ("3810", "Dark Turquoise", "#4D999A"),
-    ("3809", "Very Dark Turquoise", "#328082"),
-    ("928", "Very Light Gray Green", "#E7EDE7"),
-    ("927", "Light Gray Green", "#BFCEC4"),
-    ("926", "Medium Gray Green", "#98B3A6"),
-    ("3768", "Dark Gray Green", "#5B7B6B"),
-    // Blues
-    ("3841", "Pale Baby Blue", "#CEDEED"),
-    ("3840", "Light Baby Blue", "#A8C9E8"),
-    ("3839", "Medium Baby Blue", "#6495C8"),
-    ("3838", "Dark Baby Blue", "#3A75AE"),
-    ("800", "Pale Delft Blue", "#C9E4F2"),
-    ("809", "Delft Blue", "#94B7D5"),
-    ("799", "Medium Delft Blue", "#7393B7"),
-    ("798", "Dark Delft Blue", "#5174A0"),
-    ("797", "Royal Blue", "#13438D"),
-    ("796", "Dark Royal Blue", "#123071"),
-    ("3325", "Light Baby Blue", "#BFD8EB"),
-    ("3755", "Baby Blue", "#8DADD3"),
-    ("334", "Medium Baby Blue", "#5D8AB8"),
-    ("322", "Dark Baby Blue", "#2F5580"),
-    ("312", "Very Dark Baby Blue", "#13416D"),
-    ("311", "Medium Navy Blue", "#1C3A5C"),
-    ("336", "Navy Blue", "#13294B"),
-    ("823", "Dark Navy Blue", "#13294B"),
-    ("939", "Very Dark Navy Blue", "#13213C"),
-    // Purples
-    ("3747", "Very Light Blue Violet", "#E3E5EC"),
-    ("341", "Light Blue Violet", "#B5CAE6"),
-    ("3746", "Dark Blue Violet", "#948FCC"),
-    ("333", "Very Dark Blue Violet", "#6E5B9B"),
-    ("3837", "Ultra Dark Lavender", "#6D417E"),
-    ("211", "Light Lavender", "#E8D8EA"),
-    ("210", "Medium Lavender", "#C68FB9"),
-    ("209", "Dark Lavender", "#9C4E97"),
-    ("208", "Very Dark Lavender", "#7F2A7B"),
-    ("3836", "Light Grape", "#B78BC0"),
-    ("3835", "Medium Grape", "#924C8F"),
-    ("3834", "Dark Grape", "#742A6E"),
-    ("154", "Very Dark Grape", "#551839"),
-    ("153", "Very Light Violet", "#E8CCDF"),
-    ("3743", "Very Light Antique Violet", "#E3D7E2"),
-    ("3042", "Light Antique Violet", "#D7BFD4"),
-    ("3041", "Medium Antique Violet", "#C6A9C1"),
-    ("3740", "Dark Antique Violet", "#A17896"),
-    // Browns
-    ("3865", "Winter White", "#FAF9F4"),
-    ("739", "Ultra Very Light Tan", "#F5EDD3"),
-    ("738", "Very Light Tan", "#EBCBA1"),
-    ("437", "Light Tan", "#D9A964"),
-    ("436", "Tan", "#C68638"),
-    ("435", "Very Light Brown", "#945B25"),
-    ("434", "Light Brown", "#944B14"),
-    ("433", "Medium Brown", "#85511F"),
-    ("801", "Dark Coffee Brown", "#693F17"),
-    ("898", "Very Dark Coffee Brown", "#5C3A1F"),
-    ("938", "Ultra Dark Coffee Brown", "#4A2812"),
-    ("3371", "Black Brown", "#301904"),
-    ("543", "Ultra Very Light Beige Brown", "#F0DBC8"),
-    ("3864", "Light Mocha Beige", "#C9A992"),
-    ("3863", "Medium Mocha Beige", "#A4826A"),
-    ("3862", "Dark Mocha Beige", "#856551"),
-    ("3861", "Light Cocoa", "#A07959"),
-    ("3860", "Cocoa", "#78503B"),
-    ("3031", "Very Dark Mocha Brown", "#54372A"),
-    ("3021", "Very Dark Brown Gray", "#5B4733"),
-    // Terra Cottas & Specialty
-    ("948", "Very Light Peach", "#FED9C7"),
-    ("754", "Light Peach", "#F9CEB9"),
-    ("945", "Tawny", "#F6C199"),
-    ("3778", "Light Terra Cotta", "#DD967F"),
-    ("356", "Medium Terra Cotta", "#C66F5C"),
-    ("3830", "Terra Cotta", "#B85A41"),
-    ("355", "Dark Terra Cotta", "#A44037"),
-    ("3777", "Very Dark Terra Cotta", "#8E3031"),
-];
-
-/// Cached DMC palette with precomputed LAB values
-struct DmcPalette {
-    threads: Vec<DmcThread>,
-    labs: Vec<Lab<D65, f32>>,
-}
-
-static CACHED_PALETTE: OnceLock<DmcPalette> = OnceLock::new();
-
-impl DmcPalette {
-    fn global() -> &'static Self {
-        CACHED_PALETTE.get_or_init(Self::new)
-    }
-
-    fn new() -> Self {
-        let threads: Vec<DmcThread> = DMC_PALETTE
-            .iter()
-            .map(|(code, name, hex)| {
-                let rgb = hex_to_rgb(hex);
-                let lab = rgb_to_lab(rgb);
-                DmcThread {
-                    code: code.to_string(),
-                    name: name.to_string(),
-                    hex: hex.to_string(),
-                    rgb,
-                    lab: [lab.l, lab.a, lab.b],
-                }
-            })
-            .collect();
-
-        let labs: Vec<Lab<D65, f32>> = threads
-            .iter()
-            .map(|t| Lab::new(t.lab[0], t.lab[1], t.lab[2]))
-            .collect();
-
-        Self { threads, labs }
-    }
-
-    /// Find the closest DMC color using CIEDE2000 Delta-E (parallelized)
-    fn find_closest(&self, target: Lab<D65, f32>) -> &DmcThread {
-        let (idx, _) = self
-            .labs
-            .par_iter()
-            .enumerate()
-            .map(|(i, lab)| {
-                let delta_e = target.difference(*lab);
-                (i, delta_e)
-            })
-            .min_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(std::cmp::Ordering::Equal))
-            .unwrap_or((0, f32::MAX));
-
-        &self.threads[idx]
-    }
+fn default_fabric_hex() -> String {
+    crate::fabric::DEFAULT_FABRIC_HEX.to_string()
 }
 
 /// Convert hex string to RGB tuple
-fn hex_to_rgb(hex: &str) -> [u8; 3] {
-    let hex = hex.trim_start_matches('#');
-    let r = u8::from_str_radix(&hex[0..2], 16).unwrap_or(0);
-    let g = u8::from_str_radix(&hex[2..4], 16).unwrap_or(0);
-    let b = u8::from_str_radix(&hex[4..6], 16).unwrap_or(0);
-    [r, g, b]
+pub(crate) fn hex_to_rgb(hex: &str) -> [u8; 3] {
+    parse_hex(hex).unwrap_or([0, 0, 0])
+}
+
+/// Strictly parse a `#RRGGBB` (or `RRGGBB`) color, rejecting anything else
+pub(crate) fn parse_hex(hex: &str) -> Option<[u8; 3]> {
+    let hex = hex.trim().trim_start_matches('#');
+    if hex.len() != 6 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+    let r = u8::from_str_radix(&hex[0..2], 16).ok()?;
+    let g = u8::from_str_radix(&hex[2..4], 16).ok()?;
+    let b = u8::from_str_radix(&hex[4..6], 16).ok()?;
+    Some([r, g, b])
 }
 
 /// Convert RGB to hex string
-fn rgb_to_hex(rgb: [u8; 3]) -> String {
+pub(crate) fn rgb_to_hex(rgb: [u8; 3]) -> String {
     format!("#{:02X}{:02X}{:02X}", rgb[0], rgb[1], rgb[2])
 }
 
 /// Convert RGB [0-255] to LAB color space
-fn rgb_to_lab(rgb: [u8; 3]) -> Lab<D65, f32> {
+pub(crate) fn rgb_to_lab(rgb: [u8; 3]) -> Lab<D65, f32> {
     let srgb = Srgb::new(
         rgb[0] as f32 / 255.0,
         rgb[1] as f32 / 255.0,
@@ -378,6 +541,16 @@ fn rgb_to_lab(rgb: [u8; 3]) -> Lab<D65, f32> {
     Lab::from_color(srgb)
 }
 
+/// Convert LAB back to clamped 8-bit sRGB
+pub(crate) fn lab_to_rgb(lab: Lab<D65, f32>) -> [u8; 3] {
+    let srgb = Srgb::from_color(lab);
+    [
+        (srgb.red.clamp(0.0, 1.0) * 255.0).round() as u8,
+        (srgb.green.clamp(0.0, 1.0) * 255.0).round() as u8,
+        (srgb.blue.clamp(0.0, 1.0) * 255.0).round() as u8,
+    ]
+}
+
 /// K-means clustering center
 #[derive(Clone)]
 struct KMeansCenter {
@@ -421,20 +594,24 @@ impl KMeansCenter {
     }
 }
 
-/// Parallel k-means color quantization using CIEDE2000
-fn kmeans_quantize(
+/// Parallel k-means color quantization under the configured `ColorMetric`
+///
+/// The first `seeds.len()` centers are pinned to `seeds` and never move.
+pub(crate) fn kmeans_quantize(
     pixels: &[Lab<D65, f32>],
     k: usize,
     max_iterations: usize,
+    seeds: &[Lab<D65, f32>],
+    metric: ColorMetric,
 ) -> (Vec<Lab<D65, f32>>, Vec<u16>) {
     if pixels.is_empty() || k == 0 {
         return (vec![], vec![]);
     }
 
-    let k = k.min(pixels.len());
+    let k = k.min(pixels.len()).max(seeds.len());
 
     // Initialize centers using k-means++ strategy
-    let mut centers = kmeans_plus_plus_init(pixels, k);
+    let mut centers = kmeans_plus_plus_init(pixels, k, seeds, metric);
 
     let mut labels = vec![0u16; pixels.len()];
 
@@ -446,7 +623,7 @@ fn kmeans_quantize(
                 let mut best_idx = 0u16;
                 let mut best_dist = f32::MAX;
                 for (i, center) in centers.iter().enumerate() {
-                    let dist = pixel.difference(center.lab);
+                    let dist = metric.distance(*pixel, center.lab);
                     if dist < best_dist {
                         best_dist = dist;
                         best_idx = i as u16;
@@ -481,7 +658,7 @@ fn kmeans_quantize(
             centers[label as usize].add_sample(*pixel);
         }
 
-        for center in &mut centers {
+        for center in centers.iter_mut().skip(seeds.len()) {
             center.update_centroid();
         }
     }
@@ -491,25 +668,40 @@ fn kmeans_quantize(
 }
 
 /// K-means++ initialization for better initial centroids
-fn kmeans_plus_plus_init(pixels: &[Lab<D65, f32>], k: usize) -> Vec<KMeansCenter> {
+///
+/// `seeds` become the first centers; the rest are picked farthest-first around them.
+fn kmeans_plus_plus_init(
+    pixels: &[Lab<D65, f32>],
+    k: usize,
+    seeds: &[Lab<D65, f32>],
+    metric: ColorMetric,
+) -> Vec<KMeansCenter> {
     use std::collections::HashSet;
 
     let n = pixels.len();
-    let mut centers = Vec::with_capacity(k);
+    let mut centers: Vec<KMeansCenter> = seeds.iter().copied().map(KMeansCenter::new).collect();
+    centers.reserve(k.saturating_sub(centers.len()));
     let mut chosen_indices = HashSet::new();
 
-    // First center: pick pixel closest to median luminance
-    let mut sorted_by_l: Vec<(usize, f32)> =
-        pixels.iter().enumerate().map(|(i, p)| (i, p.l)).collect();
-    sorted_by_l.sort_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(std::cmp::Ordering::Equal));
-    let first_idx = sorted_by_l[n / 2].0;
-    centers.push(KMeansCenter::new(pixels[first_idx]));
-    chosen_indices.insert(first_idx);
+    // First center: pick pixel closest to median luminance, unless seeds were given
+    if centers.is_empty() {
+        let mut sorted_by_l: Vec<(usize, f32)> =
+            pixels.iter().enumerate().map(|(i, p)| (i, p.l)).collect();
+        sorted_by_l.sort_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(std::cmp::Ordering::Equal));
+        let first_idx = sorted_by_l[n / 2].0;
+        centers.push(KMeansCenter::new(pixels[first_idx]));
+        chosen_indices.insert(first_idx);
+    }
 
     // Remaining centers: pick farthest from existing centers (deterministic)
     let mut min_distances: Vec<f32> = pixels
         .par_iter()
-        .map(|p| p.difference(centers[0].lab))
+        .map(|p| {
+            centers
+                .iter()
+                .map(|c| metric.distance(*p, c.lab))
+                .fold(f32::MAX, f32::min)
+        })
         .collect();
 
     while centers.len() < k {
@@ -530,7 +722,7 @@ fn kmeans_plus_plus_init(pixels: &[Lab<D65, f32>], k: usize) -> Vec<KMeansCenter
             .par_iter_mut()
             .zip(pixels.par_iter())
             .for_each(|(min_d, pixel)| {
-                let d = pixel.difference(new_lab);
+                let d = metric.distance(*pixel, new_lab);
                 if d < *min_d {
                     *min_d = d;
                 }
@@ -549,6 +741,7 @@ fn remove_small_regions(
     height: u32,
     palette: &[Lab<D65, f32>],
     min_region_size: u32,
+    metric: ColorMetric,
 ) {
     let n = (width * height) as usize;
     let mut visited = vec![false; n];
@@ -609,8 +802,8 @@ fn remove_small_regions(
                                 palette.get(**label_a as usize),
                                 palette.get(**label_b as usize),
                             ) {
-                                let dist_a = current.difference(*a);
-                                let dist_b = current.difference(*b);
+                                let dist_a = metric.distance(*current, *a);
+                                let dist_b = metric.distance(*current, *b);
                                 dist_b
                                     .partial_cmp(&dist_a)
                                     .unwrap_or(std::cmp::Ordering::Equal)
@@ -632,137 +825,800 @@ fn remove_small_regions(
     }
 }
 
-/// Main pattern processing function
-pub fn process_pattern(
-    image_bytes: &[u8],
+/// Look up `config.locked_threads` in `catalog`, rejecting unknown or excluded codes.
+fn resolve_locked_threads(
+    catalog: &ThreadCatalog,
     config: &ProcessingConfig,
-    mask: Option<&[u8]>,
-) -> Result<PatternResult, String> {
-    let start_time = std::time::Instant::now();
+) -> Result<Vec<ThreadColor>, String> {
+    let mut locked: Vec<ThreadColor> = Vec::new();
+    for code in config
+        .locked_threads
+        .iter()
+        .filter(|c| !c.trim().is_empty())
+    {
+        if config
+            .excluded_threads
+            .iter()
+            .any(|ex| ex.trim().eq_ignore_ascii_case(code.trim()))
+        {
+            return Err(format!(
+                "Thread {} is both locked and excluded",
+                code.trim()
+            ));
+        }
+        let thread = catalog.find_code(code).ok_or_else(|| {
+            format!(
+                "Locked thread {} is not in the {} catalog",
+                code.trim(),
+                catalog.brand.label()
+            )
+        })?;
+        if !locked.iter().any(|t| t.code == thread.code) {
+            locked.push(thread.clone());
+        }
+    }
+    Ok(locked)
+}
+
+fn fabric_rgb(config: &ProcessingConfig) -> Result<[u8; 3], String> {
+    parse_hex(&config.fabric.hex)
+        .ok_or_else(|| format!("Invalid fabric color: {}", config.fabric.hex))
+}
+
+/// Decoded pixels and their LAB colors
+struct DecodedImage {
+    rgba: image::RgbaImage,
+    pixels: Vec<Lab<D65, f32>>,
+    /// Source pixels per art pixel in pixel-art mode, otherwise 1
+    scale: u32,
+    /// Dimensions as decoded, before pixel art is cut down to one pixel per art pixel
+    source: (u32, u32),
+}
+
+/// Stitch grid width, height and per-cell LAB colors
+type GridPixels = (u32, u32, Vec<Lab<D65, f32>>);
 
-    // Decode image
+/// Decode and adjust an image, then convert it to LAB with translucent pixels blended
+/// onto the fabric. Pixel art is reduced to one pixel per art pixel before adjusting.
+fn decode_lab(
+    image_bytes: &[u8],
+    config: &ProcessingConfig,
+    fabric_rgb: [u8; 3],
+) -> Result<DecodedImage, String> {
     let img = image::load_from_memory(image_bytes)
         .map_err(|e| format!("Failed to decode image: {}", e))?;
 
-    let rgba = img.to_rgba8();
-    let width = rgba.width();
-    let height = rgba.height();
-    let n = (width * height) as usize;
+    let mut rgba = img.to_rgba8();
+    let source = rgba.dimensions();
+    let scale = match &config.pixel_art {
+        Some(art) => {
+            let scale = match art.scale {
+                Some(0) => return Err("Pixel art scale must be at least 1".to_string()),
+                Some(scale) => scale,
+                None => detect_scale(&rgba),
+            };
+            if scale > 1 {
+                rgba = downsample_image(&rgba, scale);
+            }
+            scale
+        }
+        None => 1,
+    };
+    if let Some(adjustments) = &config.adjustments {
+        apply_adjustments(&mut rgba, adjustments)?;
+    }
 
     // Convert to LAB color space (parallel)
-    let pixels: Vec<Lab<D65, f32>> = rgba
+    let pixels = rgba
         .pixels()
         .collect::<Vec<_>>()
         .par_iter()
         .map(|p| {
-            // Alpha blend with white background
+            // Alpha blend onto the fabric
             let a = p[3] as f32 / 255.0;
-            let r = (p[0] as f32 * a + 255.0 * (1.0 - a)) as u8;
-            let g = (p[1] as f32 * a + 255.0 * (1.0 - a)) as u8;
-            let b = (p[2] as f32 * a + 255.0 * (1.0 - a)) as u8;
-            rgb_to_lab([r, g, b])
+            let blend = |c: usize| (p[c] as f32 * a + fabric_rgb[c] as f32 * (1.0 - a)) as u8;
+            rgb_to_lab([blend(0), blend(1), blend(2)])
         })
         .collect();
+    Ok(DecodedImage {
+        rgba,
+        pixels,
+        scale,
+        source,
+    })
+}
 
-    let detail_bias = (1.0 - config.simplify_amount).clamp(0.0, 1.0);
-    let color_bias = ((config.color_count as f32 - 2.0) / 62.0).clamp(0.0, 1.0);
-    let quality_bias = ((detail_bias + color_bias) * 0.5).clamp(0.0, 1.0);
-    let max_train = (8000.0 + 42000.0 * quality_bias).round() as usize;
-    let stride = (n / max_train.max(1)).max(1);
+/// LAB color of every stitch cell, as `process_pattern` sees the image before quantizing.
+pub(crate) fn stitch_grid_pixels(
+    image_bytes: &[u8],
+    config: &ProcessingConfig,
+) -> Result<GridPixels, String> {
+    let DecodedImage { rgba, pixels, .. } = decode_lab(image_bytes, config, fabric_rgb(config)?)?;
+    let source = (rgba.width(), rgba.height());
+    match stitch_grid(config) {
+        Some(grid) => {
+            let target = grid.dimensions(source.0, source.1)?;
+            Ok((target.0, target.1, resample_lab(&pixels, source, target)))
+        }
+        None => Ok((source.0, source.1, pixels)),
+    }
+}
 
-    // Filter training pixels if mask is provided.
-    let mut training_pixels: Vec<Lab<D65, f32>> = if let Some(mask) = mask {
-        pixels
-            .iter()
-            .zip(mask.iter())
-            .filter_map(|(p, &m)| if m > 0 { Some(*p) } else { None })
-            .step_by(stride)
-            .collect()
-    } else {
-        pixels.iter().step_by(stride).copied().collect()
-    };
+/// The grid pixels are resampled to; pixel art keeps one stitch per art pixel.
+fn stitch_grid(config: &ProcessingConfig) -> Option<&StitchGridConfig> {
+    config.grid.as_ref().filter(|_| config.pixel_art.is_none())
+}
+
+/// Most extra quantization passes spent replacing clusters that matched the same thread
+const MAX_REFITS: u32 = 4;
+
+/// LAB sums and pixel count of one cluster
+type ClusterSum = (f64, f64, f64, u64);
+
+/// Mean color of each cluster; neutral gray for clusters no pixel ended up in
+fn cluster_means(sums: &[ClusterSum]) -> Vec<Lab<D65, f32>> {
+    sums.iter()
+        .map(|(l, a, b, count)| {
+            if *count > 0 {
+                Lab::new(
+                    (*l / *count as f64) as f32,
+                    (*a / *count as f64) as f32,
+                    (*b / *count as f64) as f32,
+                )
+            } else {
+                Lab::new(50.0, 0.0, 0.0)
+            }
+        })
+        .collect()
+}
+
+/// Pixels one quantization pass covers and the settings it uses
+struct QuantizePass<'a> {
+    /// `None` covers every pixel
+    mask: Option<Cow<'a, [u8]>>,
+    /// Pixel count training samples are spread over
+    pixels: usize,
+    color_count: u32,
+    quantizer: Quantizer,
+    dither: DitherMode,
+}
+
+/// Pixel labels with one cluster sum and matched thread per label
+struct PaletteFit {
+    labels: Vec<u16>,
+    sums: Vec<ClusterSum>,
+    threads: Vec<ThreadColor>,
+    purchases: Vec<String>,
+}
 
-    if training_pixels.is_empty() {
-        training_pixels = pixels.iter().step_by(stride).copied().collect();
+impl PaletteFit {
+    /// Fold each cluster into the first cluster matched to the same thread and drop clusters
+    /// no pixel ended up in. The first `keep` clusters stay even when empty.
+    fn merge_shared_threads(mut self, keep: usize) -> Self {
+        let mut remap: Vec<u16> = Vec::with_capacity(self.threads.len());
+        let mut threads: Vec<ThreadColor> = Vec::new();
+        let mut sums: Vec<ClusterSum> = Vec::new();
+        for (cluster, (thread, sum)) in self.threads.into_iter().zip(self.sums).enumerate() {
+            if sum.3 == 0 && cluster >= keep {
+                remap.push(0);
+                continue;
+            }
+            match threads.iter().position(|t| t.code == thread.code) {
+                Some(existing) => {
+                    let s = &mut sums[existing];
+                    s.0 += sum.0;
+                    s.1 += sum.1;
+                    s.2 += sum.2;
+                    s.3 += sum.3;
+                    remap.push(existing as u16);
+                }
+                None => {
+                    remap.push(threads.len() as u16);
+                    threads.push(thread);
+                    sums.push(sum);
+                }
+            }
+        }
+        for label in &mut self.labels {
+            *label = remap[*label as usize];
+        }
+        self.threads = threads;
+        self.sums = sums;
+        self.retain_purchases();
+        self
     }
 
-    // Run k-means quantization
-    let k = (config.color_count as usize).min(30);
-    let max_iterations =
-        (10.0 + quality_bias * 10.0 + config.smoothing_amount.clamp(0.0, 1.0) * 4.0).round()
-            as usize;
-    let (palette_lab, _) = kmeans_quantize(&training_pixels, k, max_iterations.max(8));
+    /// Move the pixels of the smallest clusters to their nearest remaining thread until `k`
+    /// clusters are left. The first `keep` clusters are never folded.
+    fn fold_smallest(
+        &mut self,
+        pixels: &[Lab<D65, f32>],
+        k: usize,
+        keep: usize,
+        metric: ColorMetric,
+    ) {
+        while self.threads.len() > k.max(keep) {
+            let Some(drop) = (keep..self.threads.len()).min_by_key(|&c| (self.sums[c].3, c)) else {
+                break;
+            };
+            self.threads.remove(drop);
+            self.sums.remove(drop);
+            let labs: Vec<Lab<D65, f32>> = self
+                .threads
+                .iter()
+                .map(|t| Lab::new(t.lab[0], t.lab[1], t.lab[2]))
+                .collect();
+            for (pixel, label) in pixels.iter().zip(self.labels.iter_mut()) {
+                let cluster = *label as usize;
+                if cluster == drop {
+                    let nearest = (0..labs.len())
+                        .min_by(|&a, &b| {
+                            metric
+                                .distance(*pixel, labs[a])
+                                .total_cmp(&metric.distance(*pixel, labs[b]))
+                        })
+                        .unwrap_or(0);
+                    let s = &mut self.sums[nearest];
+                    s.0 += pixel.l as f64;
+                    s.1 += pixel.a as f64;
+                    s.2 += pixel.b as f64;
+                    s.3 += 1;
+                    *label = nearest as u16;
+                } else if cluster > drop {
+                    *label -= 1;
+                }
+            }
+        }
+        self.retain_purchases();
+    }
 
-    // Assign all pixels to nearest cluster (parallel)
-    let mut labels: Vec<u16> = pixels
-        .par_iter()
-        .map(|pixel| {
-            let mut best_idx = 0u16;
-            let mut best_dist = f32::MAX;
-            for (i, center) in palette_lab.iter().enumerate() {
-                let dist = pixel.difference(*center);
-                if dist < best_dist {
-                    best_dist = dist;
-                    best_idx = i as u16;
+    /// Combine the fits of several passes, each pixel labelled by the pass whose mask covers
+    /// it. Cluster sums only count covered pixels; uncovered pixels get label 0.
+    fn stack(fits: Vec<(Option<&[u8]>, PaletteFit)>, pixels: &[Lab<D65, f32>]) -> Option<Self> {
+        if fits.is_empty() {
+            return None;
+        }
+        let mut stacked = PaletteFit {
+            labels: vec![0; pixels.len()],
+            sums: Vec::new(),
+            threads: Vec::new(),
+            purchases: Vec::new(),
+        };
+        for (mask, fit) in fits {
+            let offset = stacked.threads.len() as u16;
+            let mut sums: Vec<ClusterSum> = vec![(0.0, 0.0, 0.0, 0); fit.threads.len()];
+            for (i, pixel) in pixels.iter().enumerate() {
+                if mask.map(|m| m[i] == 0).unwrap_or(false) {
+                    continue;
+                }
+                let label = fit.labels[i];
+                stacked.labels[i] = label + offset;
+                let s = &mut sums[label as usize];
+                s.0 += pixel.l as f64;
+                s.1 += pixel.a as f64;
+                s.2 += pixel.b as f64;
+                s.3 += 1;
+            }
+            stacked.sums.extend(sums);
+            stacked.threads.extend(fit.threads);
+            for code in fit.purchases {
+                if !stacked.purchases.contains(&code) {
+                    stacked.purchases.push(code);
                 }
             }
-            best_idx
+        }
+        Some(stacked)
+    }
+
+    /// Keep only suggested purchases some remaining thread is stitched with.
+    fn retain_purchases(&mut self) {
+        let threads = &self.threads;
+        self.purchases.retain(|code| {
+            threads
+                .iter()
+                .any(|thread| thread.component_codes().contains(&code.as_str()))
+        });
+    }
+}
+
+/// Count of distinct stitched pixel colors when it is below `limit`.
+fn distinct_colors(pixels: &[Lab<D65, f32>], mask: Option<&[u8]>, limit: usize) -> Option<usize> {
+    let mut distinct = std::collections::HashSet::new();
+    for (i, pixel) in pixels.iter().enumerate() {
+        if mask.map(|m| m[i] > 0).unwrap_or(true) {
+            distinct.insert([pixel.l.to_bits(), pixel.a.to_bits(), pixel.b.to_bits()]);
+            if distinct.len() >= limit {
+                return None;
+            }
+        }
+    }
+    Some(distinct.len())
+}
+
+/// One cluster per exact color of pixel art, each matched to its closest thread. On the
+/// thread palette, colors matched to one thread are merged and reported as collisions.
+fn fit_exact_colors(
+    pixels: &[Lab<D65, f32>],
+    mask: Option<&[u8]>,
+    scale: u32,
+    catalog: &ThreadCatalog,
+    config: &ProcessingConfig,
+) -> Result<(PaletteFit, PixelArtReport), String> {
+    let (colors, labels, counts) = exact_colors(pixels, mask);
+    if colors.is_empty() {
+        return Err("The mask leaves nothing to stitch".to_string());
+    }
+    if colors.len() > MAX_COLOR_COUNT as usize {
+        return Err(format!(
+            "Pixel art has {} colors, above the maximum of {}",
+            colors.len(),
+            MAX_COLOR_COUNT
+        ));
+    }
+    let InventoryMatch { threads, purchases } = match_inventory(
+        catalog,
+        &colors,
+        &counts,
+        &config.owned_threads,
+        config.max_purchases,
+        config.color_metric,
+    )?;
+
+    let mut collisions: Vec<ThreadCollision> = Vec::new();
+    if config.use_dmc_palette {
+        for (color, thread) in colors.iter().zip(&threads) {
+            let hex = rgb_to_hex(lab_to_rgb(*color));
+            match collisions.iter_mut().find(|c| c.dmc_code == thread.code) {
+                Some(collision) => collision.art_colors.push(hex),
+                None => collisions.push(ThreadCollision {
+                    dmc_code: thread.code.clone(),
+                    art_colors: vec![hex],
+                }),
+            }
+        }
+        collisions.retain(|c| c.art_colors.len() > 1);
+    }
+
+    let sums = colors
+        .iter()
+        .zip(&counts)
+        .map(|(color, &count)| {
+            let n = count as f64;
+            (
+                color.l as f64 * n,
+                color.a as f64 * n,
+                color.b as f64 * n,
+                count,
+            )
         })
         .collect();
+    let report = PixelArtReport {
+        scale,
+        colors: colors.len() as u32,
+        collisions,
+    };
+    let fit = PaletteFit {
+        labels,
+        sums,
+        threads,
+        purchases,
+    };
+    if config.use_dmc_palette {
+        Ok((fit.merge_shared_threads(0), report))
+    } else {
+        Ok((fit, report))
+    }
+}
 
-    // Remove small regions
-    if config.min_region_size > 1 {
-        remove_small_regions(
-            &mut labels,
-            width,
-            height,
-            &palette_lab,
-            config.min_region_size,
-        );
+/// The catalog `config` stitches from, blends included, and the part of it colors are
+/// matched against once the excluded threads are taken out.
+pub fn resolve_catalogs(
+    config: &ProcessingConfig,
+) -> Result<(Arc<ThreadCatalog>, Arc<ThreadCatalog>), String> {
+    let mut catalog =
+        ThreadCatalog::resolve(config.thread_brand, config.custom_library_id.as_deref())?;
+    if config.enable_blends && config.use_dmc_palette {
+        let max_delta_e = config
+            .max_blend_delta_e
+            .unwrap_or(DEFAULT_MAX_BLEND_DELTA_E);
+        catalog = catalog.cached_blends(max_delta_e);
     }
+    let matching_catalog = if config.excluded_threads.is_empty() {
+        catalog.clone()
+    } else {
+        Arc::new(
+            catalog
+                .without_codes(&config.excluded_threads)
+                .ok_or("Every thread in the catalog is excluded")?,
+        )
+    };
+    Ok((catalog, matching_catalog))
+}
 
-    // Recompute palette from final labels (get actual mean colors)
-    let mut palette_sums: Vec<(f64, f64, f64, u64)> = vec![(0.0, 0.0, 0.0, 0); k];
-    for (pixel, &label) in pixels.iter().zip(labels.iter()) {
-        let s = &mut palette_sums[label as usize];
-        s.0 += pixel.l as f64;
-        s.1 += pixel.a as f64;
-        s.2 += pixel.b as f64;
-        s.3 += 1;
+/// Main pattern processing function
+pub fn process_pattern(
+    image_bytes: &[u8],
+    config: &ProcessingConfig,
+    mask: Option<&[u8]>,
+) -> Result<PatternResult, String> {
+    let start_time = std::time::Instant::now();
+    if config.color_count > MAX_COLOR_COUNT {
+        return Err(format!(
+            "Color count {} is above the maximum of {}",
+            config.color_count, MAX_COLOR_COUNT
+        ));
+    }
+    let region_colors: u32 = config.regions.iter().map(|r| r.color_count).sum();
+    if config.regions.iter().any(|r| r.color_count == 0) {
+        return Err("Every region needs at least one color".to_string());
+    }
+    if config.pixel_art.is_some() && !config.regions.is_empty() {
+        return Err("Regions are not supported for pixel art".to_string());
+    }
+    if config.color_count + region_colors > MAX_COLOR_COUNT {
+        return Err(format!(
+            "Regions add up to {} colors, above the maximum of {}",
+            config.color_count + region_colors,
+            MAX_COLOR_COUNT
+        ));
+    }
+
+    // Resolve the thread catalog up front so a missing custom library fails fast
+    let (catalog, matching_catalog) = resolve_catalogs(config)?;
+    let locked = resolve_locked_threads(&catalog, config)?;
+
+    let fabric_rgb = fabric_rgb(config)?;
+    let DecodedImage {
+        rgba,
+        pixels: source_pixels,
+        scale,
+        source,
+    } = decode_lab(image_bytes, config, fabric_rgb)?;
+    let source_width = rgba.width();
+    let source_height = rgba.height();
+    if let Some(mask) = mask {
+        if mask.len() != (source.0 * source.1) as usize {
+            return Err(format!(
+                "Mask has {} values for a {}x{} image",
+                mask.len(),
+                source.0,
+                source.1
+            ));
+        }
+    }
+
+    // The mask covers the image as decoded, so pixel art keeps one value per art pixel
+    let art_mask = mask
+        .filter(|_| scale > 1)
+        .map(|m| downsample(m, source, scale));
+    let mask = art_mask.as_deref().or(mask);
+
+    // Transparent pixels are left as fabric
+    let transparency_mask = alpha_mask(&rgba, mask, config.fabric.alpha_threshold);
+    let mask = transparency_mask.as_deref().or(mask);
+
+    // Resample to the physical stitch grid, averaging in LAB so fine detail blends
+    // instead of aliasing
+    let source_mask = mask;
+    let (width, height, resampled, grid_mask) = match stitch_grid(config) {
+        Some(grid) => {
+            let source = (source_width, source_height);
+            let target = grid.dimensions(source_width, source_height)?;
+            let grid_mask = mask.map(|m| resample_mask(m, source, target));
+            let pixels = resample_lab(&source_pixels, source, target);
+            (target.0, target.1, Some(pixels), grid_mask)
+        }
+        None => (source_width, source_height, None, None),
+    };
+    let pixels: &[Lab<D65, f32>] = resampled.as_deref().unwrap_or(&source_pixels);
+    let mask = if stitch_grid(config).is_some() {
+        grid_mask.as_deref()
+    } else {
+        mask
+    };
+    let n = (width * height) as usize;
+
+    let detail_bias = (1.0 - config.simplify_amount).clamp(0.0, 1.0);
+    let color_bias = ((config.color_count as f32 - 2.0) / 62.0).clamp(0.0, 1.0);
+    let quality_bias = ((detail_bias + color_bias) * 0.5).clamp(0.0, 1.0);
+    let max_train = (8000.0 + 42000.0 * quality_bias).round() as usize;
+
+    // The whole image is one pass unless regions bring their own budgets. Region pixels are
+    // taken out of the base pass, the first region listed winning any overlap.
+    let mut passes = vec![QuantizePass {
+        mask: mask.map(Cow::Borrowed),
+        pixels: n,
+        color_count: config.color_count,
+        quantizer: config.quantizer,
+        dither: config.dither,
+    }];
+    if !config.regions.is_empty() {
+        let mut claimed = vec![false; n];
+        for (number, region) in config.regions.iter().enumerate() {
+            let expected = (source_width * source_height) as usize;
+            if region.mask.len() != expected {
+                return Err(format!(
+                    "Region {} mask has {} values for a {}x{} image",
+                    number + 1,
+                    region.mask.len(),
+                    source_width,
+                    source_height
+                ));
+            }
+            let region_mask = match &config.grid {
+                Some(_) => Cow::Owned(resample_mask(
+                    &region.mask,
+                    (source_width, source_height),
+                    (width, height),
+                )),
+                None => Cow::Borrowed(region.mask.as_slice()),
+            };
+            let pass_mask: Vec<u8> = (0..n)
+                .map(|i| {
+                    let stitched = mask.map(|m| m[i] > 0).unwrap_or(true);
+                    if stitched && !claimed[i] && region_mask[i] > 0 {
+                        claimed[i] = true;
+                        region_mask[i]
+                    } else {
+                        0
+                    }
+                })
+                .collect();
+            passes.push(QuantizePass {
+                pixels: pass_mask.iter().filter(|&&m| m > 0).count(),
+                mask: Some(Cow::Owned(pass_mask)),
+                color_count: region.color_count,
+                quantizer: region.quantizer.unwrap_or(config.quantizer),
+                dither: region.dither.unwrap_or(config.dither),
+            });
+        }
+        let base_mask: Vec<u8> = (0..n)
+            .map(|i| {
+                if claimed[i] {
+                    0
+                } else {
+                    mask.map(|m| m[i]).unwrap_or(255)
+                }
+            })
+            .collect();
+        passes[0].pixels = base_mask.iter().filter(|&&m| m > 0).count();
+        passes[0].mask = Some(Cow::Owned(base_mask));
     }
+    let single_pass = passes.len() == 1;
 
-    let final_palette_lab: Vec<Lab<D65, f32>> = palette_sums
+    // Quantize, with locked threads as fixed palette entries
+    let seeds: Vec<Lab<D65, f32>> = locked
         .iter()
-        .map(|(l, a, b, count)| {
-            if *count > 0 {
-                Lab::new(
-                    (*l / *count as f64) as f32,
-                    (*a / *count as f64) as f32,
-                    (*b / *count as f64) as f32,
-                )
+        .map(|t| Lab::new(t.lab[0], t.lab[1], t.lab[2]))
+        .collect();
+    let keep = locked.len();
+
+    // Fit one pass's palette; `None` when a region pass has no pixels to stitch
+    let fit_pass = |pass: &QuantizePass,
+                    owned: &[String],
+                    max_purchases: Option<u32>|
+     -> Result<Option<(PaletteFit, u32)>, String> {
+        let pass_mask = pass.mask.as_deref();
+        let stride = (pass.pixels / max_train.max(1)).max(1);
+
+        // Sample training pixels, more densely where the mask or saliency weighs heavier
+        let weights = training_weights(pixels, width, height, pass_mask, config.training_weight);
+        let mut training_pixels = weighted_sample(pixels, &weights, stride);
+
+        if training_pixels.is_empty() {
+            if !single_pass {
+                return Ok(None);
+            }
+            training_pixels = pixels.iter().step_by(stride).copied().collect();
+        }
+        let k = (pass.color_count as usize).max(seeds.len());
+
+        // Quantize to `clusters` colors, assign every pixel and match each cluster to a thread
+        let fit_palette = |clusters: usize| -> Result<PaletteFit, String> {
+            let mut direct_threads = None;
+            let palette_lab: Vec<Lab<D65, f32>> = match pass.quantizer {
+                Quantizer::Kmeans => {
+                    let max_iterations = (10.0
+                        + quality_bias * 10.0
+                        + config.smoothing_amount.clamp(0.0, 1.0) * 4.0)
+                        .round() as usize;
+                    kmeans_quantize(
+                        &training_pixels,
+                        clusters,
+                        max_iterations.max(8),
+                        &seeds,
+                        config.color_metric,
+                    )
+                    .0
+                }
+                Quantizer::ThreadDirect => {
+                    let direct = thread_direct(
+                        &training_pixels,
+                        clusters,
+                        &locked,
+                        &matching_catalog,
+                        owned,
+                        max_purchases,
+                        config.color_metric,
+                    )?;
+                    let labs = direct
+                        .threads
+                        .iter()
+                        .map(|t| Lab::new(t.lab[0], t.lab[1], t.lab[2]))
+                        .collect();
+                    direct_threads = Some(direct);
+                    labs
+                }
+                quantizer => {
+                    let free = clusters - seeds.len();
+                    let quantized = match quantizer {
+                        Quantizer::MedianCut => median_cut(&training_pixels, free),
+                        Quantizer::Wu => wu(&training_pixels, free),
+                        _ => octree(&training_pixels, free),
+                    };
+                    seeds.iter().copied().chain(quantized).collect()
+                }
+            };
+            if palette_lab.is_empty() {
+                return Err("No colors left to quantize the image with".to_string());
+            }
+
+            // Assign all pixels to a cluster, nearest or dithered
+            let mut labels = dither_labels(
+                pixels,
+                width,
+                height,
+                &palette_lab,
+                pass_mask,
+                pass.dither,
+                config.color_metric,
+            );
+
+            // Remove small regions; dithered output is deliberately fragmented, so leave it alone
+            if config.min_region_size > 1 && pass.dither == DitherMode::None {
+                remove_small_regions(
+                    &mut labels,
+                    width,
+                    height,
+                    &palette_lab,
+                    config.min_region_size,
+                    config.color_metric,
+                );
+            }
+
+            // Recompute palette from final labels (get actual mean colors); a region pass
+            // only averages its own pixels
+            let mut sums: Vec<ClusterSum> = vec![(0.0, 0.0, 0.0, 0); palette_lab.len()];
+            for (i, (pixel, &label)) in pixels.iter().zip(labels.iter()).enumerate() {
+                if !single_pass && pass_mask.map(|m| m[i] == 0).unwrap_or(false) {
+                    continue;
+                }
+                let s = &mut sums[label as usize];
+                s.0 += pixel.l as f64;
+                s.1 += pixel.a as f64;
+                s.2 += pixel.b as f64;
+                s.3 += 1;
+            }
+
+            // Map to the selected catalog's threads under the configured `ColorMetric`, limited
+            // to the user's stash. Locked clusters come first and keep their thread;
+            // thread-direct clusters already are threads.
+            let (threads, purchases) = match direct_threads {
+                Some(DirectThreads { threads, purchases }) => (threads, purchases),
+                None => {
+                    let locked_count = locked.len().min(sums.len());
+                    let means = cluster_means(&sums);
+                    let cluster_sizes: Vec<u64> = sums.iter().map(|s| s.3).collect();
+                    let InventoryMatch {
+                        threads: free_matches,
+                        purchases,
+                    } = match_inventory(
+                        &matching_catalog,
+                        &means[locked_count..],
+                        &cluster_sizes[locked_count..],
+                        owned,
+                        max_purchases,
+                        config.color_metric,
+                    )?;
+                    let matches = locked.iter().cloned().chain(free_matches).collect();
+                    (matches, purchases)
+                }
+            };
+            Ok(PaletteFit {
+                labels,
+                sums,
+                threads,
+                purchases,
+            })
+        };
+
+        // Clusters that land on one thread are merged, and the freed slots re-fitted with more
+        // clusters until `k` distinct threads come out or the refits run out. A refit that
+        // overshoots folds its smallest clusters back down to `k`.
+        let mut fit = fit_palette(k)?;
+        let mut refits = 0u32;
+        if config.use_dmc_palette {
+            fit = fit.merge_shared_threads(keep);
+            let max_clusters = training_pixels.len().min(MAX_COLOR_COUNT as usize);
+            let mut clusters = k;
+            while pass.quantizer != Quantizer::ThreadDirect
+                && fit.threads.len() < k
+                && refits < MAX_REFITS
+            {
+                let next = (clusters + k - fit.threads.len()).min(max_clusters);
+                if next <= clusters {
+                    break;
+                }
+                clusters = next;
+                refits += 1;
+                let mut candidate = fit_palette(clusters)?.merge_shared_threads(keep);
+                candidate.fold_smallest(pixels, k, keep, config.color_metric);
+                if candidate.threads.len() > fit.threads.len() {
+                    fit = candidate;
+                }
+            }
+        }
+        Ok(Some((fit, refits)))
+    };
+
+    // Later passes may reuse threads earlier passes suggested buying, within one budget
+    let mut owned = config.owned_threads.clone();
+    let mut max_purchases = config.max_purchases;
+    let mut fits: Vec<(Option<&[u8]>, PaletteFit)> = Vec::new();
+    let mut requested = 0u32;
+    let mut refits = 0u32;
+    let mut pixel_art = None;
+    if config.pixel_art.is_some() {
+        // Pixel art asks for exactly the colors it was drawn with
+        let (fit, report) = fit_exact_colors(pixels, mask, scale, &matching_catalog, config)?;
+        requested = report.colors;
+        pixel_art = Some(report);
+        fits.push((mask, fit));
+    } else {
+        for pass in &passes {
+            let Some((fit, pass_refits)) = fit_pass(pass, &owned, max_purchases)? else {
+                continue;
+            };
+            if !config.owned_threads.is_empty() {
+                for code in &fit.purchases {
+                    if !owned.contains(code) {
+                        owned.push(code.clone());
+                        max_purchases = max_purchases.map(|m| m.saturating_sub(1));
+                    }
+                }
+            }
+            requested += pass.color_count;
+            refits += pass_refits;
+            fits.push((pass.mask.as_deref(), fit));
+        }
+    }
+    let pass_threads: usize = fits.iter().map(|(_, fit)| fit.threads.len()).sum();
+    let fit = if single_pass {
+        fits.pop().map(|(_, fit)| fit)
+    } else {
+        let combined = PaletteFit::stack(fits, pixels);
+        combined.map(|fit| {
+            if config.use_dmc_palette {
+                fit.merge_shared_threads(keep)
             } else {
-                Lab::new(50.0, 0.0, 0.0) // Neutral gray for unused clusters
+                fit
             }
         })
-        .collect();
+    }
+    .ok_or("The mask leaves nothing to stitch")?;
+    let shared_threads = pass_threads.saturating_sub(fit.threads.len());
+    let PaletteFit {
+        labels,
+        sums,
+        threads: dmc_matches,
+        purchases,
+    } = fit;
+    let final_palette_lab = cluster_means(&sums);
 
     // Convert palette to hex
     let palette_hex: Vec<String> = final_palette_lab
         .iter()
-        .map(|lab| {
-            let srgb = Srgb::from_color(*lab);
-            let r = (srgb.red.clamp(0.0, 1.0) * 255.0).round() as u8;
-            let g = (srgb.green.clamp(0.0, 1.0) * 255.0).round() as u8;
-            let b = (srgb.blue.clamp(0.0, 1.0) * 255.0).round() as u8;
-            rgb_to_hex([r, g, b])
-        })
-        .collect();
-
-    // Map to DMC colors using CIEDE2000 (parallel)
-    let dmc_palette = DmcPalette::global();
-    let dmc_matches: Vec<&DmcThread> = final_palette_lab
-        .par_iter()
-        .map(|lab| dmc_palette.find_closest(*lab))
+        .map(|lab| rgb_to_hex(lab_to_rgb(*lab)))
         .collect();
 
     let dmc_palette_hex: Vec<String> = dmc_matches.iter().map(|t| t.hex.clone()).collect();
@@ -782,66 +1638,115 @@ pub fn process_pattern(
         })
         .collect();
 
-    // Build stitches array
-    let markers = [
-        'S', 'O', 'T', '*', 'D', 'X', '+', '#', '%', '@', 'A', 'B', 'C', 'E', 'H', 'K', 'M', 'N',
-        'P', 'R', 'U', 'V', 'W', 'Y', 'Z', '0', '1', '2', '3', '4',
-    ];
-
-    let stitches: Vec<Stitch> = (0..n)
+    // Build the label grid, one color per cluster
+    let colors: Vec<GridColor> = dmc_matches
+        .iter()
+        .enumerate()
+        .map(|(label, dmc)| GridColor {
+            dmc_code: if config.use_dmc_palette {
+                dmc.code.clone()
+            } else {
+                format!("RAW-{}", label + 1)
+            },
+            marker: String::new(),
+            hex: if config.use_dmc_palette {
+                dmc.hex.clone()
+            } else {
+                palette_hex[label].clone()
+            },
+            blend: dmc.blend.clone().filter(|_| config.use_dmc_palette),
+        })
+        .collect();
+    let grid_labels: Vec<u16> = (0..n)
         .into_par_iter()
         .map(|i| {
-            let x = (i as u32) % width;
-            let y = (i as u32) / width;
-            let label = labels[i] as usize;
-
-            let is_fabric = mask.map(|m| m[i] == 0).unwrap_or(false);
-
-            if is_fabric {
-                Stitch {
-                    x,
-                    y,
-                    dmc_code: "Fabric".to_string(),
-                    marker: String::new(),
-                    hex: "#FFFFFF".to_string(),
-                }
+            if mask.map(|m| m[i] == 0).unwrap_or(false) {
+                FABRIC_LABEL
             } else {
-                let dmc = &dmc_matches[label];
-                Stitch {
-                    x,
-                    y,
-                    dmc_code: if config.use_dmc_palette {
-                        dmc.code.clone()
-                    } else {
-                        format!("RAW-{}", label + 1)
-                    },
-                    marker: markers[label % markers.len()].to_string(),
-                    hex: if config.use_dmc_palette {
-                        dmc.hex.clone()
-                    } else {
-                        palette_hex[label].clone()
-                    },
-                }
+                labels[i]
             }
         })
         .collect();
 
-    // Compute legend with stitch counts
-    let mut legend_counts: HashMap<String, (u32, String, String)> = HashMap::new();
-    for stitch in &stitches {
-        if stitch.dmc_code == "Fabric" {
+    let mut grid = LabelGrid {
+        labels: grid_labels,
+        colors,
+        partials: Vec::new(),
+    };
+
+    // Merge isolated stitches into close neighbouring threads
+    let confetti_reduced = match &config.confetti {
+        Some(confetti) => reduce_confetti(
+            &mut grid,
+            width,
+            height,
+            confetti.max_delta_e.unwrap_or(DEFAULT_CONFETTI_DELTA_E),
+            config.color_metric,
+        ),
+        None => 0,
+    };
+
+    // Split cells along diagonal edges found in the full-resolution source
+    if config.fractional_stitches && config.pixel_art.is_none() {
+        grid.partials = split_diagonal_cells(
+            &source_pixels,
+            source_mask,
+            (source_width, source_height),
+            &mut grid.labels,
+            (width, height),
+            &final_palette_lab,
+            config.color_metric,
+        );
+    }
+
+    // Threads that would vanish into the fabric are not worth stitching
+    if let Some(max_delta_e) = config.fabric.match_delta_e {
+        drop_fabric_matches(
+            &mut grid,
+            rgb_to_lab(fabric_rgb),
+            max_delta_e,
+            config.color_metric,
+        );
+    }
+    assign_symbols(&mut grid, width);
+    let confetti = ConfettiReport {
+        reduced: confetti_reduced,
+        ..analyze_confetti(&grid, width, height)
+    };
+
+    let backstitch = match &config.backstitch {
+        Some(backstitch) => build_backstitch(
+            &grid,
+            width,
+            height,
+            backstitch,
+            rgb_to_lab(fabric_rgb),
+            config.color_metric,
+            &catalog,
+        )?,
+        None => Vec::new(),
+    };
+
+    // Compute legend with stitch counts, fractional stitches included
+    // Locked threads are listed even when no stitch ended up on them
+    let pinned = |code: &str| config.use_dmc_palette && locked.iter().any(|t| t.code == code);
+    let mut legend_counts: HashMap<String, (LabelUsage, String, String)> = HashMap::new();
+    for (color, usage) in grid.colors.iter().zip(grid.usage()) {
+        if usage.stitches == 0 && !pinned(&color.dmc_code) {
             continue;
         }
-        let entry = legend_counts.entry(stitch.dmc_code.clone()).or_insert((
-            0,
-            stitch.hex.clone(),
+        let entry = legend_counts.entry(color.dmc_code.clone()).or_insert((
+            LabelUsage::default(),
+            color.hex.clone(),
             String::new(),
         ));
-        entry.0 += 1;
+        entry.0.stitches += usage.stitches;
+        entry.0.partial += usage.partial;
+        entry.0.cells += usage.cells;
 
         // Find name for this code
         if entry.2.is_empty() {
-            if let Some(dmc) = dmc_matches.iter().find(|d| d.code == stitch.dmc_code) {
+            if let Some(dmc) = dmc_matches.iter().find(|d| d.code == color.dmc_code) {
                 entry.2 = dmc.name.clone();
             } else {
                 entry.2 = "Quantized Color".to_string();
@@ -849,30 +1754,104 @@ pub fn process_pattern(
         }
     }
 
-    let total_stitches = stitches.iter().filter(|s| s.dmc_code != "Fabric").count() as u32;
+    let total_stitches = grid.stitch_count();
+    let total_cells: f32 = legend_counts
+        .values()
+        .map(|(usage, _, _)| usage.cells)
+        .sum();
 
     let mut legend: Vec<LegendEntry> = legend_counts
         .into_iter()
-        .map(|(code, (count, hex, name))| LegendEntry {
+        .map(|(code, (usage, hex, name))| LegendEntry {
+            blend: dmc_matches
+                .iter()
+                .find(|d| d.code == code)
+                .and_then(|d| d.blend.clone())
+                .filter(|_| config.use_dmc_palette),
+            marker: grid
+                .colors
+                .iter()
+                .find(|c| c.dmc_code == code)
+                .map(|c| c.marker.clone())
+                .unwrap_or_default(),
             dmc_code: code,
+            brand: config.use_dmc_palette.then_some(catalog.brand),
             name,
             hex,
-            stitch_count: count,
-            coverage: count as f32 / total_stitches as f32,
+            stitch_count: usage.stitches,
+            coverage: usage.cells / total_cells.max(1.0),
+            partial_count: usage.partial,
         })
         .collect();
 
     // Sort legend by stitch count (descending)
     legend.sort_by(|a, b| b.stitch_count.cmp(&a.stitch_count));
 
+    let achieved = legend.len() as u32;
+    let shortfall = (achieved < requested).then(|| {
+        let fitted = dmc_matches.len();
+        if fitted >= requested as usize {
+            "Region cleanup, confetti reduction or fabric matching removed threads".to_string()
+        } else if let Some(art) = pixel_art.as_ref().filter(|a| !a.collisions.is_empty()) {
+            let colliding: usize = art.collisions.iter().map(|c| c.art_colors.len()).sum();
+            format!(
+                "{} art colors share a thread with another art color",
+                colliding
+            )
+        } else if shared_threads > 0 && fitted + shared_threads >= requested as usize {
+            match shared_threads {
+                1 => "1 thread was shared between regions".to_string(),
+                n => format!("{} threads were shared between regions", n),
+            }
+        } else if let Some(distinct) = distinct_colors(pixels, mask, requested as usize) {
+            format!("The image only has {} distinct colors", distinct)
+        } else if !config.owned_threads.is_empty() && config.max_purchases.is_some() {
+            format!(
+                "Only owned threads and up to {} purchases were allowed",
+                config.max_purchases.unwrap_or(0)
+            )
+        } else {
+            format!(
+                "Nearby colors still matched the same threads after {} refits",
+                refits
+            )
+        }
+    });
+
     let processing_time_ms = start_time.elapsed().as_millis() as u64;
+    let fabric_hex = rgb_to_hex(fabric_rgb);
 
     Ok(PatternResult {
         width,
         height,
-        stitches,
+        stitches: if config.expand_stitches {
+            grid.to_stitches(width, &fabric_hex)
+        } else {
+            Vec::new()
+        },
+        grid,
         palette: palette_hex,
         dmc_palette: dmc_palette_hex,
+        brand: catalog.brand,
+        suggested_purchases: if config.use_dmc_palette {
+            purchases
+        } else {
+            Vec::new()
+        },
+        physical_size: config
+            .grid
+            .as_ref()
+            .map(|grid| grid.physical_size(width, height)),
+        backstitch,
+        confetti,
+        fabric_hex,
+        thread_count: ThreadCountReport {
+            requested,
+            achieved,
+            refits,
+            shortfall,
+        },
+        pixel_art,
         legend,
         color_mappings,
         total_stitches,
@@ -894,21 +1873,40 @@ pub fn process_pattern_from_path(
 mod tests {
     use super::*;
 
+    fn encode_png(width: u32, height: u32, pixels: &[[u8; 4]]) -> Vec<u8> {
+        let mut buffer = image::RgbaImage::new(width, height);
+        for (i, pixel) in pixels.iter().enumerate() {
+            buffer.put_pixel(i as u32 % width, i as u32 / width, image::Rgba(*pixel));
+        }
+        let mut bytes = Vec::new();
+        image::DynamicImage::ImageRgba8(buffer)
+            .write_to(
+                &mut std::io::Cursor::new(&mut bytes),
+                image::ImageFormat::Png,
+            )
+            .expect("png encode");
+        bytes
+    }
+
     #[test]
     fn test_hex_conversion() {
         assert_eq!(hex_to_rgb("#FF0000"), [255, 0, 0]);
         assert_eq!(hex_to_rgb("#00FF00"), [0, 255, 0]);
         assert_eq!(hex_to_rgb("#0000FF"), [0, 0, 255]);
         assert_eq!(rgb_to_hex([255, 128, 0]), "#FF8000");
+        assert_eq!(parse_hex("ce1938"), Some([206, 25, 56]));
+        assert_eq!(parse_hex("#GG0000"), None);
+        assert_eq!(parse_hex("#FFF"), None);
+        assert_eq!(hex_to_rgb("#FFF"), [0, 0, 0]);
     }
 
     #[test]
     fn test_dmc_palette_lookup() {
-        let palette = DmcPalette::new();
+        let palette = ThreadCatalog::for_brand(ThreadBrand::Dmc).expect("DMC is bundled");
 
         // Red should match to a red DMC color
         let red = rgb_to_lab([255, 0, 0]);
-        let match_red = palette.find_closest(red);
+        let match_red = palette.find_closest(red, ColorMetric::Ciede2000);
         assert!(
             match_red.name.to_lowercase().contains("red")
                 || match_red.hex.to_uppercase().contains("E")
@@ -917,7 +1915,560 @@ mod tests {
 
         // Black should match to DMC 310
         let black = rgb_to_lab([0, 0, 0]);
-        let match_black = palette.find_closest(black);
+        let match_black = palette.find_closest(black, ColorMetric::Ciede2000);
         assert_eq!(match_black.code, "310");
     }
+
+    #[test]
+    fn test_process_pattern_uses_selected_brand() {
+        let pixels = [
+            [0, 0, 0, 255],
+            [0, 0, 0, 255],
+            [255, 255, 255, 255],
+            [0, 0, 0, 255],
+        ];
+        let bytes = encode_png(2, 2, &pixels);
+        let config = ProcessingConfig {
+            color_count: 2,
+            min_region_size: 1,
+            thread_brand: ThreadBrand::Anchor,
+            ..ProcessingConfig::default()
+        };
+
+        let result = process_pattern(&bytes, &config, None).expect("pattern should process");
+        assert_eq!(result.brand, ThreadBrand::Anchor);
+        let black = result
+            .legend
+            .iter()
+            .find(|entry| entry.dmc_code == "403")
+            .expect("black should map to Anchor 403");
+        assert_eq!(black.brand, Some(ThreadBrand::Anchor));
+        assert_eq!(black.stitch_count, 3);
+    }
+
+    #[test]
+    fn test_label_grid_expands_on_request() {
+        let pixels = [
+            [0, 0, 0, 255],
+            [255, 255, 255, 255],
+            [255, 255, 255, 255],
+            [0, 0, 0, 255],
+        ];
+        let bytes = encode_png(2, 2, &pixels);
+        let mask = [255, 255, 0, 255];
+        let config = ProcessingConfig {
+            color_count: 2,
+            min_region_size: 1,
+            ..ProcessingConfig::default()
+        };
+
+        let compact = process_pattern(&bytes, &config, Some(&mask)).expect("pattern");
+        assert!(compact.stitches.is_empty());
+        assert_eq!(compact.grid.labels[2], FABRIC_LABEL);
+        assert_eq!(compact.total_stitches, 3);
+
+        let expanded = process_pattern(
+            &bytes,
+            &ProcessingConfig {
+                expand_stitches: true,
+                ..config
+            },
+            Some(&mask),
+        )
+        .expect("pattern");
+        assert_eq!(expanded.stitches.len(), 4);
+        assert_eq!(expanded.stitches[2].dmc_code, "Fabric");
+        assert_eq!(expanded.stitches[3].dmc_code, "310");
+
+        let mut edited = expanded.clone();
+        for color in &mut edited.grid.colors {
+            color.marker = "S".to_string();
+        }
+        edited.reassign_symbols();
+        assert_ne!(edited.legend[0].marker, edited.legend[1].marker);
+        for stitch in edited.stitches.iter().filter(|s| s.dmc_code != "Fabric") {
+            let entry = edited
+                .legend
+                .iter()
+                .find(|e| e.dmc_code == stitch.dmc_code)
+                .expect("legend entry");
+            assert_eq!(stitch.marker, entry.marker);
+        }
+
+        let rebuilt = LabelGrid::from_stitches(2, 2, &expanded.stitches);
+        assert_eq!(rebuilt.to_stitches(2, "#FFFFFF").len(), 4);
+        for idx in 0..4 {
+            assert_eq!(
+                rebuilt.color(idx).map(|c| &c.dmc_code),
+                compact.grid.color(idx).map(|c| &c.dmc_code)
+            );
+        }
+    }
+
+    #[test]
+    fn test_transparency_and_fabric_color() {
+        // A red logo on a transparent background, plus a black speck
+        let pixels = [
+            [200, 30, 60, 255],
+            [200, 30, 60, 255],
+            [0, 0, 0, 0],
+            [0, 0, 0, 0],
+            [10, 10, 10, 255],
+            [0, 0, 0, 40],
+        ];
+        let bytes = encode_png(3, 2, &pixels);
+        let config = ProcessingConfig {
+            color_count: 2,
+            min_region_size: 1,
+            expand_stitches: true,
+            fabric: FabricConfig {
+                hex: "#101010".to_string(),
+                match_delta_e: Some(5.0),
+                ..FabricConfig::default()
+            },
+            ..ProcessingConfig::default()
+        };
+
+        let pattern = process_pattern(&bytes, &config, None).expect("pattern");
+        assert_eq!(pattern.fabric_hex, "#101010");
+        // Transparent pixels and the near-black thread on black fabric are left bare
+        for idx in [2, 3, 4, 5] {
+            assert_eq!(pattern.grid.labels[idx], FABRIC_LABEL);
+            assert_eq!(pattern.stitches[idx].hex, "#101010");
+        }
+        assert_eq!(pattern.total_stitches, 2);
+        assert_eq!(pattern.legend.len(), 1);
+
+        let stitch_everything = ProcessingConfig {
+            fabric: FabricConfig {
+                alpha_threshold: 0,
+                ..FabricConfig::default()
+            },
+            ..config
+        };
+        let opaque = process_pattern(&bytes, &stitch_everything, None).expect("pattern");
+        assert_eq!(opaque.total_stitches, 6);
+
+        let invalid = ProcessingConfig {
+            fabric: FabricConfig {
+                hex: "linen".to_string(),
+                ..FabricConfig::default()
+            },
+            ..ProcessingConfig::default()
+        };
+        assert!(process_pattern(&bytes, &invalid, None).is_err());
+    }
+
+    #[test]
+    fn test_locked_and_excluded_threads() {
+        let pixels = [
+            [0, 0, 0, 255],
+            [0, 0, 0, 255],
+            [200, 30, 60, 255],
+            [200, 30, 60, 255],
+        ];
+        let bytes = encode_png(2, 2, &pixels);
+        let config = ProcessingConfig {
+            color_count: 2,
+            min_region_size: 1,
+            locked_threads: vec!["321".to_string()],
+            excluded_threads: vec!["310".to_string()],
+            ..ProcessingConfig::default()
+        };
+
+        let result = process_pattern(&bytes, &config, None).expect("pattern should process");
+        assert_eq!(result.color_mappings[0].dmc.code, "321");
+        assert_eq!(result.color_mappings.len(), 2);
+        assert!(result.legend.iter().any(|entry| entry.dmc_code == "321"));
+        assert!(result.grid.colors.iter().all(|c| c.dmc_code != "310"));
+
+        let conflicting = ProcessingConfig {
+            excluded_threads: vec!["321".to_string()],
+            ..config
+        };
+        assert!(process_pattern(&bytes, &conflicting, None).is_err());
+    }
+
+    #[test]
+    fn test_locked_thread_absent_from_image_stays_in_legend() {
+        let pixels = [
+            [0, 0, 0, 255],
+            [0, 0, 0, 255],
+            [200, 30, 60, 255],
+            [200, 30, 60, 255],
+        ];
+        let bytes = encode_png(2, 2, &pixels);
+        let config = ProcessingConfig {
+            color_count: 3,
+            min_region_size: 1,
+            locked_threads: vec!["597".to_string()],
+            ..ProcessingConfig::default()
+        };
+
+        let result = process_pattern(&bytes, &config, None).expect("pattern should process");
+        let turquoise = result
+            .legend
+            .iter()
+            .find(|entry| entry.dmc_code == "597")
+            .expect("locked thread is listed");
+        assert_eq!(turquoise.stitch_count, 0);
+        assert!(!turquoise.marker.is_empty());
+        assert!(result.grid.colors.iter().any(|c| c.dmc_code == "597"));
+        assert_eq!(result.legend.len(), 3);
+    }
+
+    #[test]
+    fn test_locked_thread_on_an_all_fabric_pattern() {
+        let pixels = [[255, 255, 255, 255]; 4];
+        let bytes = encode_png(2, 2, &pixels);
+        let config = ProcessingConfig {
+            color_count: 2,
+            min_region_size: 1,
+            locked_threads: vec!["597".to_string()],
+            fabric: FabricConfig {
+                match_delta_e: Some(5.0),
+                ..FabricConfig::default()
+            },
+            ..ProcessingConfig::default()
+        };
+
+        let result = process_pattern(&bytes, &config, None).expect("pattern should process");
+        assert_eq!(result.total_stitches, 0);
+        let turquoise = result
+            .legend
+            .iter()
+            .find(|entry| entry.dmc_code == "597")
+            .expect("locked thread is listed");
+        assert_eq!(turquoise.coverage, 0.0);
+    }
+
+    #[test]
+    fn test_every_quantizer_keeps_both_colors() {
+        let pixels = [
+            [0, 0, 0, 255],
+            [0, 0, 0, 255],
+            [200, 30, 60, 255],
+            [200, 30, 60, 255],
+        ];
+        let bytes = encode_png(2, 2, &pixels);
+        for quantizer in [
+            Quantizer::Kmeans,
+            Quantizer::MedianCut,
+            Quantizer::Wu,
+            Quantizer::Octree,
+            Quantizer::ThreadDirect,
+        ] {
+            let config = ProcessingConfig {
+                color_count: 2,
+                min_region_size: 1,
+                quantizer,
+                ..ProcessingConfig::default()
+            };
+            let result = process_pattern(&bytes, &config, None).expect("pattern should process");
+            let mut codes: Vec<&str> = result.legend.iter().map(|e| e.dmc_code.as_str()).collect();
+            codes.sort_unstable();
+            assert_eq!(codes, vec!["310", "321"], "{:?}", quantizer);
+            assert_eq!(result.grid.labels[0], result.grid.labels[1]);
+        }
+    }
+
+    #[test]
+    fn test_refits_clusters_that_share_a_thread() {
+        // Mostly near-identical reds, which box and tree quantizers spend several clusters on
+        let mut pixels: Vec<[u8; 4]> = (0..24u8)
+            .map(|i| [196 + i % 6, 28 + i % 3, 58, 255])
+            .collect();
+        for _ in 0..2 {
+            pixels.extend([[0, 0, 0, 255], [255, 255, 255, 255], [20, 40, 160, 255]]);
+        }
+        let bytes = encode_png(6, 5, &pixels);
+        for quantizer in [Quantizer::MedianCut, Quantizer::Octree] {
+            let config = ProcessingConfig {
+                color_count: 4,
+                min_region_size: 1,
+                quantizer,
+                ..ProcessingConfig::default()
+            };
+            let result = process_pattern(&bytes, &config, None).expect("pattern should process");
+            assert_eq!(result.legend.len(), 4, "{:?}", quantizer);
+            assert_eq!(result.color_mappings.len(), 4);
+            assert!(result.thread_count.refits > 0);
+            assert_eq!(result.thread_count.achieved, 4);
+            assert_eq!(result.thread_count.shortfall, None);
+        }
+
+        // Two colors cannot fill four threads, and the report says why
+        let bytes = encode_png(2, 1, &[[0, 0, 0, 255], [255, 255, 255, 255]]);
+        let config = ProcessingConfig {
+            color_count: 4,
+            min_region_size: 1,
+            ..ProcessingConfig::default()
+        };
+        let result = process_pattern(&bytes, &config, None).expect("pattern should process");
+        assert_eq!(result.thread_count.requested, 4);
+        assert_eq!(result.thread_count.achieved, 2);
+        assert_eq!(
+            result.thread_count.shortfall.as_deref(),
+            Some("The image only has 2 distinct colors")
+        );
+    }
+
+    #[test]
+    fn test_soft_mask_weights_are_still_stitched() {
+        let pixels = [
+            [0, 0, 0, 255],
+            [200, 30, 60, 255],
+            [200, 30, 60, 255],
+            [255, 255, 255, 255],
+        ];
+        let bytes = encode_png(2, 2, &pixels);
+        let config = ProcessingConfig {
+            color_count: 2,
+            min_region_size: 1,
+            training_weight: TrainingWeight::Saliency,
+            ..ProcessingConfig::default()
+        };
+        let result = process_pattern(&bytes, &config, Some(&[255, 30, 255, 0]))
+            .expect("pattern should process");
+        assert_ne!(result.grid.labels[1], FABRIC_LABEL);
+        assert_eq!(result.grid.labels[3], FABRIC_LABEL);
+        assert_eq!(result.total_stitches, 3);
+    }
+
+    #[test]
+    fn test_regions_quantize_on_their_own_budget() {
+        // 4x2: the left column pair is a red/yellow/blue/black subject, the right pair a
+        // black background
+        let subject = [
+            [200, 30, 60, 255],
+            [250, 220, 40, 255],
+            [30, 60, 180, 255],
+            [0, 0, 0, 255],
+        ];
+        let mut pixels = Vec::new();
+        for row in 0..2 {
+            pixels.extend([subject[row * 2], subject[row * 2 + 1]]);
+            pixels.extend([[0, 0, 0, 255], [0, 0, 0, 255]]);
+        }
+        let bytes = encode_png(4, 2, &pixels);
+        let config = ProcessingConfig {
+            color_count: 1,
+            min_region_size: 1,
+            regions: vec![RegionBudget {
+                mask: vec![255, 255, 0, 0, 255, 255, 0, 0],
+                color_count: 4,
+                quantizer: Some(Quantizer::Wu),
+                dither: None,
+            }],
+            ..ProcessingConfig::default()
+        };
+
+        let result = process_pattern(&bytes, &config, None).expect("pattern should process");
+        let code = |idx: usize| {
+            result.grid.colors[result.grid.labels[idx] as usize]
+                .dmc_code
+                .as_str()
+        };
+        let mut subject_codes: Vec<&str> = [0, 1, 4, 5].iter().map(|&i| code(i)).collect();
+        subject_codes.sort_unstable();
+        subject_codes.dedup();
+        assert_eq!(subject_codes.len(), 4);
+        assert!([2, 3, 6, 7].iter().all(|&i| code(i) == "310"));
+
+        // Black is in both regions but listed once
+        assert_eq!(result.legend.len(), 4);
+        assert_eq!(result.thread_count.requested, 5);
+        assert_eq!(
+            result.thread_count.shortfall.as_deref(),
+            Some("1 thread was shared between regions")
+        );
+
+        assert_eq!(
+            process_pattern(&bytes, &config, Some(&[255; 3])).err(),
+            Some("Mask has 3 values for a 4x2 image".to_string())
+        );
+
+        let mismatched = ProcessingConfig {
+            regions: vec![RegionBudget {
+                mask: vec![255; 3],
+                color_count: 4,
+                quantizer: None,
+                dither: None,
+            }],
+            ..config
+        };
+        assert!(process_pattern(&bytes, &mismatched, None).is_err());
+    }
+
+    #[test]
+    fn test_pixel_art_stitches_each_art_pixel_once() {
+        // 4x2 sprite drawn 8x larger; black and a near-black both match 310
+        let art = [
+            [200, 30, 60, 255],
+            [250, 220, 40, 255],
+            [30, 60, 180, 255],
+            [0, 0, 0, 255],
+            [3, 3, 3, 255],
+            [0, 0, 0, 255],
+            [200, 30, 60, 255],
+            [250, 220, 40, 255],
+        ];
+        let pixels: Vec<[u8; 4]> = (0..32 * 16)
+            .map(|i| art[(i / 32 / 8) * 4 + (i % 32) / 8])
+            .collect();
+        let bytes = encode_png(32, 16, &pixels);
+        // The last art pixel is left as fabric
+        let mask: Vec<u8> = (0..32 * 16)
+            .map(|i| if i / 32 >= 8 && i % 32 >= 24 { 0 } else { 255 })
+            .collect();
+        let config = ProcessingConfig {
+            pixel_art: Some(PixelArtConfig::default()),
+            grid: Some(StitchGridConfig {
+                fabric_count: 14.0,
+                over: 1,
+                stitch_width: None,
+                finished_width: None,
+                finished_height: None,
+                unit: crate::grid::PhysicalUnit::Inch,
+            }),
+            ..ProcessingConfig::default()
+        };
+
+        let result = process_pattern(&bytes, &config, Some(&mask)).expect("pattern should process");
+        assert_eq!((result.width, result.height), (4, 2));
+        assert_eq!(result.grid.labels[7], FABRIC_LABEL);
+        let code = |idx: usize| {
+            result.grid.colors[result.grid.labels[idx] as usize]
+                .dmc_code
+                .as_str()
+        };
+        assert_eq!(code(0), code(6));
+        assert_eq!(code(3), "310");
+        assert_eq!(code(4), "310");
+        let mut codes: Vec<&str> = (0..4).map(code).collect();
+        codes.dedup();
+        assert_eq!(codes.len(), 4);
+
+        let report = result.pixel_art.expect("pixel art report");
+        assert_eq!(report.scale, 8);
+        assert_eq!(report.colors, 5);
+        assert_eq!(
+            report.collisions,
+            vec![ThreadCollision {
+                dmc_code: "310".to_string(),
+                art_colors: vec!["#000000".to_string(), "#030303".to_string()],
+            }]
+        );
+        assert_eq!(result.legend.len(), 4);
+        assert_eq!(
+            result.thread_count.shortfall.as_deref(),
+            Some("2 art colors share a thread with another art color")
+        );
+        let size = result.physical_size.expect("physical size");
+        assert!((size.width_inches - 4.0 / 14.0).abs() < 1e-6);
+
+        let with_regions = ProcessingConfig {
+            regions: vec![RegionBudget {
+                mask: vec![255; 32 * 16],
+                color_count: 2,
+                quantizer: None,
+                dither: None,
+            }],
+            ..config
+        };
+        assert!(process_pattern(&bytes, &with_regions, None).is_err());
+    }
+
+    #[test]
+    fn test_grid_resamples_to_finished_size() {
+        // 40x20 source, left half black and right half white; the top rows are fabric.
+        let pixels: Vec<[u8; 4]> = (0..800)
+            .map(|i| {
+                if i % 40 < 20 {
+                    [0, 0, 0, 255]
+                } else {
+                    [255, 255, 255, 255]
+                }
+            })
+            .collect();
+        let bytes = encode_png(40, 20, &pixels);
+        let mut mask = vec![255u8; 800];
+        mask[..80].fill(0);
+
+        let config = ProcessingConfig {
+            color_count: 2,
+            min_region_size: 1,
+            grid: Some(StitchGridConfig {
+                fabric_count: 14.0,
+                over: 1,
+                stitch_width: None,
+                finished_width: Some(1.0),
+                finished_height: None,
+                unit: crate::grid::PhysicalUnit::Inch,
+            }),
+            ..ProcessingConfig::default()
+        };
+        let result = process_pattern(&bytes, &config, Some(&mask)).expect("pattern should process");
+        assert_eq!((result.width, result.height), (14, 7));
+        assert_eq!(result.grid.labels.len(), 98);
+        assert_eq!(result.grid.labels[0], FABRIC_LABEL);
+        let left = result.grid.color(14).expect("stitched cell");
+        let right = result.grid.color(27).expect("stitched cell");
+        assert_ne!(left.hex, right.hex);
+        let size = result.physical_size.expect("physical size");
+        assert!((size.width_inches - 1.0).abs() < 1e-6);
+        assert!((size.height_inches - 0.5).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_fractional_stitches_on_diagonal_edge() {
+        // 40x40 source split along its "/" diagonal, stitched as a 10x10 grid.
+        let pixels: Vec<[u8; 4]> = (0..1600)
+            .map(|i| {
+                if i % 40 + i / 40 < 40 {
+                    [0, 0, 0, 255]
+                } else {
+                    [255, 255, 255, 255]
+                }
+            })
+            .collect();
+        let bytes = encode_png(40, 40, &pixels);
+        let config = ProcessingConfig {
+            color_count: 2,
+            min_region_size: 1,
+            grid: Some(StitchGridConfig {
+                fabric_count: 14.0,
+                over: 1,
+                stitch_width: Some(10),
+                finished_width: None,
+                finished_height: None,
+                unit: crate::grid::PhysicalUnit::Inch,
+            }),
+            fractional_stitches: true,
+            expand_stitches: true,
+            ..ProcessingConfig::default()
+        };
+        let result = process_pattern(&bytes, &config, None).expect("pattern should process");
+
+        let cells: Vec<u32> = result.grid.partials.iter().map(|p| p.cell).collect();
+        assert_eq!(cells, (0..10).map(|y| y * 10 + 9 - y).collect::<Vec<_>>());
+        let quarters = result
+            .stitches
+            .iter()
+            .filter(|s| s.kind == StitchKind::Quarter)
+            .count();
+        assert_eq!(quarters, 10);
+        assert_eq!(result.stitches.len(), 110);
+        assert_eq!(result.stitches[9].kind, StitchKind::ThreeQuarter);
+
+        let partial: u32 = result.legend.iter().map(|e| e.partial_count).sum();
+        let stitches: u32 = result.legend.iter().map(|e| e.stitch_count).sum();
+        let coverage: f32 = result.legend.iter().map(|e| e.coverage).sum();
+        assert_eq!((partial, stitches, result.total_stitches), (20, 110, 110));
+        assert!((coverage - 1.0).abs() < 1e-4);
+
+        let rebuilt = LabelGrid::from_stitches(10, 10, &result.stitches);
+        assert_eq!(rebuilt.partials.len(), 10);
+        assert_eq!(rebuilt.usage().iter().map(|u| u.partial).sum::<u32>(), 20);
+    }
 }
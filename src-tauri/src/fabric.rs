@@ -0,0 +1,193 @@
+//! Fabric color and unstitched areas.
+//!
+//! Transparent pixels are left as bare fabric rather than blended onto a background and
+//! stitched. Threads that are practically the fabric's color are dropped too: stitching
+//! white on white Aida or black on black Aida costs hours and is invisible.
+
+use crate::color_metric::ColorMetric;
+use crate::embroidery::{hex_to_rgb, rgb_to_lab, LabelGrid, FABRIC_LABEL};
+use image::RgbaImage;
+use palette::{white_point::D65, Lab};
+use serde::{Deserialize, Serialize};
+
+/// White Aida
+pub const DEFAULT_FABRIC_HEX: &str = "#FFFFFF";
+
+/// Pixels less opaque than this are left as fabric unless a threshold is given
+pub const DEFAULT_ALPHA_THRESHOLD: u8 = 128;
+
+/// Fabric the pattern is stitched on
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct FabricConfig {
+    /// Fabric color as `#RRGGBB`; semi-transparent pixels are blended onto it
+    #[serde(default = "default_fabric_hex")]
+    pub hex: String,
+    /// Pixels with alpha below this are left unstitched; 0 stitches every pixel
+    #[serde(default = "default_alpha_threshold")]
+    pub alpha_threshold: u8,
+    /// Leave cells unstitched when their thread is within this Delta-E of the fabric
+    #[serde(default)]
+    pub match_delta_e: Option<f32>,
+}
+
+fn default_fabric_hex() -> String {
+    DEFAULT_FABRIC_HEX.to_string()
+}
+
+fn default_alpha_threshold() -> u8 {
+    DEFAULT_ALPHA_THRESHOLD
+}
+
+impl Default for FabricConfig {
+    fn default() -> Self {
+        Self {
+            hex: default_fabric_hex(),
+            alpha_threshold: default_alpha_threshold(),
+            match_delta_e: None,
+        }
+    }
+}
+
+/// Combine `mask` with the opaque pixels of `image`, keeping the mask's weights; `None`
+/// when no pixel is transparent enough to change it.
+pub fn alpha_mask(image: &RgbaImage, mask: Option<&[u8]>, alpha_threshold: u8) -> Option<Vec<u8>> {
+    if !image.pixels().any(|p| p[3] < alpha_threshold) {
+        return None;
+    }
+    Some(
+        image
+            .pixels()
+            .enumerate()
+            .map(|(i, p)| {
+                let weight = mask.map(|m| m.get(i).copied().unwrap_or(0)).unwrap_or(255);
+                if p[3] >= alpha_threshold {
+                    weight
+                } else {
+                    0
+                }
+            })
+            .collect(),
+    )
+}
+
+/// Turn every stitch whose thread is within `max_delta_e` of `fabric` into bare fabric.
+///
+/// A split cell keeps whichever of its two threads survives as a half stitch toward that
+/// thread's corner. Returns the number of cells that lost a stitch.
+pub fn drop_fabric_matches(
+    grid: &mut LabelGrid,
+    fabric: Lab<D65, f32>,
+    max_delta_e: f32,
+    metric: ColorMetric,
+) -> u32 {
+    let dropped: Vec<bool> = grid
+        .colors
+        .iter()
+        .map(|color| metric.distance(rgb_to_lab(hex_to_rgb(&color.hex)), fabric) <= max_delta_e)
+        .collect();
+    let is_dropped = |label: u16| dropped.get(label as usize).copied().unwrap_or(false);
+    if !dropped.iter().any(|&d| d) {
+        return 0;
+    }
+
+    let mut changed = 0u32;
+    let mut partials = std::mem::take(&mut grid.partials);
+    partials.retain_mut(|partial| {
+        let cell = partial.cell as usize;
+        let primary = grid.labels[cell];
+        match (is_dropped(primary), is_dropped(partial.secondary)) {
+            (false, false) => true,
+            (false, true) => {
+                partial.secondary = FABRIC_LABEL;
+                changed += 1;
+                true
+            }
+            (true, secondary_dropped) => {
+                if secondary_dropped || partial.secondary == FABRIC_LABEL {
+                    // Nothing left to stitch; the whole-cell pass below clears the label
+                    false
+                } else {
+                    changed += 1;
+                    grid.labels[cell] = partial.secondary;
+                    partial.corner = partial.corner.opposite();
+                    partial.secondary = FABRIC_LABEL;
+                    true
+                }
+            }
+        }
+    });
+
+    for label in grid.labels.iter_mut() {
+        if is_dropped(*label) {
+            *label = FABRIC_LABEL;
+            changed += 1;
+        }
+    }
+    grid.partials = partials;
+    changed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::embroidery::GridColor;
+    use crate::fractional::{PartialCell, StitchCorner};
+
+    fn color(code: &str, hex: &str) -> GridColor {
+        GridColor {
+            dmc_code: code.to_string(),
+            marker: String::new(),
+            hex: hex.to_string(),
+            blend: None,
+        }
+    }
+
+    #[test]
+    fn alpha_mask_keeps_opaque_pixels_inside_the_mask() {
+        let image = RgbaImage::from_raw(
+            4,
+            1,
+            [
+                [0, 0, 0, 255],
+                [0, 0, 0, 10],
+                [0, 0, 0, 200],
+                [0, 0, 0, 255],
+            ]
+            .concat(),
+        )
+        .expect("image");
+        assert_eq!(alpha_mask(&image, None, 128), Some(vec![255, 0, 255, 255]));
+        assert_eq!(
+            alpha_mask(&image, Some(&[1, 1, 80, 0]), 128),
+            Some(vec![1, 0, 80, 0])
+        );
+        assert_eq!(alpha_mask(&image, None, 0), None);
+    }
+
+    #[test]
+    fn threads_matching_the_fabric_are_dropped() {
+        // 3x1: white, black, and a cell split between white and black.
+        let mut grid = LabelGrid {
+            labels: vec![0, 1, 0],
+            colors: vec![color("B5200", "#FFFFFF"), color("310", "#000000")],
+            partials: vec![PartialCell {
+                cell: 2,
+                corner: StitchCorner::TopLeft,
+                secondary: 1,
+            }],
+        };
+        let white = rgb_to_lab([250, 250, 248]);
+        let changed = drop_fabric_matches(&mut grid, white, 5.0, ColorMetric::Ciede2000);
+
+        assert_eq!(changed, 2);
+        assert_eq!(grid.labels, vec![FABRIC_LABEL, 1, 1]);
+        assert_eq!(
+            grid.partials,
+            vec![PartialCell {
+                cell: 2,
+                corner: StitchCorner::BottomRight,
+                secondary: FABRIC_LABEL,
+            }]
+        );
+    }
+}
@@ -0,0 +1,365 @@
+//! Floss quantity and stitching time estimates.
+//!
+//! Thread use is modelled per stitch from the fabric count: a cross stitch lays two
+//! diagonal legs on the front and two straight legs on the back, half cross and tent
+//! stitches one diagonal on the front. The length is multiplied by the strands stitched
+//! with, padded by a waste factor for tails and travel, and divided into six-strand skeins.
+
+use crate::embroidery::PatternResult;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Strands in a skein of stranded cotton
+pub const SKEIN_STRANDS: u32 = 6;
+
+/// Length of a standard stranded cotton skein
+pub const DEFAULT_SKEIN_METERS: f32 = 8.0;
+
+/// Full crosses an average stitcher completes per hour
+pub const DEFAULT_STITCHES_PER_HOUR: f32 = 120.0;
+
+const METERS_PER_INCH: f32 = 0.0254;
+
+/// Extra effort, in full crosses, of starting and ending a thread for a confetti stitch
+const CONFETTI_EFFORT: f32 = 2.0;
+
+/// Effort of one backstitch relative to a full cross
+const BACKSTITCH_EFFORT: f32 = 0.5;
+
+/// How the filled stitches are worked
+#[derive(Debug, Copy, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum StitchTechnique {
+    #[default]
+    Cross,
+    /// One diagonal leg on the front, a short straight leg on the back
+    HalfCross,
+    /// Continental tent stitch: one diagonal on the front and a longer one on the back
+    Tent,
+}
+
+impl StitchTechnique {
+    /// Thread per stitch, in stitch widths.
+    fn thread_per_stitch(self) -> f32 {
+        match self {
+            StitchTechnique::Cross => 2.0 * std::f32::consts::SQRT_2 + 2.0,
+            StitchTechnique::HalfCross => std::f32::consts::SQRT_2 + 1.0,
+            StitchTechnique::Tent => 2.0 * std::f32::consts::SQRT_2,
+        }
+    }
+
+    /// Time per stitch relative to a full cross.
+    fn effort(self) -> f32 {
+        match self {
+            StitchTechnique::Cross => 1.0,
+            StitchTechnique::HalfCross => 0.5,
+            StitchTechnique::Tent => 0.6,
+        }
+    }
+}
+
+/// Fabric and stitching habits to estimate with
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct FlossConfig {
+    /// Fabric threads per inch; defaults to the pattern's stitch grid fabric
+    #[serde(default)]
+    pub fabric_count: Option<f32>,
+    /// Fabric threads each stitch covers
+    #[serde(default = "default_over")]
+    pub over: u8,
+    #[serde(default = "default_strands")]
+    pub strands: u8,
+    #[serde(default = "default_backstitch_strands")]
+    pub backstitch_strands: u8,
+    #[serde(default)]
+    pub technique: StitchTechnique,
+    /// Extra thread for tails and travel, as a fraction of the stitched length
+    #[serde(default = "default_waste_factor")]
+    pub waste_factor: f32,
+    #[serde(default)]
+    pub skein_meters: Option<f32>,
+    #[serde(default)]
+    pub stitches_per_hour: Option<f32>,
+}
+
+fn default_over() -> u8 {
+    1
+}
+
+fn default_strands() -> u8 {
+    2
+}
+
+fn default_backstitch_strands() -> u8 {
+    1
+}
+
+fn default_waste_factor() -> f32 {
+    0.15
+}
+
+impl Default for FlossConfig {
+    fn default() -> Self {
+        Self {
+            fabric_count: None,
+            over: default_over(),
+            strands: default_strands(),
+            backstitch_strands: default_backstitch_strands(),
+            technique: StitchTechnique::Cross,
+            waste_factor: default_waste_factor(),
+            skein_meters: None,
+            stitches_per_hour: None,
+        }
+    }
+}
+
+/// Thread needed of one catalog code
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ThreadEstimate {
+    pub dmc_code: String,
+    /// Total single-strand length, waste included
+    pub meters: f32,
+    pub skeins: u32,
+}
+
+/// Floss and time needed for a whole pattern
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct FlossEstimate {
+    /// Blends are split into their component threads; most thread first
+    pub threads: Vec<ThreadEstimate>,
+    pub total_meters: f32,
+    pub total_skeins: u32,
+    pub stitching_hours: f32,
+    pub stitches_per_inch: f32,
+    pub strands: u8,
+}
+
+/// Estimate floss per thread and stitching time for `pattern`.
+pub fn estimate_floss(
+    pattern: &PatternResult,
+    config: &FlossConfig,
+) -> Result<FlossEstimate, String> {
+    let stitches_per_inch = match config.fabric_count {
+        Some(count) => count / config.over.max(1) as f32,
+        None => pattern
+            .physical_size
+            .as_ref()
+            .map(|size| size.stitches_per_inch)
+            .ok_or("Fabric count is required for patterns without a stitch grid")?,
+    };
+    if !(stitches_per_inch.is_finite() && stitches_per_inch > 0.0) {
+        return Err("Fabric count must be greater than 0".to_string());
+    }
+    for strands in [config.strands, config.backstitch_strands] {
+        if !(1..=SKEIN_STRANDS as u8).contains(&strands) {
+            return Err(format!(
+                "Strand count must be between 1 and {}",
+                SKEIN_STRANDS
+            ));
+        }
+    }
+    if !(config.waste_factor.is_finite() && config.waste_factor >= 0.0) {
+        return Err("Waste factor must be 0 or more".to_string());
+    }
+    let skein_meters = config.skein_meters.unwrap_or(DEFAULT_SKEIN_METERS);
+    let stitches_per_hour = config
+        .stitches_per_hour
+        .unwrap_or(DEFAULT_STITCHES_PER_HOUR);
+    let positive = |value: f32| value.is_finite() && value > 0.0;
+    if !(positive(skein_meters) && positive(stitches_per_hour)) {
+        return Err("Skein length and stitching rate must be greater than 0".to_string());
+    }
+
+    let stitch_meters = METERS_PER_INCH / stitches_per_inch;
+    let waste = 1.0 + config.waste_factor;
+    let mut meters_by_code: HashMap<String, f32> = HashMap::new();
+    let mut effort = 0.0f32;
+
+    // Filled stitches; a blend uses half its strands from each component.
+    let grid = pattern.label_grid();
+    for (color, usage) in grid.colors.iter().zip(grid.usage()) {
+        let meters = usage.cells
+            * config.technique.thread_per_stitch()
+            * stitch_meters
+            * config.strands as f32
+            * waste;
+        match &color.blend {
+            Some(components) => {
+                for code in components {
+                    *meters_by_code.entry(code.clone()).or_insert(0.0) += meters * 0.5;
+                }
+            }
+            None => *meters_by_code.entry(color.dmc_code.clone()).or_insert(0.0) += meters,
+        }
+        effort += usage.cells * config.technique.effort();
+    }
+
+    // Backstitch covers each unit edge once on the front and once on the back.
+    for line in &pattern.backstitch {
+        let units: i32 = line
+            .points
+            .windows(2)
+            .map(|pair| (pair[1].x - pair[0].x).abs() + (pair[1].y - pair[0].y).abs())
+            .sum();
+        *meters_by_code.entry(line.dmc_code.clone()).or_insert(0.0) +=
+            units as f32 * 2.0 * stitch_meters * config.backstitch_strands as f32 * waste;
+        effort += units as f32 * BACKSTITCH_EFFORT;
+    }
+    effort += pattern.confetti.total as f32 * CONFETTI_EFFORT;
+
+    let strand_meters_per_skein = skein_meters * SKEIN_STRANDS as f32;
+    let mut threads: Vec<ThreadEstimate> = meters_by_code
+        .into_iter()
+        .filter(|(_, meters)| *meters > 0.0)
+        .map(|(dmc_code, meters)| ThreadEstimate {
+            dmc_code,
+            meters,
+            skeins: (meters / strand_meters_per_skein).ceil() as u32,
+        })
+        .collect();
+    threads.sort_by(|a, b| {
+        b.meters
+            .total_cmp(&a.meters)
+            .then_with(|| a.dmc_code.cmp(&b.dmc_code))
+    });
+
+    Ok(FlossEstimate {
+        total_meters: threads.iter().map(|t| t.meters).sum(),
+        total_skeins: threads.iter().map(|t| t.skeins).sum(),
+        threads,
+        stitching_hours: effort / stitches_per_hour,
+        stitches_per_inch,
+        strands: config.strands,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backstitch::BackstitchLine;
+    use crate::confetti::ConfettiReport;
+    use crate::embroidery::{GridColor, LabelGrid, ThreadCountReport};
+    use crate::regions::GridPoint;
+    use crate::threads::ThreadBrand;
+
+    fn pattern(colors: Vec<GridColor>, labels: Vec<u16>) -> PatternResult {
+        PatternResult {
+            width: 10,
+            height: labels.len() as u32 / 10,
+            stitches: Vec::new(),
+            grid: LabelGrid {
+                labels,
+                colors,
+                partials: Vec::new(),
+            },
+            palette: Vec::new(),
+            dmc_palette: Vec::new(),
+            brand: ThreadBrand::Dmc,
+            suggested_purchases: Vec::new(),
+            physical_size: None,
+            backstitch: Vec::new(),
+            confetti: ConfettiReport::default(),
+            thread_count: ThreadCountReport::default(),
+            pixel_art: None,
+            fabric_hex: "#FFFFFF".to_string(),
+            legend: Vec::new(),
+            color_mappings: Vec::new(),
+            total_stitches: 0,
+            processing_time_ms: 0,
+        }
+    }
+
+    fn color(code: &str, blend: Option<[&str; 2]>) -> GridColor {
+        GridColor {
+            dmc_code: code.to_string(),
+            marker: String::new(),
+            hex: "#000000".to_string(),
+            blend: blend.map(|[a, b]| [a.to_string(), b.to_string()]),
+        }
+    }
+
+    #[test]
+    fn cross_stitch_length_follows_fabric_and_strands() {
+        let solid = pattern(vec![color("310", None)], vec![0; 100]);
+        let config = FlossConfig {
+            fabric_count: Some(14.0),
+            ..FlossConfig::default()
+        };
+        let estimate = estimate_floss(&solid, &config).expect("estimate");
+
+        // 100 crosses * 4.83 stitch widths * 1/14" * 2 strands * 1.15 waste.
+        let expected = 100.0 * 4.828_427 / 14.0 * 0.0254 * 2.0 * 1.15;
+        assert!((estimate.threads[0].meters - expected).abs() < 1e-3);
+        assert_eq!(estimate.total_skeins, 1);
+        assert!((estimate.stitching_hours - 100.0 / 120.0).abs() < 1e-4);
+
+        // 28ct over two is the same stitch size; half cross uses half the thread.
+        let half = estimate_floss(
+            &solid,
+            &FlossConfig {
+                fabric_count: Some(28.0),
+                over: 2,
+                technique: StitchTechnique::HalfCross,
+                ..FlossConfig::default()
+            },
+        )
+        .expect("estimate");
+        assert!((half.threads[0].meters * 2.0 - expected).abs() < 1e-3);
+
+        assert!(estimate_floss(&solid, &FlossConfig::default()).is_err());
+        for (skein_meters, stitches_per_hour) in [(f32::NAN, 100.0), (8.0, f32::INFINITY)] {
+            let rates = FlossConfig {
+                skein_meters: Some(skein_meters),
+                stitches_per_hour: Some(stitches_per_hour),
+                ..config.clone()
+            };
+            assert!(estimate_floss(&solid, &rates).is_err());
+        }
+        assert!(estimate_floss(
+            &solid,
+            &FlossConfig {
+                strands: 7,
+                ..config
+            }
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn blends_split_and_backstitch_adds_to_its_thread() {
+        let mut mixed = pattern(
+            vec![color("310", None), color("3799+413", Some(["3799", "413"]))],
+            [vec![0u16; 50], vec![1u16; 50]].concat(),
+        );
+        mixed.backstitch = vec![BackstitchLine {
+            dmc_code: "310".to_string(),
+            hex: "#000000".to_string(),
+            points: vec![GridPoint { x: 0, y: 5 }, GridPoint { x: 10, y: 5 }],
+        }];
+        let estimate = estimate_floss(
+            &mixed,
+            &FlossConfig {
+                fabric_count: Some(14.0),
+                waste_factor: 0.0,
+                ..FlossConfig::default()
+            },
+        )
+        .expect("estimate");
+
+        let meters = |code: &str| {
+            estimate
+                .threads
+                .iter()
+                .find(|t| t.dmc_code == code)
+                .map(|t| t.meters)
+                .unwrap_or(0.0)
+        };
+        let fifty = 50.0 * 4.828_427 / 14.0 * 0.0254 * 2.0;
+        let backstitch = 10.0 * 2.0 / 14.0 * 0.0254;
+        assert!((meters("310") - fifty - backstitch).abs() < 1e-4);
+        assert!((meters("3799") - fifty * 0.5).abs() < 1e-4);
+        assert!((meters("413") - fifty * 0.5).abs() < 1e-4);
+        assert_eq!(meters("3799+413"), 0.0);
+        assert_eq!(estimate.threads[0].dmc_code, "310");
+    }
+}
@@ -0,0 +1,367 @@
+//! Fractional stitches along diagonal color edges.
+//!
+//! One whole cross per cell turns a diagonal edge into a staircase. When a stitch cell
+//! covers several source pixels, the pixels under each half of the cell are compared: if
+//! splitting the cell along a diagonal explains them much better than a single color, the
+//! cell becomes a partial stitch. Two threads share the cell as a three-quarter stitch and
+//! a quarter stitch in the opposite corner; a thread against fabric becomes a half stitch.
+
+use crate::color_metric::ColorMetric;
+use crate::embroidery::FABRIC_LABEL;
+use palette::{white_point::D65, Lab};
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// Sub-samples per cell side
+const SAMPLES: u32 = 6;
+
+/// Cells narrower than this many source pixels carry no sub-cell detail
+const MIN_CELL_PIXELS: f32 = 1.5;
+
+/// A split must cost less than this share of the whole-cell error
+const SPLIT_GAIN: f32 = 0.6;
+
+/// Error charged for a stitched sample under fabric, or fabric under a stitch
+const FABRIC_MISMATCH: f32 = 50.0;
+
+/// How much of a cell a stitch covers
+#[derive(Debug, Copy, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum StitchKind {
+    /// Whole cross stitch
+    #[default]
+    Full,
+    /// Half the cell, sharing it with a quarter stitch of another thread
+    ThreeQuarter,
+    /// Half the cell, the other half left as fabric
+    Half,
+    /// The quadrant at its corner
+    Quarter,
+}
+
+impl StitchKind {
+    /// Thread used relative to a whole cross.
+    pub fn cell_fraction(self) -> f32 {
+        match self {
+            StitchKind::Full => 1.0,
+            StitchKind::ThreeQuarter => 0.75,
+            StitchKind::Half => 0.5,
+            StitchKind::Quarter => 0.25,
+        }
+    }
+}
+
+/// Cell corner a partial stitch is anchored to
+#[derive(Debug, Copy, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum StitchCorner {
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+}
+
+impl StitchCorner {
+    pub fn opposite(self) -> Self {
+        match self {
+            StitchCorner::TopLeft => StitchCorner::BottomRight,
+            StitchCorner::TopRight => StitchCorner::BottomLeft,
+            StitchCorner::BottomLeft => StitchCorner::TopRight,
+            StitchCorner::BottomRight => StitchCorner::TopLeft,
+        }
+    }
+}
+
+/// A cell split along a diagonal
+///
+/// The cell's own grid label fills the triangle toward `corner`. `secondary` is stitched as
+/// a quarter stitch in the opposite corner, or is [`FABRIC_LABEL`] when the rest of the
+/// cell stays bare.
+#[derive(Debug, Copy, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct PartialCell {
+    pub cell: u32,
+    pub corner: StitchCorner,
+    pub secondary: u16,
+}
+
+impl PartialCell {
+    /// Kind of the stitch in the cell's own label.
+    pub fn primary_kind(&self) -> StitchKind {
+        if self.secondary == FABRIC_LABEL {
+            StitchKind::Half
+        } else {
+            StitchKind::ThreeQuarter
+        }
+    }
+}
+
+/// Signed distance of (u, v) from a cell diagonal; positive toward the first corner
+type DiagonalSide = fn(f32, f32) -> f32;
+
+/// Corner of a half-cell triangle and the label it gets
+type Half = (StitchCorner, u16);
+
+struct Sample {
+    u: f32,
+    v: f32,
+    lab: Lab<D65, f32>,
+    stitched: bool,
+}
+
+/// Split edge cells of `labels` where the source shows a diagonal color edge.
+///
+/// `labels` is the `dst` stitch grid (fabric as [`FABRIC_LABEL`]) indexing `palette`; it is
+/// updated in place so every split cell holds the label of its larger stitch. Returns the
+/// split cells in cell order. Nothing is split unless cells span several source pixels.
+pub fn split_diagonal_cells(
+    source: &[Lab<D65, f32>],
+    source_mask: Option<&[u8]>,
+    src: (u32, u32),
+    labels: &mut [u16],
+    dst: (u32, u32),
+    palette: &[Lab<D65, f32>],
+    metric: ColorMetric,
+) -> Vec<PartialCell> {
+    let (src_w, src_h) = src;
+    let (dst_w, dst_h) = dst;
+    if dst_w == 0 || dst_h == 0 || palette.is_empty() {
+        return Vec::new();
+    }
+    let scale_x = src_w as f32 / dst_w as f32;
+    let scale_y = src_h as f32 / dst_h as f32;
+    if scale_x < MIN_CELL_PIXELS || scale_y < MIN_CELL_PIXELS {
+        return Vec::new();
+    }
+
+    let grid: &[u16] = labels;
+    let splits: Vec<(u16, PartialCell)> = (0..grid.len())
+        .into_par_iter()
+        .filter_map(|idx| {
+            let x = (idx as u32 % dst_w) as i32;
+            let y = (idx as u32 / dst_w) as i32;
+            let current = grid[idx];
+            let on_edge = [(-1i32, 0i32), (1, 0), (0, -1), (0, 1)]
+                .iter()
+                .any(|(dx, dy)| {
+                    let (nx, ny) = (x + dx, y + dy);
+                    nx >= 0
+                        && ny >= 0
+                        && nx < dst_w as i32
+                        && ny < dst_h as i32
+                        && grid[(ny * dst_w as i32 + nx) as usize] != current
+                });
+            if !on_edge {
+                return None;
+            }
+
+            let samples: Vec<Sample> = (0..SAMPLES * SAMPLES)
+                .map(|s| {
+                    let u = ((s % SAMPLES) as f32 + 0.5) / SAMPLES as f32;
+                    let v = ((s / SAMPLES) as f32 + 0.5) / SAMPLES as f32;
+                    let sx = (((x as f32 + u) * scale_x) as u32).min(src_w - 1);
+                    let sy = (((y as f32 + v) * scale_y) as u32).min(src_h - 1);
+                    let sidx = (sy * src_w + sx) as usize;
+                    Sample {
+                        u,
+                        v,
+                        lab: source[sidx],
+                        stitched: source_mask.map(|m| m[sidx] > 0).unwrap_or(true),
+                    }
+                })
+                .collect();
+
+            let cost = |sample: &Sample, label: u16| -> f32 {
+                match (sample.stitched, label == FABRIC_LABEL) {
+                    (true, false) => metric.distance(sample.lab, palette[label as usize]),
+                    (false, true) => 0.0,
+                    _ => FABRIC_MISMATCH,
+                }
+            };
+            let whole: f32 = samples.iter().map(|s| cost(s, current)).sum();
+            if whole <= 0.0 {
+                return None;
+            }
+
+            // Each diagonal splits the cell into a triangle toward each of two corners.
+            let diagonals: [(StitchCorner, StitchCorner, DiagonalSide); 2] = [
+                (StitchCorner::BottomLeft, StitchCorner::TopRight, |u, v| {
+                    v - u
+                }),
+                (StitchCorner::TopLeft, StitchCorner::BottomRight, |u, v| {
+                    1.0 - u - v
+                }),
+            ];
+            let mut best: Option<(f32, Half, Half)> = None;
+            for (corner_a, corner_b, side) in diagonals {
+                let half_a: Vec<&Sample> =
+                    samples.iter().filter(|s| side(s.u, s.v) > 1e-4).collect();
+                let half_b: Vec<&Sample> =
+                    samples.iter().filter(|s| side(s.u, s.v) < -1e-4).collect();
+                let label_a = half_label(&half_a, palette, metric);
+                let label_b = half_label(&half_b, palette, metric);
+                if label_a == label_b {
+                    continue;
+                }
+                let split: f32 = samples
+                    .iter()
+                    .map(|s| {
+                        let d = side(s.u, s.v);
+                        if d > 1e-4 {
+                            cost(s, label_a)
+                        } else if d < -1e-4 {
+                            cost(s, label_b)
+                        } else {
+                            cost(s, label_a).min(cost(s, label_b))
+                        }
+                    })
+                    .sum();
+                if best.map(|(c, _, _)| split < c).unwrap_or(true) {
+                    best = Some((split, (corner_a, label_a), (corner_b, label_b)));
+                }
+            }
+
+            let (split, a, b) = best?;
+            if split >= whole * SPLIT_GAIN {
+                return None;
+            }
+            // The larger stitch goes to the thread side, or to the cell's current thread.
+            let ((corner, primary), (_, secondary)) = if a.1 == FABRIC_LABEL
+                || (b.1 != FABRIC_LABEL && b.1 == current && a.1 != current)
+            {
+                (b, a)
+            } else {
+                (a, b)
+            };
+            Some((
+                primary,
+                PartialCell {
+                    cell: idx as u32,
+                    corner,
+                    secondary,
+                },
+            ))
+        })
+        .collect();
+
+    splits
+        .into_iter()
+        .map(|(primary, partial)| {
+            labels[partial.cell as usize] = primary;
+            partial
+        })
+        .collect()
+}
+
+/// Nearest palette label for the samples of one half, or fabric if most are unstitched.
+fn half_label(samples: &[&Sample], palette: &[Lab<D65, f32>], metric: ColorMetric) -> u16 {
+    let stitched: Vec<&&Sample> = samples.iter().filter(|s| s.stitched).collect();
+    if stitched.len() * 2 < samples.len() || stitched.is_empty() {
+        return FABRIC_LABEL;
+    }
+    let count = stitched.len() as f32;
+    let mean = Lab::new(
+        stitched.iter().map(|s| s.lab.l).sum::<f32>() / count,
+        stitched.iter().map(|s| s.lab.a).sum::<f32>() / count,
+        stitched.iter().map(|s| s.lab.b).sum::<f32>() / count,
+    );
+    palette
+        .iter()
+        .enumerate()
+        .min_by(|(_, a), (_, b)| {
+            metric
+                .distance(mean, **a)
+                .total_cmp(&metric.distance(mean, **b))
+        })
+        .map(|(idx, _)| idx as u16)
+        .unwrap_or(FABRIC_LABEL)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `size` x `size` source split along its "/" diagonal: black above, white below.
+    fn diagonal_source(size: u32) -> Vec<Lab<D65, f32>> {
+        (0..size * size)
+            .map(|i| {
+                let (x, y) = (i % size, i / size);
+                if x + y < size {
+                    Lab::new(0.0, 0.0, 0.0)
+                } else {
+                    Lab::new(100.0, 0.0, 0.0)
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn diagonal_edge_cells_become_three_quarter_and_quarter() {
+        let palette = [Lab::new(0.0, 0.0, 0.0), Lab::new(100.0, 0.0, 0.0)];
+        let source = diagonal_source(32);
+        // 4x4 staircase: cells above the anti-diagonal black, the rest white.
+        let mut labels: Vec<u16> = (0..16)
+            .map(|i| if i % 4 + i / 4 < 3 { 0 } else { 1 })
+            .collect();
+        let partials = split_diagonal_cells(
+            &source,
+            None,
+            (32, 32),
+            &mut labels,
+            (4, 4),
+            &palette,
+            ColorMetric::Ciede2000,
+        );
+
+        let cells: Vec<u32> = partials.iter().map(|p| p.cell).collect();
+        assert_eq!(cells, vec![3, 6, 9, 12]);
+        for partial in &partials {
+            assert_eq!(partial.primary_kind(), StitchKind::ThreeQuarter);
+            let (primary, corner) = (labels[partial.cell as usize], partial.corner);
+            // Whichever thread keeps the cell, it fills its own side of the "/" diagonal.
+            match primary {
+                0 => assert_eq!(corner, StitchCorner::TopLeft),
+                _ => assert_eq!(corner, StitchCorner::BottomRight),
+            }
+            assert_eq!(partial.secondary, 1 - primary);
+        }
+
+        // One stitch per source pixel leaves nothing to split.
+        let mut labels: Vec<u16> = (0..16).map(|i| (i % 4 + i / 4 >= 4) as u16).collect();
+        let none = split_diagonal_cells(
+            &diagonal_source(4),
+            None,
+            (4, 4),
+            &mut labels,
+            (4, 4),
+            &palette,
+            ColorMetric::Ciede2000,
+        );
+        assert!(none.is_empty());
+    }
+
+    #[test]
+    fn thread_against_fabric_becomes_a_half_stitch() {
+        let palette = [Lab::new(0.0, 0.0, 0.0)];
+        let source = vec![Lab::new(0.0, 0.0, 0.0); 64];
+        let mask: Vec<u8> = (0..64u32)
+            .map(|i| if i % 8 + i / 8 < 12 { 255 } else { 0 })
+            .collect();
+        let f = FABRIC_LABEL;
+        let mut labels = vec![0, 0, 0, f];
+        let partials = split_diagonal_cells(
+            &source,
+            Some(&mask),
+            (8, 8),
+            &mut labels,
+            (2, 2),
+            &palette,
+            ColorMetric::Ciede2000,
+        );
+
+        let half: Vec<&PartialCell> = partials.iter().filter(|p| p.cell == 3).collect();
+        assert_eq!(half.len(), 1);
+        assert_eq!(labels[3], 0);
+        assert_eq!(half[0].corner, StitchCorner::TopLeft);
+        assert_eq!(half[0].primary_kind(), StitchKind::Half);
+    }
+}
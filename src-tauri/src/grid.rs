@@ -0,0 +1,297 @@
+//! Physical-size stitch grids.
+//!
+//! A pattern is stitched on fabric with a fixed number of stitches per inch, so the stitch
+//! grid should follow the finished size rather than the source resolution. The source is
+//! resampled in LAB with area averaging: every stitch takes the coverage-weighted mean of
+//! the source pixels under it, which avoids the aliasing of nearest-neighbour scaling.
+
+use palette::{white_point::D65, Lab};
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+
+const MM_PER_INCH: f32 = 25.4;
+
+/// Most stitches along either side of a grid; 2000 is over 11 feet of 14ct Aida
+pub const MAX_GRID_STITCHES: u32 = 2000;
+
+/// Length unit for finished sizes and physical hoops
+#[derive(Debug, Copy, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum PhysicalUnit {
+    #[default]
+    Inch,
+    Millimeter,
+}
+
+impl PhysicalUnit {
+    fn per_inch(self) -> f32 {
+        match self {
+            PhysicalUnit::Inch => 1.0,
+            PhysicalUnit::Millimeter => MM_PER_INCH,
+        }
+    }
+}
+
+/// Fabric and target size for the stitch grid
+///
+/// `stitch_width` wins over a finished size. With both `finished_width` and
+/// `finished_height` the design is fitted inside that box, keeping its aspect ratio.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct StitchGridConfig {
+    /// Fabric threads per inch, e.g. 14 for 14ct Aida or 28 for 28ct evenweave
+    pub fabric_count: f32,
+    /// Fabric threads each stitch covers: 1 for Aida, 2 for evenweave and linen
+    #[serde(default = "default_over")]
+    pub over: u8,
+    #[serde(default)]
+    pub stitch_width: Option<u32>,
+    #[serde(default)]
+    pub finished_width: Option<f32>,
+    #[serde(default)]
+    pub finished_height: Option<f32>,
+    #[serde(default)]
+    pub unit: PhysicalUnit,
+}
+
+fn default_over() -> u8 {
+    1
+}
+
+/// Finished dimensions of a stitch grid on its fabric
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct PhysicalSize {
+    pub stitches_per_inch: f32,
+    pub width_inches: f32,
+    pub height_inches: f32,
+}
+
+impl StitchGridConfig {
+    /// Stitches per inch of finished work.
+    pub fn stitches_per_inch(&self) -> f32 {
+        self.fabric_count / self.over.max(1) as f32
+    }
+
+    /// Stitches per `unit` of finished work.
+    pub fn stitches_per_unit(&self) -> f32 {
+        self.stitches_per_inch() / self.unit.per_inch()
+    }
+
+    /// Stitch grid dimensions for a `source_width` x `source_height` image.
+    pub fn dimensions(&self, source_width: u32, source_height: u32) -> Result<(u32, u32), String> {
+        if !(self.fabric_count.is_finite() && self.fabric_count > 0.0) {
+            return Err("Fabric count must be greater than 0".to_string());
+        }
+        if source_width == 0 || source_height == 0 {
+            return Err("Image has no pixels".to_string());
+        }
+
+        let aspect = source_height as f32 / source_width as f32;
+        let per_unit = self.stitches_per_unit();
+        let (width, height) = match (self.stitch_width, self.finished_width, self.finished_height) {
+            (Some(stitches), _, _) => (stitches as f32, stitches as f32 * aspect),
+            (None, Some(w), Some(h)) => {
+                let scale =
+                    (w * per_unit / source_width as f32).min(h * per_unit / source_height as f32);
+                (source_width as f32 * scale, source_height as f32 * scale)
+            }
+            (None, Some(w), None) => (w * per_unit, w * per_unit * aspect),
+            (None, None, Some(h)) => (h * per_unit / aspect, h * per_unit),
+            (None, None, None) => {
+                return Err("Stitch grid needs a stitch width or a finished size".to_string())
+            }
+        };
+
+        if !(width.is_finite() && height.is_finite()) || width < 0.5 || height < 0.5 {
+            return Err("Finished size is too small for the fabric count".to_string());
+        }
+        let limit = MAX_GRID_STITCHES as f32 + 0.5;
+        if width >= limit || height >= limit {
+            return Err(format!(
+                "A {:.0}x{:.0} stitch grid is above the maximum of {} stitches per side",
+                width, height, MAX_GRID_STITCHES
+            ));
+        }
+        Ok((width.round() as u32, height.round() as u32))
+    }
+
+    /// Source pixels per `unit` of finished work when a `source_width` x `source_height`
+    /// image is stitched on this grid.
+    pub fn source_pixels_per_unit(
+        &self,
+        unit: PhysicalUnit,
+        source_width: u32,
+        source_height: u32,
+    ) -> Result<f32, String> {
+        let (width, _) = self.dimensions(source_width, source_height)?;
+        let stitches_per_unit = StitchGridConfig {
+            unit,
+            ..self.clone()
+        }
+        .stitches_per_unit();
+        Ok(stitches_per_unit * source_width as f32 / width as f32)
+    }
+
+    /// Finished size of a `width` x `height` stitch grid on this fabric.
+    pub fn physical_size(&self, width: u32, height: u32) -> PhysicalSize {
+        let per_inch = self.stitches_per_inch();
+        PhysicalSize {
+            stitches_per_inch: per_inch,
+            width_inches: width as f32 / per_inch,
+            height_inches: height as f32 / per_inch,
+        }
+    }
+}
+
+/// Area-average `pixels` from `src` dimensions down (or up) to `dst` dimensions.
+pub fn resample_lab(
+    pixels: &[Lab<D65, f32>],
+    src: (u32, u32),
+    dst: (u32, u32),
+) -> Vec<Lab<D65, f32>> {
+    resample(src, dst, |idx| {
+        [pixels[idx].l, pixels[idx].a, pixels[idx].b]
+    })
+    .into_iter()
+    .map(|[l, a, b]| Lab::new(l, a, b))
+    .collect()
+}
+
+/// Resample a fabric mask; a stitch is kept when most of its area is stitched, with the
+/// mean weight of its stitched pixels.
+pub fn resample_mask(mask: &[u8], src: (u32, u32), dst: (u32, u32)) -> Vec<u8> {
+    resample(src, dst, |idx| {
+        let weight = mask.get(idx).copied().unwrap_or(0) as f32;
+        [if weight > 0.0 { 1.0 } else { 0.0 }, weight, 0.0]
+    })
+    .into_iter()
+    .map(|[coverage, weight, _]| {
+        if coverage >= 0.5 {
+            (weight / coverage).round().clamp(1.0, 255.0) as u8
+        } else {
+            0
+        }
+    })
+    .collect()
+}
+
+fn resample<F>(src: (u32, u32), dst: (u32, u32), sample: F) -> Vec<[f32; 3]>
+where
+    F: Fn(usize) -> [f32; 3] + Sync,
+{
+    let (src_w, src_h) = src;
+    let (dst_w, dst_h) = dst;
+    let scale_x = src_w as f32 / dst_w as f32;
+    let scale_y = src_h as f32 / dst_h as f32;
+
+    (0..(dst_w * dst_h) as usize)
+        .into_par_iter()
+        .map(|i| {
+            let dx = (i as u32 % dst_w) as f32;
+            let dy = (i as u32 / dst_w) as f32;
+            let (x0, x1) = (dx * scale_x, (dx + 1.0) * scale_x);
+            let (y0, y1) = (dy * scale_y, (dy + 1.0) * scale_y);
+
+            let mut sum = [0.0f32; 3];
+            let mut total = 0.0f32;
+            for sy in y0.floor() as u32..(y1.ceil() as u32).min(src_h) {
+                let wy = (y1.min(sy as f32 + 1.0) - y0.max(sy as f32)).max(0.0);
+                for sx in x0.floor() as u32..(x1.ceil() as u32).min(src_w) {
+                    let wx = (x1.min(sx as f32 + 1.0) - x0.max(sx as f32)).max(0.0);
+                    let weight = wx * wy;
+                    if weight <= 0.0 {
+                        continue;
+                    }
+                    let value = sample((sy * src_w + sx) as usize);
+                    for (acc, v) in sum.iter_mut().zip(value) {
+                        *acc += v * weight;
+                    }
+                    total += weight;
+                }
+            }
+            sum.map(|v| v / total.max(f32::EPSILON))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn grid(fabric_count: f32, over: u8) -> StitchGridConfig {
+        StitchGridConfig {
+            fabric_count,
+            over,
+            stitch_width: None,
+            finished_width: None,
+            finished_height: None,
+            unit: PhysicalUnit::Inch,
+        }
+    }
+
+    #[test]
+    fn finished_size_follows_fabric_count() {
+        let aida = StitchGridConfig {
+            finished_width: Some(10.0),
+            ..grid(14.0, 1)
+        };
+        assert_eq!(aida.dimensions(4000, 3000), Ok((140, 105)));
+
+        // 28ct over two stitches like 14ct.
+        let evenweave = StitchGridConfig {
+            finished_width: Some(254.0),
+            unit: PhysicalUnit::Millimeter,
+            ..grid(28.0, 2)
+        };
+        assert_eq!(evenweave.dimensions(4000, 3000), Ok((140, 105)));
+
+        let boxed = StitchGridConfig {
+            finished_width: Some(10.0),
+            finished_height: Some(5.0),
+            ..grid(18.0, 1)
+        };
+        assert_eq!(boxed.dimensions(4000, 3000), Ok((120, 90)));
+        assert!((boxed.physical_size(120, 90).width_inches - 6.6667).abs() < 1e-3);
+
+        // 140 stitches from 4000 pixels: an inch of finished work spans 400 pixels
+        let per_inch = aida.source_pixels_per_unit(PhysicalUnit::Inch, 4000, 3000);
+        assert!((per_inch.expect("grid") - 400.0).abs() < 1e-3);
+        let per_mm = aida.source_pixels_per_unit(PhysicalUnit::Millimeter, 4000, 3000);
+        assert!((per_mm.expect("grid") - 400.0 / 25.4).abs() < 1e-3);
+
+        assert!(grid(14.0, 1).dimensions(100, 100).is_err());
+
+        // Sizes from the frontend are capped before anything is allocated
+        let huge = StitchGridConfig {
+            stitch_width: Some(100_000),
+            ..grid(14.0, 1)
+        };
+        assert!(huge.dimensions(4000, 3000).is_err());
+        let tall = StitchGridConfig {
+            stitch_width: Some(MAX_GRID_STITCHES),
+            ..grid(14.0, 1)
+        };
+        assert!(tall.dimensions(1000, 3000).is_err());
+        assert_eq!(tall.dimensions(3000, 1000), Ok((2000, 667)));
+    }
+
+    #[test]
+    fn area_average_blends_covered_pixels() {
+        let pixels = vec![
+            Lab::new(0.0, 0.0, 0.0),
+            Lab::new(100.0, 0.0, 0.0),
+            Lab::new(0.0, 10.0, 0.0),
+            Lab::new(100.0, 10.0, 0.0),
+        ];
+        let out = resample_lab(&pixels, (2, 2), (1, 1));
+        assert!((out[0].l - 50.0).abs() < 1e-3);
+        assert!((out[0].a - 5.0).abs() < 1e-3);
+
+        let mask = resample_mask(&[255, 255, 0, 255], (2, 2), (1, 1));
+        assert_eq!(mask, vec![255]);
+        let mask = resample_mask(&[255, 0, 0, 0], (2, 2), (1, 1));
+        assert_eq!(mask, vec![0]);
+        // Soft weights average over the stitched part of the cell
+        let mask = resample_mask(&[200, 100, 0, 60], (2, 2), (1, 1));
+        assert_eq!(mask, vec![120]);
+    }
+}
@@ -1,5 +1,8 @@
+use crate::adjust::{apply_adjustments, ImageAdjustments};
 use crate::embroidery::{process_pattern, ProcessingConfig};
+use crate::grid::{PhysicalUnit, StitchGridConfig};
 use crate::stage4::{build_stage4_regions, Stage4Config, Stage4Contract, Stage4Preset};
+use crate::threads::ThreadBrand;
 use image::GenericImageView;
 use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
@@ -8,7 +11,7 @@ use std::path::PathBuf;
 use std::time::Instant;
 use tauri::Manager;
 
-const PIPELINE_CACHE_VERSION: u8 = 8; // Bumped for Stage 4 contract + preset pipeline
+const PIPELINE_CACHE_VERSION: u8 = 12; // Bumped for hoops sized on the stitch grid
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -19,6 +22,37 @@ pub struct HoopConfig {
     pub width: f32,
     pub height: f32,
     pub rotation: f32,
+    /// When set, center and size are measured in this unit of the finished piece from its
+    /// top-left corner, which needs the stitch grid the image is stitched on
+    #[serde(default)]
+    pub unit: Option<PhysicalUnit>,
+}
+
+impl HoopConfig {
+    /// This hoop with center and size converted to pixels of a `width` x `height` image.
+    pub fn in_pixels(
+        &self,
+        grid: Option<&StitchGridConfig>,
+        width: u32,
+        height: u32,
+    ) -> Result<HoopConfig, String> {
+        let scale = match (self.unit, grid) {
+            (None, _) => 1.0,
+            (Some(unit), Some(grid)) => grid.source_pixels_per_unit(unit, width, height)?,
+            (Some(_), None) => {
+                return Err("A hoop in physical units needs a stitch grid".to_string())
+            }
+        };
+        Ok(HoopConfig {
+            shape: self.shape,
+            center_x: self.center_x * scale,
+            center_y: self.center_y * scale,
+            width: self.width * scale,
+            height: self.height * scale,
+            rotation: self.rotation,
+            unit: None,
+        })
+    }
 }
 
 #[derive(Debug, Clone, Copy, Serialize, Deserialize)]
@@ -89,11 +123,25 @@ pub fn process_image_pipeline(
     color_count: u8,
     detail_level: f32,
     hoop_config: HoopConfig,
+    adjustments: Option<ImageAdjustments>,
+    thread_brand: Option<ThreadBrand>,
+    grid: Option<StitchGridConfig>,
 ) -> Result<RegionData, String> {
     let total_start = Instant::now();
     let color_count = color_count.clamp(2, 64);
     let detail_level = detail_level.clamp(0.0, 1.0);
-    let cache_key = build_cache_key(&image_data, color_count, detail_level, &hoop_config);
+    let adjustments = adjustments.filter(|a| !a.is_identity());
+    // Project settings record "Custom" for patterns that keep their quantized colors
+    let thread_brand = thread_brand.unwrap_or_default();
+    let cache_key = build_cache_key(
+        &image_data,
+        color_count,
+        detail_level,
+        &hoop_config,
+        adjustments.as_ref(),
+        thread_brand,
+        grid.as_ref(),
+    );
 
     if let Some(cached) = read_cache(app, &cache_key)? {
         return Ok(cached);
@@ -120,6 +168,11 @@ pub fn process_image_pipeline(
     let filtered = imageproc::filter::median_filter(&image_buffer, x_radius, y_radius);
     image_buffer = filtered;
 
+    // 2. Tonal and color pre-adjustments, on the denoised pixels
+    if let Some(adjustments) = &adjustments {
+        apply_adjustments(&mut image_buffer, adjustments)?;
+    }
+
     // Convert back to raw bytes for pattern processing
     let mut image_data_filtered = Vec::new();
     let mut cursor = std::io::Cursor::new(&mut image_data_filtered);
@@ -137,12 +190,19 @@ pub fn process_image_pipeline(
     };
     let config = ProcessingConfig {
         color_count: color_count as u32,
-        use_dmc_palette: true,
+        use_dmc_palette: thread_brand != ThreadBrand::Custom,
+        thread_brand: match thread_brand {
+            ThreadBrand::Custom => ThreadBrand::Dmc,
+            brand => brand,
+        },
         smoothing_amount: 0.4 + (1.0 - detail_level) * 0.4,
         simplify_amount: 0.2 + (1.0 - detail_level) * 0.5,
         min_region_size,
+        ..ProcessingConfig::default()
     };
-    let hoop_mask = build_hoop_mask(width, height, &hoop_config);
+    // The grid only places a physical hoop; the coloring book stays at source resolution
+    let hoop = hoop_config.in_pixels(grid.as_ref(), width, height)?;
+    let hoop_mask = build_hoop_mask(width, height, &hoop);
     // Process pattern on the FILTERED image
     let pattern = process_pattern(&image_data_filtered, &config, Some(&hoop_mask))?;
     let quantize_ms = quantize_start.elapsed().as_millis() as u64;
@@ -213,6 +273,9 @@ fn build_cache_key(
     color_count: u8,
     detail_level: f32,
     hoop_config: &HoopConfig,
+    adjustments: Option<&ImageAdjustments>,
+    thread_brand: ThreadBrand,
+    grid: Option<&StitchGridConfig>,
 ) -> String {
     let mut hasher = Sha256::new();
     hasher.update([PIPELINE_CACHE_VERSION]);
@@ -229,6 +292,48 @@ fn build_cache_key(
         HoopShape::Square => 1,
         HoopShape::Oval => 2,
     }]);
+    hasher.update([match hoop_config.unit {
+        None => 0,
+        Some(PhysicalUnit::Inch) => 1,
+        Some(PhysicalUnit::Millimeter) => 2,
+    }]);
+    match grid {
+        None => hasher.update([0]),
+        Some(grid) => {
+            hasher.update([1, grid.over]);
+            hasher.update(grid.fabric_count.to_le_bytes());
+            hasher.update(grid.stitch_width.unwrap_or(0).to_le_bytes());
+            hasher.update(grid.finished_width.unwrap_or(0.0).to_le_bytes());
+            hasher.update(grid.finished_height.unwrap_or(0.0).to_le_bytes());
+            hasher.update([match grid.unit {
+                PhysicalUnit::Inch => 1,
+                PhysicalUnit::Millimeter => 2,
+            }]);
+        }
+    }
+    hasher.update(thread_brand.label().as_bytes());
+    hasher.update([0]);
+    match adjustments {
+        None => hasher.update([0]),
+        Some(adjustments) => {
+            hasher.update([
+                1,
+                adjustments.auto_levels as u8,
+                adjustments.auto_white_balance as u8,
+            ]);
+            for value in [
+                adjustments.brightness,
+                adjustments.contrast,
+                adjustments.saturation,
+                adjustments.gamma,
+                adjustments.temperature,
+                adjustments.tint,
+                adjustments.sharpen,
+            ] {
+                hasher.update(value.to_le_bytes());
+            }
+        }
+    }
     format!("{:x}", hasher.finalize())
 }
 
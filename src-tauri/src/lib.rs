@@ -1,12 +1,30 @@
+mod adjust;
+mod backstitch;
+mod color_metric;
+mod confetti;
+mod dither;
 mod embroidery;
+mod fabric;
+mod floss;
+mod fractional;
+mod grid;
 mod image_processor;
+mod palette_edit;
 mod pdf_export;
+mod pixel_art;
 mod project_hub;
+mod quantize;
 mod regions;
+mod saliency;
 mod selection;
 mod stage4;
+mod symbols;
+mod threads;
 
-use embroidery::{process_pattern, process_pattern_from_path, PatternResult, ProcessingConfig};
+use embroidery::{
+    process_pattern, process_pattern_from_path, LegendEntry, PatternResult, ProcessingConfig,
+};
+use floss::{FlossConfig, FlossEstimate};
 use pdf_export::PdfExportPayload;
 use project_hub::commands::{
     get_all_projects, init_project_hub, load_project, save_project, ProjectStoreLock,
@@ -20,6 +38,10 @@ use std::sync::Mutex;
 use std::time::{SystemTime, UNIX_EPOCH};
 use tauri::menu::{MenuBuilder, MenuId, MenuItemBuilder, SubmenuBuilder};
 use tauri::Manager;
+use threads::{
+    LibraryFormat, LibraryImportReport, PatternConversion, ThreadBrand, ThreadCatalog, ThreadColor,
+    ThreadSubstitution,
+};
 
 #[derive(Deserialize)]
 struct DialogFilter {
@@ -146,10 +168,11 @@ fn export_pattern_pdf(payload: PdfExportPayload) -> Result<Vec<u8>, String> {
 /// # Arguments
 /// * `image_bytes` - Raw image bytes (PNG, JPEG, etc.)
 /// * `config` - Processing configuration (color count, DMC mapping, etc.)
-/// * `mask` - Optional mask bytes (255 = include, 0 = exclude/fabric)
+/// * `mask` - Optional mask bytes (0 = exclude/fabric; 1-255 = include, weighting palette
+///   training toward heavier pixels)
 ///
 /// # Returns
-/// PatternResult containing stitches, palette, legend, and processing time
+/// PatternResult containing the label grid, palette, legend, and processing time
 #[tauri::command]
 fn process_embroidery_pattern(
     image_bytes: Vec<u8>,
@@ -204,6 +227,155 @@ fn process_embroidery_pattern_from_file(
     Ok(result)
 }
 
+/// Give every legend entry of an edited pattern a unique chart symbol.
+///
+/// Symbols that are still unique are kept, so the chart only changes where it has to.
+#[tauri::command]
+fn reassign_pattern_symbols(mut pattern: PatternResult) -> PatternResult {
+    pattern.reassign_symbols();
+    pattern
+}
+
+/// Stitch every cell of `from_code` with the thread of `into_code`.
+///
+/// Legend, color mappings, symbols and totals are re-derived; other colors keep their
+/// threads and symbols.
+#[tauri::command]
+fn merge_pattern_colors(
+    mut pattern: PatternResult,
+    from_code: String,
+    into_code: String,
+) -> Result<PatternResult, String> {
+    palette_edit::merge_colors(&mut pattern, &from_code, &into_code)?;
+    Ok(pattern)
+}
+
+/// Swap the thread of `dmc_code` for `thread_code` from the pattern's brand (or the
+/// imported `library_id`).
+#[tauri::command]
+fn replace_pattern_thread(
+    mut pattern: PatternResult,
+    dmc_code: String,
+    thread_code: String,
+    library_id: Option<String>,
+) -> Result<PatternResult, String> {
+    let catalog = ThreadCatalog::resolve(pattern.brand, library_id.as_deref())?;
+    let thread = catalog
+        .find_code(&thread_code)
+        .ok_or_else(|| format!("Thread {} is not in the catalog", thread_code))?
+        .clone();
+    palette_edit::replace_thread(&mut pattern, &dmc_code, &thread, &catalog)?;
+    Ok(pattern)
+}
+
+/// Split `dmc_code` into `parts` colors by re-clustering only its cells.
+///
+/// `image_bytes` and `config` must be the ones the pattern was generated from, so the
+/// cells line up with the image; only the stitch grid, adjustments, fabric, thread and
+/// stash settings of `config` are used.
+#[tauri::command]
+fn split_pattern_color(
+    mut pattern: PatternResult,
+    dmc_code: String,
+    parts: u32,
+    image_bytes: Vec<u8>,
+    config: ProcessingConfig,
+) -> Result<PatternResult, String> {
+    let (width, height, pixels) = embroidery::stitch_grid_pixels(&image_bytes, &config)?;
+    if (width, height) != (pattern.width, pattern.height) {
+        return Err("The image does not match the pattern's stitch grid".to_string());
+    }
+    let catalog = if config.use_dmc_palette {
+        Some(embroidery::resolve_catalogs(&config)?.1)
+    } else {
+        None
+    };
+    palette_edit::split_color(
+        &mut pattern,
+        &dmc_code,
+        parts,
+        &pixels,
+        catalog.as_deref(),
+        &config,
+    )?;
+    Ok(pattern)
+}
+
+/// Estimate floss per thread, skeins to buy and stitching time for a pattern.
+///
+/// The fabric count defaults to the pattern's stitch grid when it was generated with one.
+#[tauri::command]
+fn estimate_pattern_floss(
+    pattern: PatternResult,
+    config: FlossConfig,
+) -> Result<FlossEstimate, String> {
+    floss::estimate_floss(&pattern, &config)
+}
+
+/// Re-map a pattern onto another thread brand.
+///
+/// Every stitch, legend entry and color mapping is replaced by its equivalent in
+/// `target_brand` (or the imported `target_library_id`). Anchor, Madeira and Cosmo are
+/// reached through the DMC cross-reference chart and their substitutions carry no Delta-E;
+/// other brands take the nearest CIEDE2000 color and report the Delta-E of each swap.
+#[tauri::command]
+fn convert_pattern_threads(
+    pattern: PatternResult,
+    target_brand: ThreadBrand,
+    target_library_id: Option<String>,
+) -> Result<PatternConversion, String> {
+    let catalog = ThreadCatalog::resolve(target_brand, target_library_id.as_deref())?;
+    log::info!(
+        "Converting pattern from {} to {} ({} legend entries)",
+        pattern.brand.label(),
+        target_library_id.as_deref().unwrap_or(target_brand.label()),
+        pattern.legend.len()
+    );
+    Ok(threads::convert_pattern(&pattern, &catalog))
+}
+
+/// List the nearest `target_brand` (or `target_library_id`) equivalent of every legend entry.
+#[tauri::command]
+fn convert_legend_threads(
+    legend: Vec<LegendEntry>,
+    target_brand: ThreadBrand,
+    target_library_id: Option<String>,
+) -> Result<Vec<ThreadSubstitution>, String> {
+    let catalog = ThreadCatalog::resolve(target_brand, target_library_id.as_deref())?;
+    Ok(threads::convert_legend(&legend, &catalog))
+}
+
+/// Import a user thread library from CSV or JSON text.
+///
+/// Rows need a code and hex, with optional name, brand and series. The library is cached
+/// under `library_id` for `ProcessingConfig.custom_library_id` only when the returned
+/// report has no issues.
+#[tauri::command]
+fn import_thread_library(
+    library_id: String,
+    contents: String,
+    format: LibraryFormat,
+) -> Result<LibraryImportReport, String> {
+    let report = threads::import_library(&library_id, &contents, format)?;
+    log::info!(
+        "Imported thread library '{}': {} threads, {} issues",
+        report.library_id,
+        report.thread_count,
+        report.issues.len()
+    );
+    Ok(report)
+}
+
+/// List every thread in a bundled brand or an imported library.
+#[tauri::command]
+fn list_thread_catalog(
+    brand: ThreadBrand,
+    library_id: Option<String>,
+) -> Result<Vec<ThreadColor>, String> {
+    let catalog = ThreadCatalog::resolve(brand, library_id.as_deref())?;
+    Ok(catalog.threads().to_vec())
+}
+
 #[tauri::command]
 fn init_selection_workspace(
     image_rgba: Vec<u8>,
@@ -246,6 +418,9 @@ async fn process_image(
     color_count: u8,
     detail_level: f32,
     hoop_config: image_processor::HoopConfig,
+    adjustments: Option<adjust::ImageAdjustments>,
+    thread_brand: Option<threads::ThreadBrand>,
+    grid: Option<grid::StitchGridConfig>,
 ) -> Result<image_processor::RegionData, String> {
     tauri::async_runtime::spawn_blocking(move || {
         image_processor::process_image_pipeline(
@@ -254,6 +429,9 @@ async fn process_image(
             color_count,
             detail_level,
             hoop_config,
+            adjustments,
+            thread_brand,
+            grid,
         )
     })
     .await
@@ -278,6 +456,15 @@ pub fn run() {
             export_pattern_pdf,
             process_embroidery_pattern,
             process_embroidery_pattern_from_file,
+            reassign_pattern_symbols,
+            merge_pattern_colors,
+            replace_pattern_thread,
+            split_pattern_color,
+            estimate_pattern_floss,
+            convert_pattern_threads,
+            convert_legend_threads,
+            import_thread_library,
+            list_thread_catalog,
             init_selection_workspace,
             magic_wand_click_command,
             refine_selection,
@@ -0,0 +1,599 @@
+//! Palette edits on a finished pattern.
+//!
+//! Merging two colors, swapping a color's thread or splitting a color in two only touch
+//! the labels involved; every other cell, thread and chart symbol stays as it was. The
+//! legend, color mappings, symbols, confetti report and totals are then re-derived from
+//! the label grid, without re-running quantization.
+//!
+//! Labels of the grid line up with `palette`, `dmc_palette` and `color_mappings`, one
+//! quantized cluster each, so those are edited alongside when their lengths match.
+
+use crate::confetti::{analyze_confetti, ConfettiReport};
+use crate::embroidery::{
+    hex_to_rgb, kmeans_quantize, lab_to_rgb, rgb_to_hex, rgb_to_lab, ColorMapping, DmcMetadata,
+    GridColor, LabelUsage, LegendEntry, PatternResult, ProcessingConfig, MAX_COLOR_COUNT,
+};
+use crate::symbols::assign_symbols;
+use crate::threads::{match_inventory, InventoryMatch, ThreadCatalog, ThreadColor};
+use palette::{white_point::D65, Lab};
+use std::collections::HashMap;
+
+/// Most colors one split may produce
+pub const MAX_SPLIT_PARTS: u32 = 8;
+
+const SPLIT_ITERATIONS: usize = 20;
+
+/// Stitch every cell of `from_code` with the thread of `into_code`.
+pub fn merge_colors(
+    pattern: &mut PatternResult,
+    from_code: &str,
+    into_code: &str,
+) -> Result<(), String> {
+    if from_code == into_code {
+        return Err("Choose two different colors to merge".to_string());
+    }
+    pattern.grid = pattern.label_grid().into_owned();
+    let from = labels_of(pattern, from_code)?;
+    let into = labels_of(pattern, into_code)?[0];
+    let target = pattern.grid.colors[into].clone();
+    let target_mapping = pattern
+        .color_mappings
+        .get(into)
+        .map(|mapping| mapping.dmc.clone());
+    let aligned = is_aligned(pattern);
+
+    for label in from {
+        pattern.grid.colors[label] = target.clone();
+        if aligned {
+            pattern.dmc_palette[label] = target.hex.clone();
+            let mapping = &mut pattern.color_mappings[label];
+            mapping.mapped_hex = target.hex.clone();
+            if let Some(dmc) = &target_mapping {
+                mapping.dmc = dmc.clone();
+            }
+        }
+    }
+    recolor_backstitch(pattern, from_code, &target);
+    rederive(pattern, None);
+    Ok(())
+}
+
+/// Stitch every cell of `dmc_code` with `thread` instead.
+pub fn replace_thread(
+    pattern: &mut PatternResult,
+    dmc_code: &str,
+    thread: &ThreadColor,
+    catalog: &ThreadCatalog,
+) -> Result<(), String> {
+    pattern.grid = pattern.label_grid().into_owned();
+    let labels = labels_of(pattern, dmc_code)?;
+    // Joining a thread already in the pattern keeps that thread's symbol.
+    let marker = pattern
+        .grid
+        .colors
+        .iter()
+        .find(|color| color.dmc_code == thread.code)
+        .map(|color| color.marker.clone());
+    let aligned = is_aligned(pattern);
+
+    for label in labels {
+        let color = &mut pattern.grid.colors[label];
+        color.dmc_code = thread.code.clone();
+        color.hex = thread.hex.clone();
+        color.blend = thread.blend.clone();
+        if let Some(marker) = &marker {
+            color.marker = marker.clone();
+        }
+        if aligned {
+            pattern.dmc_palette[label] = thread.hex.clone();
+            let mapping = &mut pattern.color_mappings[label];
+            mapping.mapped_hex = thread.hex.clone();
+            mapping.dmc = dmc_metadata(thread);
+        }
+    }
+    let recolored = pattern.grid.colors[labels_of(pattern, &thread.code)?[0]].clone();
+    recolor_backstitch(pattern, dmc_code, &recolored);
+    rederive(pattern, Some(catalog));
+    Ok(())
+}
+
+/// Re-cluster the cells of `dmc_code` into `parts` colors.
+///
+/// `pixels` holds the LAB color of every cell, as
+/// [`stitch_grid_pixels`](crate::embroidery::stitch_grid_pixels) returns it. The cluster
+/// closest to the current thread keeps it and its symbol; the others are matched against
+/// `catalog` within the stash and purchase budget of `config`, or kept as raw quantized
+/// colors when it is `None`. Threads the pattern already suggests buying count as owned.
+pub fn split_color(
+    pattern: &mut PatternResult,
+    dmc_code: &str,
+    parts: u32,
+    pixels: &[Lab<D65, f32>],
+    catalog: Option<&ThreadCatalog>,
+    config: &ProcessingConfig,
+) -> Result<(), String> {
+    let metric = config.color_metric;
+    if !(2..=MAX_SPLIT_PARTS).contains(&parts) {
+        return Err(format!(
+            "A color can be split into 2 to {} parts",
+            MAX_SPLIT_PARTS
+        ));
+    }
+    if pixels.len() != (pattern.width * pattern.height) as usize {
+        return Err("The image does not match the pattern's stitch grid".to_string());
+    }
+    pattern.grid = pattern.label_grid().into_owned();
+    let labels = labels_of(pattern, dmc_code)?;
+    let base = labels[0];
+    let cells: Vec<usize> = (0..pattern.grid.labels.len())
+        .filter(|&idx| labels.contains(&(pattern.grid.labels[idx] as usize)))
+        .collect();
+    if cells.len() < parts as usize {
+        return Err(format!(
+            "Color {} has too few stitches to split into {} parts",
+            dmc_code, parts
+        ));
+    }
+    if pattern.grid.colors.len() + parts as usize > MAX_COLOR_COUNT as usize {
+        return Err(format!(
+            "Patterns are limited to {} colors",
+            MAX_COLOR_COUNT
+        ));
+    }
+
+    let cell_pixels: Vec<Lab<D65, f32>> = cells.iter().map(|&idx| pixels[idx]).collect();
+    let (centers, assignments) =
+        kmeans_quantize(&cell_pixels, parts as usize, SPLIT_ITERATIONS, &[], metric);
+    let current = rgb_to_lab(hex_to_rgb(&pattern.grid.colors[base].hex));
+    let kept = (0..centers.len())
+        .min_by(|&a, &b| {
+            metric
+                .distance(centers[a], current)
+                .total_cmp(&metric.distance(centers[b], current))
+        })
+        .unwrap_or(0);
+
+    let new_clusters: Vec<usize> = (0..centers.len()).filter(|&c| c != kept).collect();
+    let mut threads = match catalog {
+        Some(catalog) => {
+            let owned: Vec<String> = if config.owned_threads.is_empty() {
+                Vec::new()
+            } else {
+                config
+                    .owned_threads
+                    .iter()
+                    .chain(&pattern.suggested_purchases)
+                    .cloned()
+                    .collect()
+            };
+            let mut weights = vec![0u64; centers.len()];
+            for &cluster in &assignments {
+                weights[cluster as usize] += 1;
+            }
+            let InventoryMatch { threads, purchases } = match_inventory(
+                catalog,
+                &new_clusters.iter().map(|&c| centers[c]).collect::<Vec<_>>(),
+                &new_clusters.iter().map(|&c| weights[c]).collect::<Vec<_>>(),
+                &owned,
+                config
+                    .max_purchases
+                    .map(|max| max.saturating_sub(pattern.suggested_purchases.len() as u32)),
+                metric,
+            )?;
+            pattern.suggested_purchases.extend(purchases);
+            threads
+        }
+        None => Vec::new(),
+    }
+    .into_iter();
+
+    // One label per cluster: the kept cluster reuses `base`, the others are appended.
+    let aligned = is_aligned(pattern);
+    let mut cluster_labels = vec![base; centers.len()];
+    for (cluster, center) in centers.iter().enumerate() {
+        let quantized = rgb_to_hex(lab_to_rgb(*center));
+        if cluster == kept {
+            if aligned {
+                pattern.palette[base] = quantized.clone();
+                pattern.color_mappings[base].original_hex = quantized;
+            }
+            continue;
+        }
+        let color = match threads.next() {
+            Some(thread) => {
+                if aligned {
+                    pattern.color_mappings.push(ColorMapping {
+                        original_hex: quantized.clone(),
+                        mapped_hex: thread.hex.clone(),
+                        dmc: dmc_metadata(&thread),
+                    });
+                }
+                GridColor {
+                    dmc_code: thread.code,
+                    marker: String::new(),
+                    hex: thread.hex,
+                    blend: thread.blend,
+                }
+            }
+            None => {
+                let code = format!("RAW-{}", pattern.grid.colors.len() + 1);
+                if aligned {
+                    pattern.color_mappings.push(ColorMapping {
+                        original_hex: quantized.clone(),
+                        mapped_hex: quantized.clone(),
+                        dmc: DmcMetadata {
+                            code: code.clone(),
+                            name: "Quantized Color".to_string(),
+                            hex: quantized.clone(),
+                        },
+                    });
+                }
+                GridColor {
+                    dmc_code: code,
+                    marker: String::new(),
+                    hex: quantized.clone(),
+                    blend: None,
+                }
+            }
+        };
+        if aligned {
+            pattern.palette.push(quantized);
+            pattern.dmc_palette.push(color.hex.clone());
+        }
+        // A thread already in the pattern keeps its symbol.
+        let marker = pattern
+            .grid
+            .colors
+            .iter()
+            .find(|existing| existing.dmc_code == color.dmc_code)
+            .map(|existing| existing.marker.clone());
+        pattern.grid.colors.push(GridColor {
+            marker: marker.unwrap_or_default(),
+            ..color
+        });
+        cluster_labels[cluster] = pattern.grid.colors.len() - 1;
+    }
+    if cluster_labels
+        .iter()
+        .all(|&label| pattern.grid.colors[label].dmc_code == dmc_code)
+    {
+        return Err(format!(
+            "Every part of {} matches the same thread; nothing to split",
+            dmc_code
+        ));
+    }
+
+    for (&idx, &cluster) in cells.iter().zip(&assignments) {
+        pattern.grid.labels[idx] = cluster_labels[cluster as usize] as u16;
+    }
+    rederive(pattern, catalog);
+    Ok(())
+}
+
+/// Labels stitched with `code`, in label order.
+fn labels_of(pattern: &PatternResult, code: &str) -> Result<Vec<usize>, String> {
+    let labels: Vec<usize> = pattern
+        .grid
+        .colors
+        .iter()
+        .enumerate()
+        .filter(|(_, color)| color.dmc_code == code)
+        .map(|(label, _)| label)
+        .collect();
+    if labels.is_empty() {
+        return Err(format!("Color {} is not in the pattern", code));
+    }
+    Ok(labels)
+}
+
+/// Whether `palette`, `dmc_palette` and `color_mappings` hold one entry per label.
+fn is_aligned(pattern: &PatternResult) -> bool {
+    let labels = pattern.grid.colors.len();
+    pattern.palette.len() == labels
+        && pattern.dmc_palette.len() == labels
+        && pattern.color_mappings.len() == labels
+}
+
+fn dmc_metadata(thread: &ThreadColor) -> DmcMetadata {
+    DmcMetadata {
+        code: thread.code.clone(),
+        name: thread.name.clone(),
+        hex: thread.hex.clone(),
+    }
+}
+
+fn recolor_backstitch(pattern: &mut PatternResult, from_code: &str, color: &GridColor) {
+    for line in &mut pattern.backstitch {
+        if line.dmc_code == from_code {
+            line.dmc_code = color.dmc_code.clone();
+            line.hex = color.hex.clone();
+        }
+    }
+}
+
+/// Rebuild symbols, legend, confetti, totals, the thread count and any expanded stitches
+/// from the grid.
+///
+/// Legend names come from the previous legend, then from `catalog` for new threads. Previous
+/// entries without stitches are locked threads and stay while their color is in the grid.
+fn rederive(pattern: &mut PatternResult, catalog: Option<&ThreadCatalog>) {
+    let (width, height) = (pattern.width, pattern.height);
+    assign_symbols(&mut pattern.grid, width);
+    pattern.confetti = ConfettiReport {
+        reduced: pattern.confetti.reduced,
+        ..analyze_confetti(&pattern.grid, width, height)
+    };
+    pattern.total_stitches = pattern.grid.stitch_count();
+
+    let pinned = |code: &str| {
+        pattern
+            .legend
+            .iter()
+            .any(|entry| entry.stitch_count == 0 && entry.dmc_code == code)
+    };
+    let mut usage_by_code: Vec<(&GridColor, LabelUsage)> = Vec::new();
+    for (color, usage) in pattern.grid.colors.iter().zip(pattern.grid.usage()) {
+        if usage.stitches == 0 && !pinned(&color.dmc_code) {
+            continue;
+        }
+        match usage_by_code
+            .iter_mut()
+            .find(|(seen, _)| seen.dmc_code == color.dmc_code)
+        {
+            Some((_, total)) => {
+                total.stitches += usage.stitches;
+                total.partial += usage.partial;
+                total.cells += usage.cells;
+            }
+            None => usage_by_code.push((color, usage)),
+        }
+    }
+    let total_cells: f32 = usage_by_code.iter().map(|(_, usage)| usage.cells).sum();
+
+    let previous: HashMap<&str, &LegendEntry> = pattern
+        .legend
+        .iter()
+        .map(|entry| (entry.dmc_code.as_str(), entry))
+        .collect();
+    let mut legend: Vec<LegendEntry> = usage_by_code
+        .into_iter()
+        .map(|(color, usage)| {
+            let thread = catalog.and_then(|catalog| catalog.find_code(&color.dmc_code));
+            let (name, brand) = match (previous.get(color.dmc_code.as_str()), thread) {
+                (Some(entry), _) => (entry.name.clone(), entry.brand),
+                (None, Some(thread)) => (thread.name.clone(), Some(thread.brand)),
+                (None, None) => ("Quantized Color".to_string(), None),
+            };
+            LegendEntry {
+                dmc_code: color.dmc_code.clone(),
+                brand,
+                name,
+                hex: color.hex.clone(),
+                stitch_count: usage.stitches,
+                coverage: usage.cells / total_cells.max(1.0),
+                partial_count: usage.partial,
+                marker: color.marker.clone(),
+                blend: color.blend.clone(),
+            }
+        })
+        .collect();
+    legend.sort_by(|a, b| {
+        b.stitch_count
+            .cmp(&a.stitch_count)
+            .then_with(|| a.dmc_code.cmp(&b.dmc_code))
+    });
+    pattern.legend = legend;
+
+    let achieved = pattern.legend.len() as u32;
+    let report = &mut pattern.thread_count;
+    if achieved < report.achieved {
+        report.shortfall = Some("Palette edits merged threads".to_string());
+    }
+    if achieved >= report.requested {
+        report.shortfall = None;
+    }
+    report.achieved = achieved;
+
+    if !pattern.stitches.is_empty() {
+        pattern.stitches = pattern.grid.to_stitches(width, &pattern.fabric_hex);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backstitch::BackstitchLine;
+    use crate::embroidery::{LabelGrid, ThreadCountReport};
+    use crate::regions::GridPoint;
+    use crate::threads::ThreadBrand;
+
+    /// 4x2 pattern: a red left half and a black right half, matched to DMC threads.
+    fn pattern() -> PatternResult {
+        let catalog = ThreadCatalog::for_brand(ThreadBrand::Dmc).expect("DMC is bundled");
+        let threads = [
+            catalog.find_code("321").expect("321").clone(),
+            catalog.find_code("310").expect("310").clone(),
+        ];
+        let mut result = PatternResult {
+            width: 4,
+            height: 2,
+            stitches: Vec::new(),
+            grid: LabelGrid {
+                labels: vec![0, 0, 1, 1, 0, 0, 1, 1],
+                colors: threads
+                    .iter()
+                    .zip(["S", "O"])
+                    .map(|(thread, marker)| GridColor {
+                        dmc_code: thread.code.clone(),
+                        marker: marker.to_string(),
+                        hex: thread.hex.clone(),
+                        blend: None,
+                    })
+                    .collect(),
+                partials: Vec::new(),
+            },
+            palette: vec!["#C8203A".to_string(), "#050505".to_string()],
+            dmc_palette: threads.iter().map(|t| t.hex.clone()).collect(),
+            brand: ThreadBrand::Dmc,
+            suggested_purchases: Vec::new(),
+            physical_size: None,
+            backstitch: vec![BackstitchLine {
+                dmc_code: "310".to_string(),
+                hex: threads[1].hex.clone(),
+                points: vec![GridPoint { x: 0, y: 0 }, GridPoint { x: 4, y: 0 }],
+            }],
+            confetti: ConfettiReport::default(),
+            thread_count: ThreadCountReport::default(),
+            pixel_art: None,
+            fabric_hex: "#FFFFFF".to_string(),
+            legend: Vec::new(),
+            color_mappings: threads
+                .iter()
+                .map(|thread| ColorMapping {
+                    original_hex: thread.hex.clone(),
+                    mapped_hex: thread.hex.clone(),
+                    dmc: dmc_metadata(thread),
+                })
+                .collect(),
+            total_stitches: 0,
+            processing_time_ms: 0,
+        };
+        rederive(&mut result, Some(&catalog));
+        result
+    }
+
+    #[test]
+    fn merge_and_replace_rederive_the_legend() {
+        let mut merged = pattern();
+        merge_colors(&mut merged, "321", "310").expect("merge");
+        assert_eq!(merged.legend.len(), 1);
+        assert_eq!(merged.legend[0].dmc_code, "310");
+        assert_eq!(merged.legend[0].stitch_count, 8);
+        assert_eq!(merged.legend[0].marker, "O");
+        assert_eq!(merged.color_mappings[0].dmc.code, "310");
+        assert!(merge_colors(&mut merged, "321", "310").is_err());
+
+        let catalog = ThreadCatalog::for_brand(ThreadBrand::Dmc).expect("DMC is bundled");
+        let navy = catalog.find_code("823").expect("823").clone();
+        let mut replaced = pattern();
+        replace_thread(&mut replaced, "310", &navy, &catalog).expect("replace");
+        let entry = replaced
+            .legend
+            .iter()
+            .find(|entry| entry.dmc_code == "823")
+            .expect("legend entry");
+        assert_eq!(entry.name, navy.name);
+        assert_eq!(entry.stitch_count, 4);
+        // The untouched color keeps its symbol, and the backstitch follows its thread.
+        assert_eq!(replaced.grid.colors[0].marker, "S");
+        assert_eq!(replaced.backstitch[0].dmc_code, "823");
+        assert_eq!(replaced.dmc_palette[1], navy.hex);
+    }
+
+    #[test]
+    fn edits_keep_locked_threads_and_recount_threads() {
+        let catalog = ThreadCatalog::for_brand(ThreadBrand::Dmc).expect("DMC is bundled");
+        let turquoise = catalog.find_code("597").expect("597");
+        let mut edited = pattern();
+        edited.grid.colors.push(GridColor {
+            dmc_code: turquoise.code.clone(),
+            marker: String::new(),
+            hex: turquoise.hex.clone(),
+            blend: None,
+        });
+        edited.legend.push(LegendEntry {
+            dmc_code: turquoise.code.clone(),
+            brand: Some(ThreadBrand::Dmc),
+            name: turquoise.name.clone(),
+            hex: turquoise.hex.clone(),
+            stitch_count: 0,
+            coverage: 0.0,
+            partial_count: 0,
+            marker: String::new(),
+            blend: None,
+        });
+        edited.thread_count = ThreadCountReport {
+            requested: 3,
+            achieved: 3,
+            refits: 0,
+            shortfall: None,
+        };
+
+        merge_colors(&mut edited, "321", "310").expect("merge");
+        assert_eq!(edited.legend.len(), 2);
+        let locked = edited
+            .legend
+            .iter()
+            .find(|entry| entry.dmc_code == "597")
+            .expect("locked thread stays listed");
+        assert_eq!(locked.stitch_count, 0);
+        assert!(!locked.marker.is_empty());
+        assert_eq!(edited.thread_count.achieved, 2);
+        assert!(edited.thread_count.shortfall.is_some());
+    }
+
+    #[test]
+    fn split_reclusters_only_the_chosen_color() {
+        let mut split = pattern();
+        // The red cells are light red on top and dark red below.
+        let mut pixels = vec![rgb_to_lab([0, 0, 0]); 8];
+        pixels[0] = rgb_to_lab([250, 130, 140]);
+        pixels[1] = rgb_to_lab([250, 130, 140]);
+        pixels[4] = rgb_to_lab([120, 10, 20]);
+        pixels[5] = rgb_to_lab([120, 10, 20]);
+        let catalog = ThreadCatalog::for_brand(ThreadBrand::Dmc).expect("DMC is bundled");
+
+        split_color(
+            &mut split,
+            "321",
+            2,
+            &pixels,
+            Some(&catalog),
+            &ProcessingConfig::default(),
+        )
+        .expect("split");
+        assert_eq!(split.grid.colors.len(), 3);
+        assert_eq!(split.legend.len(), 3);
+        assert_eq!(split.grid.labels[0], split.grid.labels[1]);
+        assert_ne!(split.grid.labels[0], split.grid.labels[4]);
+        assert_eq!(&split.grid.labels[2..4], &[1, 1]);
+        assert_eq!(split.grid.colors[1].marker, "O");
+        assert_eq!(split.color_mappings.len(), 3);
+        assert_eq!(split.total_stitches, 8);
+
+        assert!(split_color(
+            &mut pattern(),
+            "321",
+            2,
+            &pixels[..4],
+            Some(&catalog),
+            &ProcessingConfig::default()
+        )
+        .is_err());
+
+        // With a stash and no purchase budget the new part stays within it
+        let stash = ProcessingConfig {
+            owned_threads: ["321", "310", "961"].map(String::from).to_vec(),
+            max_purchases: Some(0),
+            ..ProcessingConfig::default()
+        };
+        let mut owned = pattern();
+        split_color(&mut owned, "321", 2, &pixels, Some(&catalog), &stash).expect("split");
+        assert_eq!(owned.grid.colors.len(), 3);
+        assert!(owned
+            .grid
+            .colors
+            .iter()
+            .all(|color| stash.owned_threads.contains(&color.dmc_code)));
+        assert!(owned.suggested_purchases.is_empty());
+
+        // A purchase budget lets it reach the closest catalog thread instead
+        let budget = ProcessingConfig {
+            max_purchases: Some(1),
+            ..stash
+        };
+        let mut bought = pattern();
+        split_color(&mut bought, "321", 2, &pixels, Some(&catalog), &budget).expect("split");
+        assert_eq!(bought.suggested_purchases, vec!["962".to_string()]);
+    }
+}
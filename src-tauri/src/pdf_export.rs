@@ -1,4 +1,7 @@
+use crate::floss::FlossEstimate;
+use crate::fractional::{StitchCorner, StitchKind};
 use crate::regions::{self, GridPoint, PatternRegion};
+use crate::symbols::marker_glyph;
 use serde::Deserialize;
 
 const A4_WIDTH_PT: f32 = 595.0;
@@ -40,6 +43,12 @@ pub struct PdfExportPayload {
     pub height: u32,
     pub stitches: Vec<PdfExportStitch>,
     pub legend: Vec<PdfExportLegendEntry>,
+    /// Backstitch lines drawn over the blueprint grid
+    #[serde(default)]
+    pub backstitch: Vec<PdfExportBackstitchLine>,
+    /// Skein counts listed in the thread manifest
+    #[serde(default)]
+    pub floss: Option<FlossEstimate>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -49,6 +58,11 @@ pub struct PdfExportStitch {
     pub dmc_code: String,
     pub marker: String,
     pub hex: String,
+    #[serde(default)]
+    pub kind: StitchKind,
+    /// Corner a partial stitch fills toward
+    #[serde(default)]
+    pub corner: Option<StitchCorner>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -58,6 +72,56 @@ pub struct PdfExportLegendEntry {
     pub hex: String,
     pub stitch_count: u32,
     pub coverage: f32,
+    /// Of `stitch_count`, how many are fractional stitches
+    #[serde(default)]
+    pub partial_count: u32,
+    /// Component codes for a blended entry, stitched one strand each
+    #[serde(default)]
+    pub blend: Option<[String; 2]>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PdfExportBackstitchLine {
+    pub dmc_code: String,
+    pub hex: String,
+    /// Grid corners, (0, 0) being the top-left corner of the chart
+    pub points: Vec<GridPoint>,
+}
+
+impl PdfExportBackstitchLine {
+    /// Number of single backstitches along the line.
+    fn stitch_length(&self) -> i32 {
+        self.points
+            .windows(2)
+            .map(|pair| (pair[1].x - pair[0].x).abs() + (pair[1].y - pair[0].y).abs())
+            .sum()
+    }
+}
+
+/// Backstitch threads with their total length in stitches, in first-use order
+fn backstitch_threads(payload: &PdfExportPayload) -> Vec<(&str, &str, i32)> {
+    let mut threads: Vec<(&str, &str, i32)> = Vec::new();
+    for line in &payload.backstitch {
+        match threads
+            .iter_mut()
+            .find(|(code, _, _)| *code == line.dmc_code)
+        {
+            Some(thread) => thread.2 += line.stitch_length(),
+            None => threads.push((&line.dmc_code, &line.hex, line.stitch_length())),
+        }
+    }
+    threads
+}
+
+/// Skeins the floss estimate lists for `code`
+fn skeins_for(payload: &PdfExportPayload, code: &str) -> Option<u32> {
+    payload
+        .floss
+        .as_ref()?
+        .threads
+        .iter()
+        .find(|thread| thread.dmc_code == code)
+        .map(|thread| thread.skeins)
 }
 
 pub fn export_pattern_pdf(payload: &PdfExportPayload) -> Result<Vec<u8>, String> {
@@ -317,30 +381,71 @@ fn build_stitch_grid_page(payload: &PdfExportPayload, layout: &GridLayout) -> St
             continue;
         }
         let (x, y) = layout.cell_bottom_left(stitch.x, stitch.y, payload.height);
+        let partial = stitch.corner.filter(|_| stitch.kind != StitchKind::Full);
 
         if stitch.dmc_code != "Fabric" {
             let (r, g, b) = parse_hex(&stitch.hex);
             let tint_r = 1.0 - (1.0 - r) * 0.16;
             let tint_g = 1.0 - (1.0 - g) * 0.16;
             let tint_b = 1.0 - (1.0 - b) * 0.16;
-            stream.push_str(&format!(
-                "{:.3} {:.3} {:.3} rg {:.3} {:.3} {:.3} {:.3} re f\n",
-                tint_r, tint_g, tint_b, x, y, layout.cell, layout.cell
-            ));
+            match partial {
+                Some(corner) => {
+                    let [a, b, c] = partial_triangle(corner, x, y, layout.cell);
+                    stream.push_str(&format!(
+                        "{:.3} {:.3} {:.3} rg {:.3} {:.3} m {:.3} {:.3} l {:.3} {:.3} l h f\n",
+                        tint_r, tint_g, tint_b, a.0, a.1, b.0, b.1, c.0, c.1
+                    ));
+                    // The dividing diagonal makes the split readable without color.
+                    stream.push_str(&format!(
+                        "0.35 0.35 0.35 RG 0.3 w {:.3} {:.3} m {:.3} {:.3} l S\n",
+                        b.0, b.1, c.0, c.1
+                    ));
+                }
+                None => stream.push_str(&format!(
+                    "{:.3} {:.3} {:.3} rg {:.3} {:.3} {:.3} {:.3} re f\n",
+                    tint_r, tint_g, tint_b, x, y, layout.cell, layout.cell
+                )),
+            }
         }
 
         if stitch.dmc_code == "Fabric" {
             continue;
         }
-        let marker = stitch
-            .marker
-            .chars()
-            .next()
-            .unwrap_or(' ')
-            .to_ascii_uppercase();
-        stream.push_str(&draw_vector_symbol(marker, x, y, layout.cell));
+        let marker = stitch.marker.to_ascii_uppercase();
+        match partial {
+            // Partial stitches get a half-size symbol in the quadrant at their corner.
+            Some(corner) => {
+                let half = layout.cell * 0.5;
+                let (qx, qy) = match corner {
+                    StitchCorner::TopLeft => (x, y + half),
+                    StitchCorner::TopRight => (x + half, y + half),
+                    StitchCorner::BottomLeft => (x, y),
+                    StitchCorner::BottomRight => (x + half, y),
+                };
+                stream.push_str(&draw_vector_symbol(&marker, qx, qy, half));
+            }
+            None => stream.push_str(&draw_vector_symbol(&marker, x, y, layout.cell)),
+        }
     }
 
+    // Backstitch sits on top of the symbols, along cell edges.
+    stream.push_str(&format!("1 J 1 j {:.3} w\n", (layout.cell * 0.18).max(0.6)));
+    for line in &payload.backstitch {
+        if line.points.len() < 2 {
+            continue;
+        }
+        let (r, g, b) = parse_hex(&line.hex);
+        stream.push_str(&format!("{:.3} {:.3} {:.3} RG\n", r, g, b));
+        for (idx, point) in line.points.iter().enumerate() {
+            let x = layout.origin_x + point.x as f32 * layout.cell;
+            let y = layout.origin_y + (payload.height as i32 - point.y) as f32 * layout.cell;
+            let op = if idx == 0 { "m" } else { "l" };
+            stream.push_str(&format!("{:.3} {:.3} {}\n", x, y, op));
+        }
+        stream.push_str("S\n");
+    }
+    stream.push_str("0 J 0 j\n");
+
     stream.push_str(&text_cmd(
         40.0,
         28.0,
@@ -351,6 +456,19 @@ fn build_stitch_grid_page(payload: &PdfExportPayload, layout: &GridLayout) -> St
     stream
 }
 
+/// Triangle of the cell at (x, y) toward `corner`: the corner, then its two neighbours.
+fn partial_triangle(corner: StitchCorner, x: f32, y: f32, cell: f32) -> [(f32, f32); 3] {
+    let (cx, ox) = match corner {
+        StitchCorner::TopLeft | StitchCorner::BottomLeft => (x, x + cell),
+        StitchCorner::TopRight | StitchCorner::BottomRight => (x + cell, x),
+    };
+    let (cy, oy) = match corner {
+        StitchCorner::BottomLeft | StitchCorner::BottomRight => (y, y + cell),
+        StitchCorner::TopLeft | StitchCorner::TopRight => (y + cell, y),
+    };
+    [(cx, cy), (ox, cy), (cx, oy)]
+}
+
 fn build_manifest_page(payload: &PdfExportPayload, page_width: f32, page_height: f32) -> String {
     let mut stream = String::new();
 
@@ -362,6 +480,20 @@ fn build_manifest_page(payload: &PdfExportPayload, page_width: f32, page_height:
         10.0,
         "Color swatches, DMC metadata, and stitch counts",
     ));
+    if let Some(floss) = &payload.floss {
+        stream.push_str(&text_cmd(
+            40.0,
+            page_height - 90.0,
+            9.0,
+            &format!(
+                "Floss: {} skeins at {} strands, {:.0} stitches/in | About {:.0} h of stitching",
+                floss.total_skeins,
+                floss.strands,
+                floss.stitches_per_inch,
+                floss.stitching_hours.ceil()
+            ),
+        ));
+    }
 
     let top = page_height - 108.0;
     let bottom = 52.0;
@@ -370,6 +502,8 @@ fn build_manifest_page(payload: &PdfExportPayload, page_width: f32, page_height:
     let gutter = 24.0;
     let col_w = (page_width - 80.0 - gutter) / columns as f32;
     let rows_per_col = ((top - bottom) / row_h).floor().max(1.0) as usize;
+    // Skein counts need a little more room in the stat column.
+    let stat_x = if payload.floss.is_some() { 104.0 } else { 72.0 };
 
     for (idx, entry) in payload.legend.iter().enumerate() {
         let col = idx / rows_per_col;
@@ -394,17 +528,101 @@ fn build_manifest_page(payload: &PdfExportPayload, page_width: f32, page_height:
         stream.push_str("0.2 0.2 0.2 RG 0.4 w\n");
         stream.push_str(&format!("{:.3} {:.3} 10 10 re S\n", x, y - 9.0));
 
-        let code = sanitize_text(&entry.dmc_code);
-        let name = sanitize_text(&entry.name);
-        let stat = format!("{} st | {:.1}%", entry.stitch_count, coverage);
+        let (code, name) = match &entry.blend {
+            Some([a, b]) => (
+                sanitize_text(&format!("{} + {}", a, b)),
+                sanitize_text(&format!("1 strand each: {}", entry.name)),
+            ),
+            None => (sanitize_text(&entry.dmc_code), sanitize_text(&entry.name)),
+        };
+        let code_size = if code.len() > 8 { 7.0 } else { 9.0 };
+        let stat = if entry.partial_count > 0 {
+            format!(
+                "{} st ({} part) | {:.1}%",
+                entry.stitch_count, entry.partial_count, coverage
+            )
+        } else {
+            format!("{} st | {:.1}%", entry.stitch_count, coverage)
+        };
+        let stat = match skeins_for(payload, &entry.dmc_code) {
+            Some(skeins) => format!("{} | {} sk", stat, skeins),
+            None => stat,
+        };
 
         stream.push_str("0 0 0 rg\n");
-        stream.push_str(&text_cmd(x + 16.0, y - 1.0, 9.0, &code));
+        stream.push_str(&text_cmd(x + 16.0, y - 1.0, code_size, &code));
         stream.push_str(&text_cmd(x + 64.0, y - 1.0, 8.0, &name));
-        stream.push_str(&text_cmd(x + col_w - 72.0, y - 1.0, 8.0, &stat));
+        stream.push_str(&text_cmd(x + col_w - stat_x, y - 1.0, 8.0, &stat));
+    }
+
+    let listed = |code: &str| payload.legend.iter().any(|entry| entry.dmc_code == code);
+    let backstitch = backstitch_threads(payload);
+    for (offset, (code, hex, length)) in backstitch.iter().enumerate() {
+        let idx = payload.legend.len() + offset;
+        let col = idx / rows_per_col;
+        if col >= columns {
+            break;
+        }
+        let row = idx % rows_per_col;
+
+        let x = 40.0 + col as f32 * (col_w + gutter);
+        let y = top - row as f32 * row_h;
+        let (r, g, b) = parse_hex(hex);
+
+        // A short stroke instead of a filled swatch marks backstitch rows.
+        stream.push_str(&format!(
+            "{:.3} {:.3} {:.3} RG 1.6 w 1 J {:.3} {:.3} m {:.3} {:.3} l S 0 J\n",
+            r,
+            g,
+            b,
+            x,
+            y - 4.0,
+            x + 10.0,
+            y - 4.0
+        ));
+        stream.push_str("0 0 0 rg\n");
+        stream.push_str(&text_cmd(x + 16.0, y - 1.0, 9.0, &sanitize_text(code)));
+        stream.push_str(&text_cmd(x + 64.0, y - 1.0, 8.0, "Backstitch"));
+        let stat = match skeins_for(payload, code).filter(|_| !listed(code)) {
+            Some(skeins) => format!("{} bs | {} sk", length, skeins),
+            None => format!("{} bs", length),
+        };
+        stream.push_str(&text_cmd(x + col_w - stat_x, y - 1.0, 8.0, &stat));
+    }
+
+    // Blend components that are not stitched on their own still need buying.
+    let components: Vec<(&str, u32)> = payload
+        .floss
+        .iter()
+        .flat_map(|floss| floss.threads.iter())
+        .filter(|thread| {
+            !listed(&thread.dmc_code) && !backstitch.iter().any(|(c, _, _)| *c == thread.dmc_code)
+        })
+        .map(|thread| (thread.dmc_code.as_str(), thread.skeins))
+        .collect();
+    for (offset, (code, skeins)) in components.iter().enumerate() {
+        let idx = payload.legend.len() + backstitch.len() + offset;
+        let col = idx / rows_per_col;
+        if col >= columns {
+            break;
+        }
+        let row = idx % rows_per_col;
+
+        let x = 40.0 + col as f32 * (col_w + gutter);
+        let y = top - row as f32 * row_h;
+        stream.push_str("0 0 0 rg\n");
+        stream.push_str(&text_cmd(x + 16.0, y - 1.0, 9.0, &sanitize_text(code)));
+        stream.push_str(&text_cmd(x + 64.0, y - 1.0, 8.0, "Blend strand"));
+        stream.push_str(&text_cmd(
+            x + col_w - stat_x,
+            y - 1.0,
+            8.0,
+            &format!("{} sk", skeins),
+        ));
     }
 
-    let truncated = payload.legend.len() > rows_per_col * columns;
+    let truncated =
+        payload.legend.len() + backstitch.len() + components.len() > rows_per_col * columns;
     if truncated {
         stream.push_str(&text_cmd(
             40.0,
@@ -428,9 +646,11 @@ fn extract_outline_regions(payload: &PdfExportPayload) -> Result<Vec<PatternRegi
     let regions_payload = regions::RegionExtractionPayload {
         width: payload.width,
         height: payload.height,
+        // Regions follow each cell's main thread; quarter stitches would overwrite it.
         stitches: payload
             .stitches
             .iter()
+            .filter(|s| s.kind != StitchKind::Quarter)
             .map(|s| regions::RegionStitch {
                 x: s.x,
                 y: s.y,
@@ -438,6 +658,7 @@ fn extract_outline_regions(payload: &PdfExportPayload) -> Result<Vec<PatternRegi
                 hex: s.hex.clone(),
             })
             .collect(),
+        grid: None,
         legend: payload
             .legend
             .iter()
@@ -694,133 +915,43 @@ fn number_glyph(ch: char) -> Option<[&'static str; 5]> {
     Some(glyph)
 }
 
-fn draw_vector_symbol(marker: char, x: f32, y: f32, cell: f32) -> String {
-    let glyph = match marker_glyph(marker) {
-        Some(glyph) => glyph,
-        None => return String::new(),
-    };
+/// Draw `marker` centered in a cell; multi-glyph symbols shrink to fit side by side.
+fn draw_vector_symbol(marker: &str, x: f32, y: f32, cell: f32) -> String {
+    let glyphs: Vec<[&'static str; 7]> = marker.chars().filter_map(marker_glyph).collect();
+    if glyphs.is_empty() {
+        return String::new();
+    }
 
-    let scale = (cell * 0.72 / 7.0).max(0.35);
-    let glyph_w = 5.0 * scale;
+    // Each glyph is 5 units wide with a 1-unit gap between glyphs.
+    let units_wide = (glyphs.len() * 6 - 1) as f32;
+    let scale = (cell * 0.72 / 7.0)
+        .min(cell * 0.86 / units_wide)
+        .max(0.35 / glyphs.len() as f32);
+    let glyph_w = units_wide * scale;
     let glyph_h = 7.0 * scale;
     let offset_x = x + (cell - glyph_w) * 0.5;
     let offset_y = y + (cell - glyph_h) * 0.5;
 
     let mut stream = String::from("0 0 0 rg\n");
-    for (row, row_bits) in glyph.iter().enumerate() {
-        for (col, bit) in row_bits.as_bytes().iter().enumerate() {
-            if *bit != b'1' {
-                continue;
+    for (index, glyph) in glyphs.iter().enumerate() {
+        let glyph_x = offset_x + index as f32 * 6.0 * scale;
+        for (row, row_bits) in glyph.iter().enumerate() {
+            for (col, bit) in row_bits.as_bytes().iter().enumerate() {
+                if *bit != b'1' {
+                    continue;
+                }
+                let px = glyph_x + col as f32 * scale;
+                let py = offset_y + (6 - row) as f32 * scale;
+                stream.push_str(&format!(
+                    "{:.3} {:.3} {:.3} {:.3} re f\n",
+                    px, py, scale, scale
+                ));
             }
-            let px = offset_x + col as f32 * scale;
-            let py = offset_y + (6 - row) as f32 * scale;
-            stream.push_str(&format!(
-                "{:.3} {:.3} {:.3} {:.3} re f\n",
-                px, py, scale, scale
-            ));
         }
     }
     stream
 }
 
-fn marker_glyph(marker: char) -> Option<[&'static str; 7]> {
-    let glyph = match marker {
-        '0' => [
-            "01110", "10001", "10011", "10101", "11001", "10001", "01110",
-        ],
-        '1' => [
-            "00100", "01100", "00100", "00100", "00100", "00100", "01110",
-        ],
-        '2' => [
-            "01110", "10001", "00001", "00010", "00100", "01000", "11111",
-        ],
-        '3' => [
-            "11110", "00001", "00001", "01110", "00001", "00001", "11110",
-        ],
-        '4' => [
-            "00010", "00110", "01010", "10010", "11111", "00010", "00010",
-        ],
-        'A' => [
-            "01110", "10001", "10001", "11111", "10001", "10001", "10001",
-        ],
-        'B' => [
-            "11110", "10001", "10001", "11110", "10001", "10001", "11110",
-        ],
-        'C' => [
-            "01111", "10000", "10000", "10000", "10000", "10000", "01111",
-        ],
-        'D' => [
-            "11110", "10001", "10001", "10001", "10001", "10001", "11110",
-        ],
-        'E' => [
-            "11111", "10000", "10000", "11110", "10000", "10000", "11111",
-        ],
-        'H' => [
-            "10001", "10001", "10001", "11111", "10001", "10001", "10001",
-        ],
-        'K' => [
-            "10001", "10010", "10100", "11000", "10100", "10010", "10001",
-        ],
-        'M' => [
-            "10001", "11011", "10101", "10101", "10001", "10001", "10001",
-        ],
-        'N' => [
-            "10001", "11001", "10101", "10011", "10001", "10001", "10001",
-        ],
-        'O' => [
-            "01110", "10001", "10001", "10001", "10001", "10001", "01110",
-        ],
-        'P' => [
-            "11110", "10001", "10001", "11110", "10000", "10000", "10000",
-        ],
-        'R' => [
-            "11110", "10001", "10001", "11110", "10100", "10010", "10001",
-        ],
-        'S' => [
-            "01111", "10000", "10000", "01110", "00001", "00001", "11110",
-        ],
-        'T' => [
-            "11111", "00100", "00100", "00100", "00100", "00100", "00100",
-        ],
-        'U' => [
-            "10001", "10001", "10001", "10001", "10001", "10001", "01110",
-        ],
-        'V' => [
-            "10001", "10001", "10001", "10001", "10001", "01010", "00100",
-        ],
-        'W' => [
-            "10001", "10001", "10001", "10101", "10101", "10101", "01010",
-        ],
-        'X' => [
-            "10001", "10001", "01010", "00100", "01010", "10001", "10001",
-        ],
-        'Y' => [
-            "10001", "10001", "01010", "00100", "00100", "00100", "00100",
-        ],
-        'Z' => [
-            "11111", "00001", "00010", "00100", "01000", "10000", "11111",
-        ],
-        '*' => [
-            "00100", "10101", "01110", "11111", "01110", "10101", "00100",
-        ],
-        '+' => [
-            "00100", "00100", "00100", "11111", "00100", "00100", "00100",
-        ],
-        '#' => [
-            "01010", "11111", "01010", "01010", "11111", "01010", "01010",
-        ],
-        '%' => [
-            "11001", "11010", "00100", "01000", "10110", "00110", "00000",
-        ],
-        '@' => [
-            "01110", "10001", "10111", "10101", "10111", "10000", "01110",
-        ],
-        _ => return None,
-    };
-
-    Some(glyph)
-}
-
 fn write_pdf_document(pages: &[String], page_width: f32, page_height: f32) -> Vec<u8> {
     let page_count = pages.len();
     let first_page_object_id = 3usize;
@@ -946,6 +1077,8 @@ mod tests {
                     dmc_code: "DMC-321".to_string(),
                     marker: "A".to_string(),
                     hex: "#C04040".to_string(),
+                    kind: StitchKind::Full,
+                    corner: None,
                 },
                 PdfExportStitch {
                     x: 1,
@@ -953,6 +1086,8 @@ mod tests {
                     dmc_code: "DMC-321".to_string(),
                     marker: "A".to_string(),
                     hex: "#C04040".to_string(),
+                    kind: StitchKind::Full,
+                    corner: None,
                 },
                 PdfExportStitch {
                     x: 2,
@@ -960,6 +1095,8 @@ mod tests {
                     dmc_code: "DMC-444".to_string(),
                     marker: "B".to_string(),
                     hex: "#EEEEEE".to_string(),
+                    kind: StitchKind::Full,
+                    corner: None,
                 },
                 PdfExportStitch {
                     x: 0,
@@ -967,6 +1104,8 @@ mod tests {
                     dmc_code: "DMC-321".to_string(),
                     marker: "A".to_string(),
                     hex: "#C04040".to_string(),
+                    kind: StitchKind::Full,
+                    corner: None,
                 },
             ],
             legend: vec![
@@ -976,6 +1115,8 @@ mod tests {
                     hex: "#C04040".to_string(),
                     stitch_count: 3,
                     coverage: 0.5,
+                    partial_count: 0,
+                    blend: None,
                 },
                 PdfExportLegendEntry {
                     dmc_code: "DMC-444".to_string(),
@@ -983,8 +1124,12 @@ mod tests {
                     hex: "#EEEEEE".to_string(),
                     stitch_count: 1,
                     coverage: 0.16,
+                    partial_count: 0,
+                    blend: None,
                 },
             ],
+            backstitch: Vec::new(),
+            floss: None,
         }
     }
 
@@ -1031,4 +1176,114 @@ mod tests {
         assert!(text.contains(" l\n"), "expected vector line commands");
         assert!(!text.contains("/Subtype /Image"));
     }
+
+    #[test]
+    fn multi_glyph_symbols_draw_every_glyph() {
+        let single = draw_vector_symbol("S", 0.0, 0.0, 10.0);
+        let pair = draw_vector_symbol("SO", 0.0, 0.0, 10.0);
+        assert!(!single.is_empty());
+        assert!(pair.matches(" re f").count() > single.matches(" re f").count());
+    }
+
+    #[test]
+    fn manifest_lists_blend_components() {
+        let mut payload = outline_fixture(PdfPageSize::Letter, None);
+        payload.legend[1].dmc_code = "3799+413".to_string();
+        payload.legend[1].blend = Some(["3799".to_string(), "413".to_string()]);
+
+        let stream = build_manifest_page(&payload, 612.0, 792.0);
+        assert!(stream.contains("(DMC-321) Tj"));
+        assert!(stream.contains("(3799 + 413) Tj"));
+        assert!(stream.contains("(1 strand each: Gray) Tj"));
+    }
+
+    #[test]
+    fn blueprint_draws_backstitch_over_the_grid() {
+        let mut payload = outline_fixture(PdfPageSize::Letter, None);
+        payload.backstitch = vec![PdfExportBackstitchLine {
+            dmc_code: "310".to_string(),
+            hex: "#000000".to_string(),
+            points: vec![
+                GridPoint { x: 0, y: 1 },
+                GridPoint { x: 2, y: 1 },
+                GridPoint { x: 2, y: 0 },
+            ],
+        }];
+        let layout = GridLayout::new(3, 2, 612.0, 792.0);
+
+        let page = build_stitch_grid_page(&payload, &layout);
+        let stroke = page
+            .find("0.000 0.000 0.000 RG\n")
+            .expect("backstitch stroke");
+        assert!(page[stroke..].starts_with(&format!(
+            "0.000 0.000 0.000 RG\n{:.3} {:.3} m\n",
+            layout.origin_x,
+            layout.origin_y + layout.cell
+        )));
+        assert!(
+            page.rfind(" re f").unwrap_or(0) < stroke,
+            "drawn after symbols"
+        );
+
+        let manifest = build_manifest_page(&payload, 612.0, 792.0);
+        assert!(manifest.contains("(Backstitch) Tj"));
+        assert!(manifest.contains("(3 bs) Tj"));
+    }
+
+    #[test]
+    fn blueprint_draws_fractional_stitches_as_triangles() {
+        let mut payload = outline_fixture(PdfPageSize::Letter, None);
+        payload.stitches[2].kind = StitchKind::ThreeQuarter;
+        payload.stitches[2].corner = Some(StitchCorner::TopLeft);
+        payload.stitches.push(PdfExportStitch {
+            x: 2,
+            y: 0,
+            dmc_code: "DMC-321".to_string(),
+            marker: "A".to_string(),
+            hex: "#C04040".to_string(),
+            kind: StitchKind::Quarter,
+            corner: Some(StitchCorner::BottomRight),
+        });
+        payload.legend[0].stitch_count = 4;
+        payload.legend[0].partial_count = 1;
+        let layout = GridLayout::new(3, 2, 612.0, 792.0);
+
+        let page = build_stitch_grid_page(&payload, &layout);
+        assert_eq!(page.matches(" l h f").count(), 2);
+        let (x, y) = layout.cell_bottom_left(2, 0, 2);
+        let [corner, _, _] = partial_triangle(StitchCorner::TopLeft, x, y, layout.cell);
+        assert_eq!(corner, (x, y + layout.cell));
+
+        let manifest = build_manifest_page(&payload, 612.0, 792.0);
+        assert!(manifest.contains("(4 st \\(1 part\\) | 50.0%) Tj"));
+
+        let regions = extract_outline_regions(&payload).expect("regions");
+        assert!(regions.iter().any(|region| region.dmc_code == "DMC-444"));
+    }
+
+    #[test]
+    fn manifest_lists_skeins_from_the_floss_estimate() {
+        let mut payload = outline_fixture(PdfPageSize::Letter, None);
+        payload.legend[1].dmc_code = "3799+413".to_string();
+        payload.legend[1].blend = Some(["3799".to_string(), "413".to_string()]);
+        let thread = |code: &str, skeins| crate::floss::ThreadEstimate {
+            dmc_code: code.to_string(),
+            meters: 1.0,
+            skeins,
+        };
+        payload.floss = Some(FlossEstimate {
+            threads: vec![thread("DMC-321", 2), thread("3799", 1), thread("413", 1)],
+            total_meters: 3.0,
+            total_skeins: 4,
+            stitching_hours: 1.2,
+            stitches_per_inch: 14.0,
+            strands: 2,
+        });
+
+        let stream = build_manifest_page(&payload, 612.0, 792.0);
+        assert!(stream.contains("(3 st | 50.0% | 2 sk) Tj"));
+        assert!(stream.contains("(Blend strand) Tj"));
+        assert_eq!(stream.matches("(1 sk) Tj").count(), 2);
+        assert!(stream.contains("Floss: 4 skeins at 2 strands, 14 stitches/in | About 2 h"));
+    }
 }
@@ -0,0 +1,192 @@
+//! Pixel-art input: native scale detection and exact colors.
+//!
+//! An upscaled sprite is made of `scale`-square blocks of one color each, aligned to the
+//! top-left corner. The scale is the largest block size every row and column run of equal
+//! pixels is a multiple of, checked against the blocks themselves; runs cut off by the
+//! right or bottom edge are ignored, so a partial last block is fine.
+
+use image::RgbaImage;
+use palette::{white_point::D65, Lab};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Pixel-art mode settings
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct PixelArtConfig {
+    /// Source pixels per art pixel; detected when `None`
+    #[serde(default)]
+    pub scale: Option<u32>,
+}
+
+/// Art colors that were matched to the same thread
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ThreadCollision {
+    pub dmc_code: String,
+    /// The art colors, as `#RRGGBB`
+    pub art_colors: Vec<String>,
+}
+
+/// How a pixel-art pattern was read
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct PixelArtReport {
+    pub scale: u32,
+    /// Exact colors in the stitched art
+    pub colors: u32,
+    pub collisions: Vec<ThreadCollision>,
+}
+
+fn gcd(a: u32, b: u32) -> u32 {
+    if b == 0 {
+        a
+    } else {
+        gcd(b, a % b)
+    }
+}
+
+/// Size in pixels of the art pixels `image` was upscaled with; 1 for ordinary images.
+pub fn detect_scale(image: &RgbaImage) -> u32 {
+    let (width, height) = image.dimensions();
+    let pixel = |x: u32, y: u32| image.get_pixel(x, y).0;
+
+    // Every complete run of equal pixels spans whole art pixels
+    let runs = |outer: u32, inner: u32, at: &dyn Fn(u32, u32) -> [u8; 4]| {
+        let mut scale = 0;
+        for o in 0..outer {
+            let mut start = 0;
+            for i in 1..inner {
+                if at(o, i) != at(o, i - 1) {
+                    scale = gcd(scale, i - start);
+                    start = i;
+                }
+            }
+        }
+        scale
+    };
+    let across = runs(height, width, &|y, x| pixel(x, y));
+    let down = runs(width, height, &|x, y| pixel(x, y));
+    let candidate = match gcd(across, down) {
+        0 => return 1,
+        scale => scale,
+    };
+
+    // Runs can line up by accident, so the blocks themselves must be flat
+    (1..=candidate)
+        .rev()
+        .filter(|scale| candidate % scale == 0)
+        .find(|&scale| {
+            (0..height)
+                .all(|y| (0..width).all(|x| pixel(x, y) == pixel(x - x % scale, y - y % scale)))
+        })
+        .unwrap_or(1)
+}
+
+/// Art-pixel dimensions of a `source`-sized image at `scale`.
+pub fn art_dimensions(source: (u32, u32), scale: u32) -> (u32, u32) {
+    (source.0.div_ceil(scale), source.1.div_ceil(scale))
+}
+
+/// The top-left value of every `scale`-square block of a row-major `source`-sized buffer.
+pub fn downsample<T: Copy + Default>(values: &[T], source: (u32, u32), scale: u32) -> Vec<T> {
+    let (width, height) = art_dimensions(source, scale);
+    (0..height)
+        .flat_map(|y| (0..width).map(move |x| (x, y)))
+        .map(|(x, y)| {
+            let idx = (y * scale * source.0 + x * scale) as usize;
+            values.get(idx).copied().unwrap_or_default()
+        })
+        .collect()
+}
+
+/// One pixel per art pixel of `image`.
+pub fn downsample_image(image: &RgbaImage, scale: u32) -> RgbaImage {
+    let (width, height) = art_dimensions(image.dimensions(), scale);
+    RgbaImage::from_fn(width, height, |x, y| *image.get_pixel(x * scale, y * scale))
+}
+
+/// Distinct colors of the stitched pixels, most used first, with every pixel's index into
+/// them and their pixel counts. Fabric pixels get label 0.
+pub fn exact_colors(
+    pixels: &[Lab<D65, f32>],
+    mask: Option<&[u8]>,
+) -> (Vec<Lab<D65, f32>>, Vec<u16>, Vec<u64>) {
+    let stitched = |i: usize| mask.map(|m| m[i] > 0).unwrap_or(true);
+    let key = |lab: &Lab<D65, f32>| [lab.l.to_bits(), lab.a.to_bits(), lab.b.to_bits()];
+
+    let mut counts: HashMap<[u32; 3], (usize, u64)> = HashMap::new();
+    for (i, pixel) in pixels.iter().enumerate() {
+        if stitched(i) {
+            counts.entry(key(pixel)).or_insert((i, 0)).1 += 1;
+        }
+    }
+    let mut colors: Vec<(usize, u64)> = counts.into_values().collect();
+    colors.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+
+    let index: HashMap<[u32; 3], u16> = colors
+        .iter()
+        .enumerate()
+        .map(|(label, (first, _))| (key(&pixels[*first]), label as u16))
+        .collect();
+    let labels = pixels
+        .iter()
+        .enumerate()
+        .map(
+            |(i, pixel)| {
+                if stitched(i) {
+                    index[&key(pixel)]
+                } else {
+                    0
+                }
+            },
+        )
+        .collect();
+    (
+        colors.iter().map(|(first, _)| pixels[*first]).collect(),
+        labels,
+        colors.iter().map(|(_, count)| *count).collect(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 3x2 sprite: a red, green and blue top row over a blue, blue and red bottom row.
+    fn sprite(scale: u32) -> RgbaImage {
+        let art = [
+            [[255, 0, 0, 255], [0, 255, 0, 255], [0, 0, 255, 255]],
+            [[0, 0, 255, 255], [0, 0, 255, 255], [255, 0, 0, 255]],
+        ];
+        RgbaImage::from_fn(3 * scale, 2 * scale, |x, y| {
+            image::Rgba(art[(y / scale) as usize][(x / scale) as usize])
+        })
+    }
+
+    #[test]
+    fn detects_the_upscale_factor() {
+        assert_eq!(detect_scale(&sprite(8)), 8);
+        assert_eq!(detect_scale(&sprite(3)), 3);
+        assert_eq!(detect_scale(&sprite(1)), 1);
+        assert_eq!(downsample_image(&sprite(8), 8), sprite(1));
+
+        // A cropped last column still reads at the full scale
+        let cropped = image::imageops::crop_imm(&sprite(4), 0, 0, 10, 8).to_image();
+        assert_eq!(detect_scale(&cropped), 4);
+        assert_eq!(downsample_image(&cropped, 4).dimensions(), (3, 2));
+
+        // A single stray pixel breaks the blocks
+        let mut noisy = sprite(4);
+        noisy.put_pixel(5, 1, image::Rgba([1, 2, 3, 255]));
+        assert_eq!(detect_scale(&noisy), 1);
+    }
+
+    #[test]
+    fn exact_colors_count_every_pixel() {
+        let lab = |l: f32| Lab::new(l, 0.0, 0.0);
+        let pixels = [lab(10.0), lab(50.0), lab(50.0), lab(90.0), lab(10.0)];
+        let (colors, labels, counts) = exact_colors(&pixels, Some(&[255, 255, 255, 255, 0]));
+        assert_eq!(colors, vec![lab(50.0), lab(10.0), lab(90.0)]);
+        assert_eq!(labels, vec![1, 0, 0, 2, 0]);
+        assert_eq!(counts, vec![2, 1, 1]);
+        assert_eq!(downsample(&[1u8, 2, 3, 4, 5, 6], (3, 2), 2), vec![1, 3]);
+    }
+}
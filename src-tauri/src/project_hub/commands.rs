@@ -138,6 +138,9 @@ pub fn save_project(
 
     validate_project_id(&project.project_id)?;
     project.reference_image_path = normalize_path_string(&project.reference_image_path)?;
+    if let Some(brand) = project.settings.thread_brand() {
+        project.settings.floss_brand = brand.label().to_string();
+    }
     if project.last_modified.trim().is_empty() {
         project.last_modified = now_timestamp();
     }
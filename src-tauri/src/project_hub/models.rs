@@ -1,3 +1,4 @@
+use crate::threads::ThreadBrand;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 
@@ -8,6 +9,13 @@ pub struct ProjectSettings {
     pub floss_brand: String,
 }
 
+impl ProjectSettings {
+    /// Bundled catalog named by `floss_brand`, if it names one.
+    pub fn thread_brand(&self) -> Option<ThreadBrand> {
+        ThreadBrand::from_label(&self.floss_brand)
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ProjectDocument {
     pub project_id: String,
@@ -0,0 +1,550 @@
+//! Palette quantizers besides k-means.
+//!
+//! Median cut, Wu and octree work on LAB directly, so their boxes and cells split along
+//! perceptual rather than RGB axes. Wu and octree bin LAB into a unit cube (L over 0..100,
+//! a and b over -128..128). Thread-direct skips clustering: every pixel is snapped to the
+//! catalog first, then the least-used threads are folded into their nearest neighbour until
+//! `color_count` remain, so no two palette entries can end up on the same thread.
+
+use crate::color_metric::ColorMetric;
+use crate::threads::{match_inventory, InventoryMatch, ThreadCatalog, ThreadColor};
+use palette::{white_point::D65, Lab};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::ops::Range;
+
+/// Wu histogram cells per axis, plus the zero row the cumulative moments need
+const WU_SIDE: usize = 33;
+
+/// Octree depth; 64 cells per axis is finer than any thread catalog
+const OCTREE_DEPTH: u32 = 6;
+
+/// How pixels are reduced to the palette
+#[derive(Debug, Copy, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Quantizer {
+    /// K-means with k-means++ seeding
+    #[default]
+    Kmeans,
+    /// Repeatedly halve the box with the widest, most populated spread at its median
+    MedianCut,
+    /// Wu's greedy variance minimization over a LAB histogram
+    Wu,
+    /// Merge the sparsest cells of an LAB octree
+    Octree,
+    /// Snap pixels to threads, then drop threads by least Delta-E loss
+    ThreadDirect,
+}
+
+fn unit_cube(lab: &Lab<D65, f32>) -> [f32; 3] {
+    [
+        (lab.l / 100.0).clamp(0.0, 1.0),
+        ((lab.a + 128.0) / 256.0).clamp(0.0, 1.0),
+        ((lab.b + 128.0) / 256.0).clamp(0.0, 1.0),
+    ]
+}
+
+fn mean(sum: [f64; 3], count: f64) -> Lab<D65, f32> {
+    Lab::new(
+        (sum[0] / count) as f32,
+        (sum[1] / count) as f32,
+        (sum[2] / count) as f32,
+    )
+}
+
+/// Up to `k` colors by median cut.
+pub fn median_cut(pixels: &[Lab<D65, f32>], k: usize) -> Vec<Lab<D65, f32>> {
+    if pixels.is_empty() || k == 0 {
+        return Vec::new();
+    }
+    let mut points: Vec<[f32; 3]> = pixels.iter().map(|p| [p.l, p.a, p.b]).collect();
+    let mut boxes: Vec<Range<usize>> = Vec::with_capacity(k);
+    boxes.push(0..points.len());
+
+    while boxes.len() < k {
+        // Widest axis of each box, weighted by its population
+        let widest = boxes
+            .iter()
+            .enumerate()
+            .filter_map(|(i, range)| {
+                let slice = &points[range.clone()];
+                (0..3)
+                    .map(|axis| {
+                        let (lo, hi) = slice.iter().fold((f32::MAX, f32::MIN), |(lo, hi), p| {
+                            (lo.min(p[axis]), hi.max(p[axis]))
+                        });
+                        (axis, hi - lo)
+                    })
+                    .max_by(|a, b| a.1.total_cmp(&b.1))
+                    .filter(|(_, extent)| *extent > 0.0)
+                    .map(|(axis, extent)| (i, axis, extent * slice.len() as f32))
+            })
+            .max_by(|a, b| a.2.total_cmp(&b.2));
+        let Some((i, axis, _)) = widest else {
+            break;
+        };
+
+        let range = boxes[i].clone();
+        let mid = range.len() / 2;
+        points[range.clone()].select_nth_unstable_by(mid, |a, b| a[axis].total_cmp(&b[axis]));
+        boxes[i] = range.start..range.start + mid;
+        boxes.push(range.start + mid..range.end);
+    }
+
+    boxes
+        .into_iter()
+        .map(|range| {
+            let slice = &points[range];
+            let sum = slice.iter().fold([0.0f64; 3], |mut sum, p| {
+                for c in 0..3 {
+                    sum[c] += p[c] as f64;
+                }
+                sum
+            });
+            mean(sum, slice.len() as f64)
+        })
+        .collect()
+}
+
+/// Cumulative moments of a Wu histogram: count, LAB sums and summed squared norm
+struct WuMoments {
+    weight: Vec<f64>,
+    sums: [Vec<f64>; 3],
+    squares: Vec<f64>,
+}
+
+/// Histogram box with exclusive lower and inclusive upper bounds per axis
+#[derive(Clone, Copy)]
+struct WuBox {
+    lo: [usize; 3],
+    hi: [usize; 3],
+}
+
+fn wu_index(i: usize, j: usize, k: usize) -> usize {
+    (i * WU_SIDE + j) * WU_SIDE + k
+}
+
+impl WuMoments {
+    fn new(pixels: &[Lab<D65, f32>]) -> Self {
+        let cells = WU_SIDE * WU_SIDE * WU_SIDE;
+        let mut moments = WuMoments {
+            weight: vec![0.0; cells],
+            sums: [vec![0.0; cells], vec![0.0; cells], vec![0.0; cells]],
+            squares: vec![0.0; cells],
+        };
+        let bins = (WU_SIDE - 1) as f32;
+        for pixel in pixels {
+            let u = unit_cube(pixel);
+            let bin = |c: usize| ((u[c] * bins) as usize).min(WU_SIDE - 2) + 1;
+            let idx = wu_index(bin(0), bin(1), bin(2));
+            let lab = [pixel.l as f64, pixel.a as f64, pixel.b as f64];
+            moments.weight[idx] += 1.0;
+            for (sums, value) in moments.sums.iter_mut().zip(lab) {
+                sums[idx] += value;
+            }
+            moments.squares[idx] += lab.iter().map(|v| v * v).sum::<f64>();
+        }
+
+        // Prefix sums along each axis in turn give inclusive 3D cumulative moments.
+        let strides = [WU_SIDE * WU_SIDE, WU_SIDE, 1];
+        for stride in strides {
+            for idx in 0..cells {
+                if (idx / stride) % WU_SIDE == 0 {
+                    continue;
+                }
+                moments.weight[idx] += moments.weight[idx - stride];
+                for c in 0..3 {
+                    moments.sums[c][idx] += moments.sums[c][idx - stride];
+                }
+                moments.squares[idx] += moments.squares[idx - stride];
+            }
+        }
+        moments
+    }
+
+    fn volume(table: &[f64], b: &WuBox) -> f64 {
+        let [l0, a0, b0] = b.lo;
+        let [l1, a1, b1] = b.hi;
+        table[wu_index(l1, a1, b1)] - table[wu_index(l1, a1, b0)] - table[wu_index(l1, a0, b1)]
+            + table[wu_index(l1, a0, b0)]
+            - table[wu_index(l0, a1, b1)]
+            + table[wu_index(l0, a1, b0)]
+            + table[wu_index(l0, a0, b1)]
+            - table[wu_index(l0, a0, b0)]
+    }
+
+    /// Count and LAB sums of a box
+    fn totals(&self, b: &WuBox) -> (f64, [f64; 3]) {
+        (
+            Self::volume(&self.weight, b),
+            [
+                Self::volume(&self.sums[0], b),
+                Self::volume(&self.sums[1], b),
+                Self::volume(&self.sums[2], b),
+            ],
+        )
+    }
+
+    fn variance(&self, b: &WuBox) -> f64 {
+        let (weight, sums) = self.totals(b);
+        if weight <= 0.0 {
+            return 0.0;
+        }
+        let norm: f64 = sums.iter().map(|s| s * s).sum();
+        Self::volume(&self.squares, b) - norm / weight
+    }
+
+    /// Split `b` where the two halves explain the most variance; `None` if it cannot split.
+    fn cut(&self, b: &WuBox) -> Option<(WuBox, WuBox)> {
+        let (weight, sums) = self.totals(b);
+        let score = |half: &WuBox| {
+            let (w, s) = self.totals(half);
+            let rest_w = weight - w;
+            if w <= 0.0 || rest_w <= 0.0 {
+                return None;
+            }
+            let rest: f64 = (0..3).map(|c| (sums[c] - s[c]).powi(2)).sum();
+            Some(s.iter().map(|v| v * v).sum::<f64>() / w + rest / rest_w)
+        };
+
+        let mut best: Option<(f64, WuBox, WuBox)> = None;
+        for axis in 0..3 {
+            for position in b.lo[axis] + 1..b.hi[axis] {
+                let mut lower = *b;
+                lower.hi[axis] = position;
+                let Some(value) = score(&lower) else {
+                    continue;
+                };
+                if best.map(|(v, _, _)| value > v).unwrap_or(true) {
+                    let mut upper = *b;
+                    upper.lo[axis] = position;
+                    best = Some((value, lower, upper));
+                }
+            }
+        }
+        best.map(|(_, lower, upper)| (lower, upper))
+    }
+}
+
+/// Up to `k` colors by Wu's variance-minimizing box cuts.
+pub fn wu(pixels: &[Lab<D65, f32>], k: usize) -> Vec<Lab<D65, f32>> {
+    if pixels.is_empty() || k == 0 {
+        return Vec::new();
+    }
+    let moments = WuMoments::new(pixels);
+    let mut boxes = vec![WuBox {
+        lo: [0; 3],
+        hi: [WU_SIDE - 1; 3],
+    }];
+    let mut variances = vec![moments.variance(&boxes[0])];
+
+    while boxes.len() < k {
+        let Some((next, _)) = variances
+            .iter()
+            .enumerate()
+            .filter(|(_, v)| **v > 0.0)
+            .max_by(|a, b| a.1.total_cmp(b.1))
+        else {
+            break;
+        };
+        match moments.cut(&boxes[next]) {
+            Some((lower, upper)) => {
+                boxes[next] = lower;
+                variances[next] = moments.variance(&lower);
+                boxes.push(upper);
+                variances.push(moments.variance(&upper));
+            }
+            None => variances[next] = 0.0,
+        }
+    }
+
+    boxes
+        .iter()
+        .filter_map(|b| {
+            let (weight, sums) = moments.totals(b);
+            (weight > 0.0).then(|| mean(sums, weight))
+        })
+        .collect()
+}
+
+struct OctreeNode {
+    children: [Option<usize>; 8],
+    count: u64,
+    sum: [f64; 3],
+    level: u32,
+    leaf: bool,
+}
+
+/// Up to `k` colors by octree reduction, folding the sparsest deepest cells first.
+pub fn octree(pixels: &[Lab<D65, f32>], k: usize) -> Vec<Lab<D65, f32>> {
+    if pixels.is_empty() || k == 0 {
+        return Vec::new();
+    }
+    let new_node = |level: u32| OctreeNode {
+        children: [None; 8],
+        count: 0,
+        sum: [0.0; 3],
+        level,
+        leaf: level == OCTREE_DEPTH,
+    };
+    let mut nodes = vec![new_node(0)];
+    let cells = (1u32 << OCTREE_DEPTH) as f32;
+    for pixel in pixels {
+        let u = unit_cube(pixel);
+        let coords = u.map(|v| ((v * cells) as u32).min((1 << OCTREE_DEPTH) - 1));
+        let lab = [pixel.l as f64, pixel.a as f64, pixel.b as f64];
+        let mut node = 0;
+        loop {
+            nodes[node].count += 1;
+            for (sum, value) in nodes[node].sum.iter_mut().zip(lab) {
+                *sum += value;
+            }
+            let level = nodes[node].level;
+            if level == OCTREE_DEPTH {
+                break;
+            }
+            let bit = OCTREE_DEPTH - 1 - level;
+            let child = ((coords[0] >> bit & 1) << 2
+                | (coords[1] >> bit & 1) << 1
+                | (coords[2] >> bit & 1)) as usize;
+            node = match nodes[node].children[child] {
+                Some(existing) => existing,
+                None => {
+                    nodes.push(new_node(level + 1));
+                    let created = nodes.len() - 1;
+                    nodes[node].children[child] = Some(created);
+                    created
+                }
+            };
+        }
+    }
+
+    // Fold whole levels bottom-up, sparsest nodes first, until `k` leaves remain.
+    let mut leaves = nodes.iter().filter(|n| n.leaf).count();
+    for level in (0..OCTREE_DEPTH).rev() {
+        if leaves <= k {
+            break;
+        }
+        let mut candidates: Vec<usize> = (0..nodes.len())
+            .filter(|&i| nodes[i].level == level)
+            .collect();
+        candidates.sort_by_key(|&i| (nodes[i].count, i));
+        for i in candidates {
+            if leaves <= k {
+                break;
+            }
+            let children = nodes[i].children.iter().flatten().count();
+            nodes[i].children = [None; 8];
+            nodes[i].leaf = true;
+            leaves = leaves + 1 - children;
+        }
+    }
+
+    let mut colors = Vec::with_capacity(leaves);
+    let mut stack = vec![0usize];
+    while let Some(i) = stack.pop() {
+        let node = &nodes[i];
+        if node.leaf {
+            colors.push(mean(node.sum, node.count as f64));
+        } else {
+            stack.extend(node.children.iter().rev().flatten());
+        }
+    }
+    colors
+}
+
+/// Threads chosen by [`thread_direct`], locked threads first
+pub struct DirectThreads {
+    pub threads: Vec<ThreadColor>,
+    pub purchases: Vec<String>,
+}
+
+/// Snap `pixels` to `catalog` threads and fold the least-missed threads into their nearest
+/// neighbour until at most `k` remain. `locked` threads are always kept.
+///
+/// Pixels are matched through [`match_inventory`], so the stash and purchase budget apply.
+pub fn thread_direct(
+    pixels: &[Lab<D65, f32>],
+    k: usize,
+    locked: &[ThreadColor],
+    catalog: &ThreadCatalog,
+    owned: &[String],
+    max_purchases: Option<u32>,
+    metric: ColorMetric,
+) -> Result<DirectThreads, String> {
+    // Near-identical pixels share one lookup.
+    let mut groups: HashMap<[i32; 3], ([f64; 3], u64)> = HashMap::new();
+    for pixel in pixels {
+        let key = [
+            pixel.l.round() as i32,
+            pixel.a.round() as i32,
+            pixel.b.round() as i32,
+        ];
+        let entry = groups.entry(key).or_insert(([0.0; 3], 0));
+        entry.0[0] += pixel.l as f64;
+        entry.0[1] += pixel.a as f64;
+        entry.0[2] += pixel.b as f64;
+        entry.1 += 1;
+    }
+    let mut groups: Vec<_> = groups.into_iter().collect();
+    groups.sort_by_key(|(key, _)| *key);
+    let targets: Vec<Lab<D65, f32>> = groups
+        .iter()
+        .map(|(_, (sum, count))| mean(*sum, *count as f64))
+        .collect();
+    let weights: Vec<u64> = groups.iter().map(|(_, (_, count))| *count).collect();
+    let InventoryMatch { threads, purchases } =
+        match_inventory(catalog, &targets, &weights, owned, max_purchases, metric)?;
+
+    // Candidate threads with the pixels they cover: locked first, then by use.
+    let mut candidates: Vec<(ThreadColor, u64)> =
+        locked.iter().map(|thread| (thread.clone(), 0)).collect();
+    let mut used: Vec<(ThreadColor, u64)> = Vec::new();
+    for (thread, weight) in threads.into_iter().zip(weights) {
+        if let Some(existing) = candidates.iter_mut().find(|(t, _)| t.code == thread.code) {
+            existing.1 += weight;
+        } else if let Some(existing) = used.iter_mut().find(|(t, _)| t.code == thread.code) {
+            existing.1 += weight;
+        } else {
+            used.push((thread, weight));
+        }
+    }
+    used.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.code.cmp(&b.0.code)));
+    candidates.extend(used);
+
+    let n = candidates.len();
+    let labs: Vec<Lab<D65, f32>> = candidates
+        .iter()
+        .map(|(thread, _)| Lab::new(thread.lab[0], thread.lab[1], thread.lab[2]))
+        .collect();
+    let distance: Vec<f32> = (0..n * n)
+        .map(|i| metric.distance(labs[i / n], labs[i % n]))
+        .collect();
+    let mut weight: Vec<u64> = candidates.iter().map(|(_, w)| *w).collect();
+    let mut alive = vec![true; n];
+    let nearest_alive = |alive: &[bool], i: usize| {
+        (0..n)
+            .filter(|&j| j != i && alive[j])
+            .min_by(|&a, &b| distance[i * n + a].total_cmp(&distance[i * n + b]))
+    };
+    let mut nearest: Vec<Option<usize>> = (0..n).map(|i| nearest_alive(&alive, i)).collect();
+
+    let mut remaining = n;
+    while remaining > k.max(locked.len()) {
+        // Folding a thread costs its pixels the distance to the neighbour they move to.
+        let loss = |i: usize| {
+            nearest[i]
+                .map(|j| weight[i] as f64 * distance[i * n + j] as f64)
+                .unwrap_or(f64::MAX)
+        };
+        let Some(drop) = (locked.len()..n)
+            .filter(|&i| alive[i])
+            .min_by(|&a, &b| loss(a).total_cmp(&loss(b)).then(b.cmp(&a)))
+        else {
+            break;
+        };
+        let Some(into) = nearest[drop] else {
+            break;
+        };
+        alive[drop] = false;
+        weight[into] += weight[drop];
+        remaining -= 1;
+        for i in 0..n {
+            if alive[i] && nearest[i] == Some(drop) {
+                nearest[i] = nearest_alive(&alive, i);
+            }
+        }
+    }
+
+    let threads: Vec<ThreadColor> = candidates
+        .into_iter()
+        .zip(alive)
+        .filter(|(_, alive)| *alive)
+        .map(|((thread, _), _)| thread)
+        .collect();
+    let purchases = purchases
+        .into_iter()
+        .filter(|code| {
+            threads
+                .iter()
+                .any(|thread| thread.component_codes().contains(&code.as_str()))
+        })
+        .collect();
+    Ok(DirectThreads { threads, purchases })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::embroidery::rgb_to_lab;
+    use crate::threads::ThreadBrand;
+
+    /// Three tight clusters: 50 dark blue, 25 orange and 25 pale green pixels.
+    fn clustered() -> Vec<Lab<D65, f32>> {
+        let mut pixels = Vec::new();
+        for (rgb, count) in [
+            ([20, 30, 120], 50),
+            ([240, 140, 20], 25),
+            ([190, 230, 190], 25),
+        ] {
+            for i in 0..count {
+                let jitter = (i % 5) as u8;
+                pixels.push(rgb_to_lab([rgb[0] + jitter, rgb[1], rgb[2] - jitter]));
+            }
+        }
+        pixels
+    }
+
+    #[test]
+    fn box_and_tree_quantizers_find_each_cluster() {
+        let pixels = clustered();
+        let centers = [
+            rgb_to_lab([22, 30, 118]),
+            rgb_to_lab([242, 140, 18]),
+            rgb_to_lab([192, 230, 188]),
+        ];
+        for (name, palette) in [
+            ("median cut", median_cut(&pixels, 3)),
+            ("wu", wu(&pixels, 3)),
+            ("octree", octree(&pixels, 3)),
+        ] {
+            assert_eq!(palette.len(), 3, "{}", name);
+            for center in centers {
+                let closest = palette
+                    .iter()
+                    .map(|color| ColorMetric::Cie76.distance(*color, center))
+                    .fold(f32::MAX, f32::min);
+                assert!(closest < 3.0, "{} misses {:?}: {}", name, center, closest);
+            }
+        }
+        assert!(wu(&pixels, 8).len() <= 8);
+        assert_eq!(median_cut(&[rgb_to_lab([1, 2, 3]); 4], 3).len(), 1);
+    }
+
+    #[test]
+    fn thread_direct_folds_the_least_used_thread() {
+        let dmc = ThreadCatalog::for_brand(ThreadBrand::Dmc).expect("DMC is bundled");
+        let thread = |code: &str| dmc.find_code(code).expect("thread").clone();
+        let lab = |code: &str| {
+            let t = thread(code);
+            Lab::new(t.lab[0], t.lab[1], t.lab[2])
+        };
+        // Lots of 310 black, some 3371 near-black brown and a little 321 red.
+        let pixels: Vec<Lab<D65, f32>> = [("310", 60), ("3371", 10), ("321", 30)]
+            .iter()
+            .flat_map(|(code, count)| vec![lab(code); *count])
+            .collect();
+
+        let direct = thread_direct(&pixels, 2, &[], &dmc, &[], None, ColorMetric::Ciede2000)
+            .expect("direct");
+        let codes: Vec<&str> = direct.threads.iter().map(|t| t.code.as_str()).collect();
+        assert_eq!(codes, vec!["310", "321"]);
+
+        // A locked thread survives even when it is the cheapest to drop.
+        let locked = [thread("3371")];
+        let direct = thread_direct(&pixels, 2, &locked, &dmc, &[], None, ColorMetric::Ciede2000)
+            .expect("direct");
+        assert_eq!(direct.threads[0].code, "3371");
+        assert_eq!(direct.threads.len(), 2);
+    }
+}
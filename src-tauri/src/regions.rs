@@ -1,3 +1,4 @@
+use crate::embroidery::{LabelGrid, FABRIC_LABEL};
 use serde::{Deserialize, Serialize};
 use std::collections::{HashMap, VecDeque};
 use std::sync::{Mutex, OnceLock};
@@ -29,7 +30,11 @@ pub struct PatternRegion {
 pub struct RegionExtractionPayload {
     pub width: u32,
     pub height: u32,
+    /// Legacy per-cell form; ignored when `grid` is present
+    #[serde(default)]
     pub stitches: Vec<RegionStitch>,
+    #[serde(default)]
+    pub grid: Option<LabelGrid>,
     pub legend: Vec<RegionLegendEntry>,
 }
 
@@ -111,26 +116,47 @@ pub fn extract_regions(payload: &RegionExtractionPayload) -> Result<Vec<PatternR
         palette_hex.push(entry.hex.clone());
     }
 
+    let mut intern = |code: &str, hex: &str| {
+        let key = color_key(code, hex);
+        if let Some(idx) = palette_by_key.get(&key) {
+            return *idx;
+        }
+        let idx = palette_code.len();
+        palette_by_key.insert(key, idx);
+        palette_code.push(code.to_string());
+        palette_hex.push(hex.to_string());
+        idx
+    };
+
     let mut color_grid = vec![NO_REGION; len];
-    for stitch in &payload.stitches {
-        if stitch.x >= payload.width
-            || stitch.y >= payload.height
-            || is_fabric_code(&stitch.dmc_code)
-        {
-            continue;
+    if let Some(grid) = &payload.grid {
+        // Intern lazily so palette order matches the per-cell form.
+        let mut index_by_label: Vec<Option<usize>> = vec![None; grid.colors.len()];
+        for (idx, &label) in grid.labels.iter().enumerate().take(len) {
+            if label == FABRIC_LABEL {
+                continue;
+            }
+            let Some(color) = grid.colors.get(label as usize) else {
+                continue;
+            };
+            if is_fabric_code(&color.dmc_code) {
+                continue;
+            }
+            let color_index = *index_by_label[label as usize]
+                .get_or_insert_with(|| intern(&color.dmc_code, &color.hex));
+            color_grid[idx] = color_index;
+        }
+    } else {
+        for stitch in &payload.stitches {
+            if stitch.x >= payload.width
+                || stitch.y >= payload.height
+                || is_fabric_code(&stitch.dmc_code)
+            {
+                continue;
+            }
+            let idx = stitch.y as usize * width + stitch.x as usize;
+            color_grid[idx] = intern(&stitch.dmc_code, &stitch.hex);
         }
-        let key = color_key(&stitch.dmc_code, &stitch.hex);
-        let color_index = if let Some(idx) = palette_by_key.get(&key) {
-            *idx
-        } else {
-            let idx = palette_code.len();
-            palette_by_key.insert(key, idx);
-            palette_code.push(stitch.dmc_code.clone());
-            palette_hex.push(stitch.hex.clone());
-            idx
-        };
-        let idx = stitch.y as usize * width + stitch.x as usize;
-        color_grid[idx] = color_index;
     }
 
     let mut visited = vec![false; len];
@@ -267,65 +293,16 @@ fn build_region_loops(
     region_id_grid: &[usize],
     region_id: usize,
 ) -> Vec<Vec<GridPoint>> {
+    let inside = |cell: Option<usize>| cell.is_some_and(|idx| region_id_grid[idx] == region_id);
     let mut segments = Vec::<(GridPoint, GridPoint)>::new();
-
-    for idx in 0..region_id_grid.len() {
-        if region_id_grid[idx] != region_id {
-            continue;
-        }
-
-        let x = idx % width;
-        let y = idx / width;
-
-        if y == 0 || region_id_grid[idx - width] != region_id {
-            segments.push((
-                GridPoint {
-                    x: x as i32,
-                    y: y as i32,
-                },
-                GridPoint {
-                    x: x as i32 + 1,
-                    y: y as i32,
-                },
-            ));
-        }
-        if x + 1 >= width || region_id_grid[idx + 1] != region_id {
-            segments.push((
-                GridPoint {
-                    x: x as i32 + 1,
-                    y: y as i32,
-                },
-                GridPoint {
-                    x: x as i32 + 1,
-                    y: y as i32 + 1,
-                },
-            ));
-        }
-        if y + 1 >= height || region_id_grid[idx + width] != region_id {
-            segments.push((
-                GridPoint {
-                    x: x as i32 + 1,
-                    y: y as i32 + 1,
-                },
-                GridPoint {
-                    x: x as i32,
-                    y: y as i32 + 1,
-                },
-            ));
+    for_each_cell_edge(width, height, |start, end, before, after| {
+        let (before, after) = (inside(before), inside(after));
+        if before != after {
+            // Keep the region on the right: rightwards along its top, downwards along its right.
+            let forward = if start.y == end.y { after } else { before };
+            segments.push(if forward { (start, end) } else { (end, start) });
         }
-        if x == 0 || region_id_grid[idx - 1] != region_id {
-            segments.push((
-                GridPoint {
-                    x: x as i32,
-                    y: y as i32 + 1,
-                },
-                GridPoint {
-                    x: x as i32,
-                    y: y as i32,
-                },
-            ));
-        }
-    }
+    });
 
     if segments.is_empty() {
         return Vec::new();
@@ -415,6 +392,33 @@ fn build_region_loops(
     loops
 }
 
+/// Visit every unit cell edge of a `width` x `height` grid: horizontal edges above each row,
+/// then vertical edges left of each column. Edges run rightwards or downwards between the
+/// cell before them (above or left) and after them (below or right); `None` is off the grid.
+pub(crate) fn for_each_cell_edge(
+    width: usize,
+    height: usize,
+    mut visit: impl FnMut(GridPoint, GridPoint, Option<usize>, Option<usize>),
+) {
+    let cell = |x: usize, y: usize| (x < width && y < height).then_some(y * width + x);
+    let point = |x: usize, y: usize| GridPoint {
+        x: x as i32,
+        y: y as i32,
+    };
+    for y in 0..=height {
+        for x in 0..width {
+            let above = y.checked_sub(1).and_then(|above| cell(x, above));
+            visit(point(x, y), point(x + 1, y), above, cell(x, y));
+        }
+    }
+    for x in 0..=width {
+        for y in 0..height {
+            let left = x.checked_sub(1).and_then(|left| cell(left, y));
+            visit(point(x, y), point(x, y + 1), left, cell(x, y));
+        }
+    }
+}
+
 fn simplify_axis_aligned_loop(mut loop_points: Vec<GridPoint>) -> Vec<GridPoint> {
     if loop_points.len() < 4 {
         return loop_points;
@@ -500,6 +504,18 @@ fn hash_region_payload(payload: &RegionExtractionPayload) -> u64 {
         hash = fnv1a_str(hash, &stitch.hex);
     }
 
+    if let Some(grid) = &payload.grid {
+        hash = fnv1a_u64(hash, grid.labels.len() as u64);
+        for label in &grid.labels {
+            hash = fnv1a_bytes(hash, &label.to_le_bytes());
+        }
+        hash = fnv1a_u64(hash, grid.colors.len() as u64);
+        for color in &grid.colors {
+            hash = fnv1a_str(hash, &color.dmc_code);
+            hash = fnv1a_str(hash, &color.hex);
+        }
+    }
+
     hash = fnv1a_u64(hash, payload.legend.len() as u64);
     for legend in &payload.legend {
         hash = fnv1a_str(hash, &legend.dmc_code);
@@ -0,0 +1,229 @@
+//! Weights for palette training samples.
+//!
+//! A mask value above zero means the pixel is stitched, and the value weights how densely
+//! it is sampled for quantization: a face painted 255 over a background painted 40 claims
+//! most of the palette while the background is still stitched. Saliency and edge density
+//! derive a similar weighting from the image itself and scale the mask weights by it.
+//!
+//! Weights are normalized so the heaviest pixel is sampled as densely as an unweighted
+//! image, which keeps uniform and binary masks sampling exactly as before.
+
+use palette::{white_point::D65, Lab};
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// Weight the least salient pixels keep, so backgrounds still get some palette entries
+const MIN_AUTO_WEIGHT: f32 = 0.15;
+
+/// Automatic weighting of palette training samples
+#[derive(Debug, Copy, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum TrainingWeight {
+    /// Only the mask weights samples
+    #[default]
+    Uniform,
+    /// Favor colors that stand out from the image's average color
+    Saliency,
+    /// Favor busy, detailed areas over flat ones
+    EdgeDensity,
+}
+
+/// Sampling weight of every pixel, 0 for fabric and at most 1.
+pub fn training_weights(
+    pixels: &[Lab<D65, f32>],
+    width: u32,
+    height: u32,
+    mask: Option<&[u8]>,
+    mode: TrainingWeight,
+) -> Vec<f32> {
+    let base: Vec<f32> = match mask {
+        Some(mask) => mask.iter().map(|&m| m as f32).collect(),
+        None => vec![1.0; pixels.len()],
+    };
+    let auto = match mode {
+        TrainingWeight::Uniform => None,
+        TrainingWeight::Saliency => Some(saliency_map(pixels, width, height, mask)),
+        TrainingWeight::EdgeDensity => Some(edge_density_map(pixels, width, height)),
+    };
+    let weights: Vec<f32> = match auto {
+        Some(auto) => base
+            .iter()
+            .zip(auto)
+            .map(|(b, s)| b * (MIN_AUTO_WEIGHT + (1.0 - MIN_AUTO_WEIGHT) * s))
+            .collect(),
+        None => base,
+    };
+    let max = weights.iter().copied().fold(0.0, f32::max);
+    if max <= 0.0 {
+        return weights;
+    }
+    weights.into_iter().map(|w| w / max).collect()
+}
+
+/// Every `stride`-th unit of accumulated weight, so a pixel of weight 1 is taken once per
+/// `stride` pixels and lighter pixels proportionally less often.
+pub fn weighted_sample(
+    pixels: &[Lab<D65, f32>],
+    weights: &[f32],
+    stride: usize,
+) -> Vec<Lab<D65, f32>> {
+    let stride = stride.max(1) as f32;
+    let mut credit = stride - 1.0;
+    let mut samples = Vec::new();
+    for (pixel, &weight) in pixels.iter().zip(weights) {
+        credit += weight;
+        while credit >= stride {
+            samples.push(*pixel);
+            credit -= stride;
+        }
+    }
+    samples
+}
+
+/// Frequency-tuned saliency: distance of each lightly blurred pixel from the mean color of
+/// the stitched area, scaled to 0..1.
+fn saliency_map(
+    pixels: &[Lab<D65, f32>],
+    width: u32,
+    height: u32,
+    mask: Option<&[u8]>,
+) -> Vec<f32> {
+    let channel = |get: fn(&Lab<D65, f32>) -> f32| {
+        let values: Vec<f32> = pixels.iter().map(get).collect();
+        box_blur(&values, width, height, 1)
+    };
+    let blurred = [channel(|p| p.l), channel(|p| p.a), channel(|p| p.b)];
+
+    let (mut sum, mut count) = ([0.0f64; 3], 0usize);
+    for (i, pixel) in pixels.iter().enumerate() {
+        if mask.map(|m| m[i] > 0).unwrap_or(true) {
+            sum[0] += pixel.l as f64;
+            sum[1] += pixel.a as f64;
+            sum[2] += pixel.b as f64;
+            count += 1;
+        }
+    }
+    let mean = sum.map(|s| (s / count.max(1) as f64) as f32);
+
+    let distances: Vec<f32> = (0..pixels.len())
+        .into_par_iter()
+        .map(|i| {
+            (0..3)
+                .map(|c| (blurred[c][i] - mean[c]).powi(2))
+                .sum::<f32>()
+                .sqrt()
+        })
+        .collect();
+    normalize(distances)
+}
+
+/// Lightness gradient magnitude averaged over a neighbourhood about 1/32 of the image,
+/// scaled to 0..1.
+fn edge_density_map(pixels: &[Lab<D65, f32>], width: u32, height: u32) -> Vec<f32> {
+    let (w, h) = (width as usize, height as usize);
+    let lightness = |x: usize, y: usize| pixels[y * w + x].l;
+    let gradients: Vec<f32> = (0..pixels.len())
+        .into_par_iter()
+        .map(|i| {
+            let (x, y) = (i % w, i / w);
+            let dx = lightness((x + 1).min(w - 1), y) - lightness(x.saturating_sub(1), y);
+            let dy = lightness(x, (y + 1).min(h - 1)) - lightness(x, y.saturating_sub(1));
+            (dx * dx + dy * dy).sqrt()
+        })
+        .collect();
+    let radius = (width.max(height) / 64).max(1) as usize;
+    normalize(box_blur(&gradients, width, height, radius))
+}
+
+fn normalize(values: Vec<f32>) -> Vec<f32> {
+    let max = values.iter().copied().fold(0.0, f32::max);
+    if max <= 0.0 {
+        return vec![0.0; values.len()];
+    }
+    values.into_iter().map(|v| v / max).collect()
+}
+
+/// Mean over the `(2 * radius + 1)`-square window around each value, clamped at the edges.
+fn box_blur(values: &[f32], width: u32, height: u32, radius: usize) -> Vec<f32> {
+    let (w, h) = (width as usize, height as usize);
+    let pass = |values: &[f32], horizontal: bool| -> Vec<f32> {
+        (0..values.len())
+            .into_par_iter()
+            .map(|i| {
+                let (x, y) = (i % w, i / w);
+                let (pos, len) = if horizontal { (x, w) } else { (y, h) };
+                let range = pos.saturating_sub(radius)..(pos + radius + 1).min(len);
+                let count = range.len() as f32;
+                range
+                    .map(|p| {
+                        if horizontal {
+                            values[y * w + p]
+                        } else {
+                            values[p * w + x]
+                        }
+                    })
+                    .sum::<f32>()
+                    / count
+            })
+            .collect()
+    };
+    pass(&pass(values, true), false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::embroidery::rgb_to_lab;
+
+    #[test]
+    fn mask_weights_set_sampling_density() {
+        let pixels: Vec<Lab<D65, f32>> = (0..200u8).map(|i| rgb_to_lab([i, 0, 0])).collect();
+
+        // Uniform weights take every stride-th pixel, as a plain step would
+        let uniform = training_weights(&pixels, 20, 10, None, TrainingWeight::Uniform);
+        let plain: Vec<Lab<D65, f32>> = pixels.iter().step_by(3).copied().collect();
+        assert_eq!(weighted_sample(&pixels, &uniform, 3), plain);
+
+        // A binary mask samples its stitched pixels the same way
+        let mask: Vec<u8> = (0..200).map(|i| if i % 2 == 0 { 1 } else { 0 }).collect();
+        let weights = training_weights(&pixels, 20, 10, Some(&mask), TrainingWeight::Uniform);
+        let plain: Vec<Lab<D65, f32>> = pixels.iter().step_by(2).step_by(3).copied().collect();
+        assert_eq!(weighted_sample(&pixels, &weights, 3), plain);
+
+        // The first half is painted five times heavier than the second
+        let mask: Vec<u8> = (0..200).map(|i| if i < 100 { 255 } else { 51 }).collect();
+        let weights = training_weights(&pixels, 20, 10, Some(&mask), TrainingWeight::Uniform);
+        let samples = weighted_sample(&pixels, &weights, 2);
+        let heavy = samples.iter().filter(|p| p.l < pixels[100].l).count();
+        assert_eq!(heavy, 50);
+        assert!((9..=11).contains(&(samples.len() - heavy)));
+    }
+
+    #[test]
+    fn saliency_and_edges_favor_the_subject() {
+        // A red 4x4 square in the middle of a flat gray 16x16 image; weights are compared
+        // on the square's left edge
+        let (width, height) = (16u32, 16u32);
+        let pixels: Vec<Lab<D65, f32>> = (0..width * height)
+            .map(|i| {
+                let (x, y) = (i % width, i / width);
+                if (6..10).contains(&x) && (6..10).contains(&y) {
+                    rgb_to_lab([220, 30, 40])
+                } else {
+                    rgb_to_lab([128, 128, 128])
+                }
+            })
+            .collect();
+        let edge = (8 * width + 6) as usize;
+        for mode in [TrainingWeight::Saliency, TrainingWeight::EdgeDensity] {
+            let weights = training_weights(&pixels, width, height, None, mode);
+            let corner = weights[0];
+            assert!(weights[edge] > 0.5, "{:?}", mode);
+            assert!(
+                (MIN_AUTO_WEIGHT - 1e-6..0.5).contains(&corner),
+                "{:?}",
+                mode
+            );
+        }
+    }
+}
@@ -1,5 +1,6 @@
-use crate::embroidery::{PatternResult, Stitch};
-use palette::{color_difference::Ciede2000, white_point::D65, FromColor, Lab, Srgb};
+use crate::color_metric::ColorMetric;
+use crate::embroidery::PatternResult;
+use palette::{white_point::D65, FromColor, Lab, Srgb};
 use serde::{Deserialize, Serialize};
 use std::cmp::Ordering;
 use std::collections::{HashMap, HashSet, VecDeque};
@@ -14,6 +15,9 @@ pub struct Stage4Config {
     pub smoothing_strength: f32,
     pub smoothing_passes: u8,
     pub max_merge_passes: u16,
+    /// Color difference used to pick merge targets
+    #[serde(default)]
+    pub color_metric: ColorMetric,
 }
 
 #[derive(Debug, Copy, Clone, Serialize, Deserialize, PartialEq, Eq)]
@@ -34,6 +38,7 @@ impl Stage4Config {
             smoothing_strength: 0.25,
             smoothing_passes: 1,
             max_merge_passes: 96,
+            color_metric: ColorMetric::Ciede2000,
         }
     }
 
@@ -46,6 +51,7 @@ impl Stage4Config {
             smoothing_strength: 0.45,
             smoothing_passes: 1,
             max_merge_passes: 120,
+            color_metric: ColorMetric::Ciede2000,
         }
     }
 
@@ -58,6 +64,7 @@ impl Stage4Config {
             smoothing_strength: 0.55,
             smoothing_passes: 2,
             max_merge_passes: 160,
+            color_metric: ColorMetric::Ciede2000,
         }
     }
 
@@ -376,13 +383,21 @@ fn build_label_map(
 ) -> (Vec<i32>, Vec<ColorMeta>) {
     let mut labels = vec![-1; width * height];
     let color_name_by_code = build_dmc_name_lookup(pattern);
+    let grid = pattern.label_grid();
+
+    let mut used = vec![false; grid.colors.len()];
+    for &label in &grid.labels {
+        if let Some(flag) = used.get_mut(label as usize) {
+            *flag = true;
+        }
+    }
 
     let mut unique_keys = HashSet::<String>::new();
-    for stitch in &pattern.stitches {
-        if is_fabric(stitch) {
+    for (color, _) in grid.colors.iter().zip(&used).filter(|(_, used)| **used) {
+        if is_fabric_code(&color.dmc_code) {
             continue;
         }
-        unique_keys.insert(color_key(&stitch.dmc_code, &stitch.hex));
+        unique_keys.insert(color_key(&color.dmc_code, &color.hex));
     }
 
     let mut ordered_keys = unique_keys.into_iter().collect::<Vec<_>>();
@@ -407,19 +422,18 @@ fn build_label_map(
         label_by_key.insert(key.clone(), idx);
     }
 
-    for stitch in &pattern.stitches {
-        if is_fabric(stitch) {
-            continue;
-        }
-        let x = stitch.x as usize;
-        let y = stitch.y as usize;
-        if x >= width || y >= height {
-            continue;
-        }
-
-        let key = color_key(&stitch.dmc_code, &stitch.hex);
-        if let Some(label) = label_by_key.get(&key) {
-            labels[y * width + x] = *label as i32;
+    let label_by_color: Vec<Option<i32>> = grid
+        .colors
+        .iter()
+        .map(|color| {
+            label_by_key
+                .get(&color_key(&color.dmc_code, &color.hex))
+                .map(|label| *label as i32)
+        })
+        .collect();
+    for (idx, &label) in grid.labels.iter().enumerate().take(width * height) {
+        if let Some(Some(label)) = label_by_color.get(label as usize) {
+            labels[idx] = *label;
         }
     }
 
@@ -515,6 +529,7 @@ fn enforce_region_constraints(
                 &selected_set,
                 palette,
                 false,
+                config.color_metric,
             )
             .or_else(|| {
                 choose_merge_target(
@@ -523,6 +538,7 @@ fn enforce_region_constraints(
                     &selected_set,
                     palette,
                     true,
+                    config.color_metric,
                 )
             }) else {
                 continue;
@@ -576,6 +592,7 @@ fn choose_merge_target(
     selected_sources: &HashSet<usize>,
     palette: &[ColorMeta],
     allow_source_target: bool,
+    metric: ColorMetric,
 ) -> Option<usize> {
     let source = components.get(source_id)?;
     let mut options = source
@@ -588,7 +605,7 @@ fn choose_merge_target(
             let neighbor = components.get(*neighbor_id)?;
             let source_meta = palette.get(source.label)?;
             let neighbor_meta = palette.get(neighbor.label)?;
-            let color_distance = source_meta.lab.difference(neighbor_meta.lab);
+            let color_distance = metric.distance(source_meta.lab, neighbor_meta.lab);
             Some((
                 neighbor.label,
                 boundary_len,
@@ -1262,8 +1279,8 @@ fn normalize_hex(hex: &str) -> String {
     }
 }
 
-fn is_fabric(stitch: &Stitch) -> bool {
-    stitch.dmc_code.eq_ignore_ascii_case("fabric")
+fn is_fabric_code(code: &str) -> bool {
+    code.eq_ignore_ascii_case("fabric")
 }
 
 fn hex_to_rgb(hex: &str) -> Option<[u8; 3]> {
@@ -1289,9 +1306,14 @@ fn rgb_to_lab(rgb: [u8; 3]) -> Lab<D65, f32> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::confetti::ConfettiReport;
     #[cfg(feature = "stage4-fixtures")]
     use crate::embroidery::process_pattern;
-    use crate::embroidery::{ColorMapping, DmcMetadata, LegendEntry, PatternResult};
+    use crate::embroidery::{
+        ColorMapping, DmcMetadata, LabelGrid, LegendEntry, PatternResult, Stitch, ThreadCountReport,
+    };
+    use crate::fractional::StitchKind;
+    use crate::threads::ThreadBrand;
     #[cfg(feature = "stage4-fixtures")]
     use image::{ImageBuffer, Rgba};
     #[cfg(feature = "stage4-fixtures")]
@@ -1315,6 +1337,9 @@ mod tests {
                     dmc_code: (*code).to_string(),
                     marker: String::new(),
                     hex: (*hex).to_string(),
+                    blend: None,
+                    kind: StitchKind::Full,
+                    corner: None,
                 });
                 if code.eq_ignore_ascii_case("fabric") {
                     continue;
@@ -1335,15 +1360,28 @@ mod tests {
         PatternResult {
             width,
             height,
-            stitches,
+            grid: LabelGrid::from_stitches(width, height, &stitches),
+            stitches: Vec::new(),
             palette: mappings.values().map(|m| m.mapped_hex.clone()).collect(),
             dmc_palette: mappings.values().map(|m| m.dmc.hex.clone()).collect(),
+            brand: ThreadBrand::Dmc,
+            suggested_purchases: Vec::new(),
+            physical_size: None,
+            backstitch: Vec::new(),
+            confetti: ConfettiReport::default(),
+            thread_count: ThreadCountReport::default(),
+            pixel_art: None,
+            fabric_hex: "#FFFFFF".to_string(),
             legend: vec![LegendEntry {
                 dmc_code: "X".to_string(),
+                brand: Some(ThreadBrand::Dmc),
                 name: "X".to_string(),
                 hex: "#000000".to_string(),
                 stitch_count: 1,
                 coverage: 1.0,
+                partial_count: 0,
+                marker: String::new(),
+                blend: None,
             }],
             color_mappings: mappings.into_values().collect(),
             total_stitches: (width * height) as u32,
@@ -1359,6 +1397,7 @@ mod tests {
             smoothing_strength: 0.0,
             smoothing_passes: 0,
             max_merge_passes: 128,
+            color_metric: ColorMetric::Ciede2000,
         }
     }
 
@@ -1685,6 +1724,7 @@ mod tests {
                     smoothing_amount: 0.45,
                     simplify_amount: 0.25,
                     min_region_size: 10,
+                    ..crate::embroidery::ProcessingConfig::default()
                 };
                 let pattern = process_pattern(&image_bytes, &processing, None)
                     .expect("pattern processing failed");
@@ -1764,9 +1804,15 @@ mod tests {
     #[cfg(feature = "stage4-fixtures")]
     fn write_preview_png(path: &Path, pattern: &PatternResult) -> Result<(), String> {
         let mut preview = ImageBuffer::<Rgba<u8>, Vec<u8>>::new(pattern.width, pattern.height);
-        for stitch in &pattern.stitches {
-            let rgb = hex_to_rgb(&stitch.hex).unwrap_or([255, 255, 255]);
-            preview.put_pixel(stitch.x, stitch.y, Rgba([rgb[0], rgb[1], rgb[2], 255]));
+        let grid = pattern.label_grid();
+        for idx in 0..grid.labels.len() {
+            let rgb = grid
+                .color(idx)
+                .and_then(|color| hex_to_rgb(&color.hex))
+                .unwrap_or([255, 255, 255]);
+            let x = idx as u32 % pattern.width;
+            let y = idx as u32 / pattern.width;
+            preview.put_pixel(x, y, Rgba([rgb[0], rgb[1], rgb[2], 255]));
         }
 
         let mut file = fs::File::create(path).map_err(|e| e.to_string())?;
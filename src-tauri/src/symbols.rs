@@ -0,0 +1,500 @@
+//! Chart symbol allocation.
+//!
+//! Every legend entry gets its own symbol, whatever the palette size. Single glyphs from
+//! [`SYMBOL_SET`] are handed out first; after that symbols are pairs, then triples, of the
+//! same glyphs (bijective base-N numbering), so the sequence never repeats.
+//!
+//! Within that pool, symbols are chosen for legibility on a black-and-white print: colors
+//! that share a lot of border get glyphs whose bitmaps overlap little (no `O` next to `0`),
+//! and darker threads get heavier glyphs so the chart still reads as a tonal image.
+
+use crate::embroidery::{hex_to_rgb, rgb_to_lab, LabelGrid, FABRIC_LABEL};
+use crate::regions::for_each_cell_edge;
+use std::collections::{HashMap, HashSet};
+
+/// Glyphs in allocation order; [`marker_glyph`] has a bitmap for each one.
+///
+/// The first 30 keep the order charts used before allocation existed.
+pub const SYMBOL_SET: &[char] = &[
+    'S', 'O', 'T', '*', 'D', 'X', '+', '#', '%', '@', 'A', 'B', 'C', 'E', 'H', 'K', 'M', 'N', 'P',
+    'R', 'U', 'V', 'W', 'Y', 'Z', '0', '1', '2', '3', '4', 'F', 'G', 'I', 'J', 'L', 'Q', '5', '6',
+    '7', '8', '9', '=', '?', '!', '&', '$', '<', '>', '/', '^', '~',
+];
+
+/// Cost of giving two bordering codes look-alike glyphs, per share of shared border
+const SIMILARITY_COST: f32 = 1.0;
+/// Cost of a glyph whose ink weight does not match the thread's darkness
+const WEIGHT_COST: f32 = 0.35;
+/// Mild preference for symbols early in the sequence (single, familiar glyphs)
+const ORDER_COST: f32 = 0.15;
+
+/// The `index`-th symbol of the allocation sequence.
+pub fn symbol_for_index(index: usize) -> String {
+    let base = SYMBOL_SET.len();
+    let mut remaining = index;
+    let mut glyphs = Vec::new();
+    loop {
+        glyphs.push(SYMBOL_SET[remaining % base]);
+        if remaining < base {
+            break;
+        }
+        remaining = remaining / base - 1;
+    }
+    glyphs.iter().rev().collect()
+}
+
+fn is_valid_symbol(symbol: &str) -> bool {
+    !symbol.is_empty() && symbol.chars().all(|ch| SYMBOL_SET.contains(&ch))
+}
+
+/// 5x7 glyph bitmaps of a symbol, one bit per pixel
+fn glyph_masks(symbol: &str) -> Vec<u64> {
+    symbol
+        .chars()
+        .filter_map(marker_glyph)
+        .map(|rows| {
+            rows.iter()
+                .flat_map(|row| row.bytes())
+                .fold(0u64, |mask, bit| (mask << 1) | u64::from(bit == b'1'))
+        })
+        .collect()
+}
+
+/// Dice overlap of two symbols' bitmaps: 1 for identical, 0 for disjoint
+fn glyph_similarity(a: &[u64], b: &[u64]) -> f32 {
+    if a.len() != b.len() || a.is_empty() {
+        // Symbols of different lengths are told apart by their width alone.
+        return 0.0;
+    }
+    let total: f32 = a
+        .iter()
+        .zip(b)
+        .map(|(x, y)| {
+            let ink = (x.count_ones() + y.count_ones()).max(1) as f32;
+            2.0 * (x & y).count_ones() as f32 / ink
+        })
+        .sum();
+    total / a.len() as f32
+}
+
+/// Fraction of the glyph area that is inked
+fn glyph_weight(masks: &[u64]) -> f32 {
+    let ink: u32 = masks.iter().map(|m| m.count_ones()).sum();
+    ink as f32 / (35 * masks.len().max(1)) as f32
+}
+
+/// Give every thread code in `grid` one symbol that no other code shares.
+///
+/// Colors that share a code share its symbol. Existing symbols are kept when they are
+/// valid and unclaimed, so this can be re-run after palette edits without reshuffling the
+/// chart. The remaining codes, busiest first, take the free symbol that contrasts best
+/// with their already-assigned neighbors and matches their tone.
+pub fn assign_symbols(grid: &mut LabelGrid, width: u32) {
+    let mut counts = vec![0u64; grid.colors.len()];
+    for &label in &grid.labels {
+        if label != FABRIC_LABEL {
+            if let Some(count) = counts.get_mut(label as usize) {
+                *count += 1;
+            }
+        }
+    }
+
+    // One entry per code, in first-seen order, with its total stitch count.
+    let mut codes: Vec<(&str, u64)> = Vec::new();
+    let mut code_index: HashMap<&str, usize> = HashMap::new();
+    let mut code_of_label: Vec<usize> = Vec::with_capacity(grid.colors.len());
+    for (color, count) in grid.colors.iter().zip(&counts) {
+        let idx = *code_index
+            .entry(color.dmc_code.as_str())
+            .or_insert_with(|| {
+                codes.push((color.dmc_code.as_str(), 0));
+                codes.len() - 1
+            });
+        codes[idx].1 += count;
+        code_of_label.push(idx);
+    }
+    let mut order: Vec<usize> = (0..codes.len()).collect();
+    order.sort_by(|a, b| codes[*b].1.cmp(&codes[*a].1).then_with(|| a.cmp(b)));
+
+    let mut symbol_of_code: Vec<Option<String>> = vec![None; codes.len()];
+    let mut taken: HashSet<String> = HashSet::new();
+    for &idx in &order {
+        let code = codes[idx].0;
+        let existing = grid
+            .colors
+            .iter()
+            .find(|color| color.dmc_code == code && is_valid_symbol(&color.marker))
+            .map(|color| color.marker.clone());
+        if let Some(symbol) = existing {
+            if taken.insert(symbol.clone()) {
+                symbol_of_code[idx] = Some(symbol);
+            }
+        }
+    }
+
+    let adjacency = code_adjacency(grid, width, &code_of_label, codes.len());
+    let darkness: Vec<f32> = codes
+        .iter()
+        .map(|(code, _)| {
+            let hex = grid
+                .colors
+                .iter()
+                .find(|color| color.dmc_code == *code)
+                .map(|color| color.hex.as_str())
+                .unwrap_or("#FFFFFF");
+            1.0 - rgb_to_lab(hex_to_rgb(hex)).l / 100.0
+        })
+        .collect();
+
+    // Enough candidates for every code even if all the early ones are already taken.
+    let pool_size = (codes.len() + taken.len()).max(SYMBOL_SET.len());
+    let pool: Vec<(String, Vec<u64>)> = (0..pool_size)
+        .map(symbol_for_index)
+        .filter(|symbol| !taken.contains(symbol))
+        .map(|symbol| {
+            let masks = glyph_masks(&symbol);
+            (symbol, masks)
+        })
+        .collect();
+    let weights: Vec<f32> = pool.iter().map(|(_, masks)| glyph_weight(masks)).collect();
+    let (min_weight, max_weight) = weights
+        .iter()
+        .fold((f32::MAX, f32::MIN), |(lo, hi), w| (lo.min(*w), hi.max(*w)));
+    let weight_range = (max_weight - min_weight).max(f32::EPSILON);
+    let mut used = vec![false; pool.len()];
+
+    for &idx in &order {
+        if symbol_of_code[idx].is_some() {
+            continue;
+        }
+        let neighbors = &adjacency[idx];
+        let border: u64 = neighbors.values().sum();
+        let assigned_neighbors: Vec<(Vec<u64>, f32)> = neighbors
+            .iter()
+            .filter_map(|(other, shared)| {
+                let symbol = symbol_of_code[*other].as_deref()?;
+                Some((glyph_masks(symbol), *shared as f32 / border.max(1) as f32))
+            })
+            .collect();
+
+        let mut best: Option<(usize, f32)> = None;
+        for (candidate, (_, masks)) in pool.iter().enumerate() {
+            if used[candidate] {
+                continue;
+            }
+            let clash: f32 = assigned_neighbors
+                .iter()
+                .map(|(other, share)| share * glyph_similarity(masks, other))
+                .sum();
+            let weight = (weights[candidate] - min_weight) / weight_range;
+            let cost = SIMILARITY_COST * clash
+                + WEIGHT_COST * (weight - darkness[idx]).abs()
+                + ORDER_COST * candidate as f32 / pool.len() as f32;
+            if best.map(|(_, best_cost)| cost < best_cost).unwrap_or(true) {
+                best = Some((candidate, cost));
+            }
+        }
+
+        // The pool holds at least one free symbol per unassigned code.
+        if let Some((candidate, _)) = best {
+            used[candidate] = true;
+            symbol_of_code[idx] = Some(pool[candidate].0.clone());
+        }
+    }
+
+    for (color, code) in grid.colors.iter_mut().zip(&code_of_label) {
+        if let Some(symbol) = &symbol_of_code[*code] {
+            color.marker = symbol.clone();
+        }
+    }
+}
+
+/// Shared border length, in cell edges, between each pair of codes
+fn code_adjacency(
+    grid: &LabelGrid,
+    width: u32,
+    code_of_label: &[usize],
+    code_count: usize,
+) -> Vec<HashMap<usize, u64>> {
+    let mut adjacency: Vec<HashMap<usize, u64>> = vec![HashMap::new(); code_count];
+    let width = width.max(1) as usize;
+    let code_at = |idx: Option<usize>| -> Option<usize> {
+        let label = *grid.labels.get(idx?)?;
+        code_of_label.get(label as usize).copied()
+    };
+    for_each_cell_edge(width, grid.labels.len() / width, |_, _, before, after| {
+        if let (Some(a), Some(b)) = (code_at(before), code_at(after)) {
+            if a != b {
+                *adjacency[a].entry(b).or_insert(0) += 1;
+                *adjacency[b].entry(a).or_insert(0) += 1;
+            }
+        }
+    });
+    adjacency
+}
+
+/// 5x7 bitmap of a chart glyph, top row first; `1` is inked
+pub(crate) fn marker_glyph(marker: char) -> Option<[&'static str; 7]> {
+    let glyph = match marker {
+        '0' => [
+            "01110", "10001", "10011", "10101", "11001", "10001", "01110",
+        ],
+        '1' => [
+            "00100", "01100", "00100", "00100", "00100", "00100", "01110",
+        ],
+        '2' => [
+            "01110", "10001", "00001", "00010", "00100", "01000", "11111",
+        ],
+        '3' => [
+            "11110", "00001", "00001", "01110", "00001", "00001", "11110",
+        ],
+        '4' => [
+            "00010", "00110", "01010", "10010", "11111", "00010", "00010",
+        ],
+        '5' => [
+            "11111", "10000", "11110", "00001", "00001", "10001", "01110",
+        ],
+        '6' => [
+            "00110", "01000", "10000", "11110", "10001", "10001", "01110",
+        ],
+        '7' => [
+            "11111", "00001", "00010", "00100", "01000", "01000", "01000",
+        ],
+        '8' => [
+            "01110", "10001", "10001", "01110", "10001", "10001", "01110",
+        ],
+        '9' => [
+            "01110", "10001", "10001", "01111", "00001", "00010", "01100",
+        ],
+        'A' => [
+            "01110", "10001", "10001", "11111", "10001", "10001", "10001",
+        ],
+        'B' => [
+            "11110", "10001", "10001", "11110", "10001", "10001", "11110",
+        ],
+        'C' => [
+            "01111", "10000", "10000", "10000", "10000", "10000", "01111",
+        ],
+        'D' => [
+            "11110", "10001", "10001", "10001", "10001", "10001", "11110",
+        ],
+        'E' => [
+            "11111", "10000", "10000", "11110", "10000", "10000", "11111",
+        ],
+        'F' => [
+            "11111", "10000", "10000", "11110", "10000", "10000", "10000",
+        ],
+        'G' => [
+            "01111", "10000", "10000", "10011", "10001", "10001", "01111",
+        ],
+        'H' => [
+            "10001", "10001", "10001", "11111", "10001", "10001", "10001",
+        ],
+        'I' => [
+            "01110", "00100", "00100", "00100", "00100", "00100", "01110",
+        ],
+        'J' => [
+            "00111", "00010", "00010", "00010", "00010", "10010", "01100",
+        ],
+        'K' => [
+            "10001", "10010", "10100", "11000", "10100", "10010", "10001",
+        ],
+        'L' => [
+            "10000", "10000", "10000", "10000", "10000", "10000", "11111",
+        ],
+        'M' => [
+            "10001", "11011", "10101", "10101", "10001", "10001", "10001",
+        ],
+        'N' => [
+            "10001", "11001", "10101", "10011", "10001", "10001", "10001",
+        ],
+        'O' => [
+            "01110", "10001", "10001", "10001", "10001", "10001", "01110",
+        ],
+        'P' => [
+            "11110", "10001", "10001", "11110", "10000", "10000", "10000",
+        ],
+        'Q' => [
+            "01110", "10001", "10001", "10001", "10101", "10010", "01101",
+        ],
+        'R' => [
+            "11110", "10001", "10001", "11110", "10100", "10010", "10001",
+        ],
+        'S' => [
+            "01111", "10000", "10000", "01110", "00001", "00001", "11110",
+        ],
+        'T' => [
+            "11111", "00100", "00100", "00100", "00100", "00100", "00100",
+        ],
+        'U' => [
+            "10001", "10001", "10001", "10001", "10001", "10001", "01110",
+        ],
+        'V' => [
+            "10001", "10001", "10001", "10001", "10001", "01010", "00100",
+        ],
+        'W' => [
+            "10001", "10001", "10001", "10101", "10101", "10101", "01010",
+        ],
+        'X' => [
+            "10001", "10001", "01010", "00100", "01010", "10001", "10001",
+        ],
+        'Y' => [
+            "10001", "10001", "01010", "00100", "00100", "00100", "00100",
+        ],
+        'Z' => [
+            "11111", "00001", "00010", "00100", "01000", "10000", "11111",
+        ],
+        '*' => [
+            "00100", "10101", "01110", "11111", "01110", "10101", "00100",
+        ],
+        '+' => [
+            "00100", "00100", "00100", "11111", "00100", "00100", "00100",
+        ],
+        '#' => [
+            "01010", "11111", "01010", "01010", "11111", "01010", "01010",
+        ],
+        '%' => [
+            "11001", "11010", "00100", "01000", "10110", "00110", "00000",
+        ],
+        '@' => [
+            "01110", "10001", "10111", "10101", "10111", "10000", "01110",
+        ],
+        '=' => [
+            "00000", "00000", "11111", "00000", "11111", "00000", "00000",
+        ],
+        '?' => [
+            "01110", "10001", "00001", "00010", "00100", "00000", "00100",
+        ],
+        '!' => [
+            "00100", "00100", "00100", "00100", "00100", "00000", "00100",
+        ],
+        '&' => [
+            "01100", "10010", "10100", "01000", "10101", "10010", "01101",
+        ],
+        '$' => [
+            "00100", "01111", "10100", "01110", "00101", "11110", "00100",
+        ],
+        '<' => [
+            "00010", "00100", "01000", "10000", "01000", "00100", "00010",
+        ],
+        '>' => [
+            "01000", "00100", "00010", "00001", "00010", "00100", "01000",
+        ],
+        '/' => [
+            "00001", "00001", "00010", "00100", "01000", "10000", "10000",
+        ],
+        '^' => [
+            "00100", "01010", "10001", "00000", "00000", "00000", "00000",
+        ],
+        '~' => [
+            "00000", "00000", "01000", "10101", "00010", "00000", "00000",
+        ],
+        _ => return None,
+    };
+
+    Some(glyph)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::embroidery::GridColor;
+
+    fn grid(codes: &[&str], labels: Vec<u16>) -> LabelGrid {
+        LabelGrid {
+            labels,
+            colors: codes
+                .iter()
+                .map(|code| GridColor {
+                    dmc_code: code.to_string(),
+                    marker: String::new(),
+                    hex: "#000000".to_string(),
+                    blend: None,
+                })
+                .collect(),
+            partials: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn every_allocated_symbol_has_a_glyph() {
+        for ch in SYMBOL_SET {
+            assert!(marker_glyph(*ch).is_some(), "missing glyph for {:?}", ch);
+        }
+        let glyphs: HashSet<[&str; 7]> = SYMBOL_SET
+            .iter()
+            .filter_map(|ch| marker_glyph(*ch))
+            .collect();
+        assert_eq!(glyphs.len(), SYMBOL_SET.len());
+    }
+
+    #[test]
+    fn symbols_stay_unique_for_large_palettes() {
+        let count = SYMBOL_SET.len() * 3;
+        let symbols: HashSet<String> = (0..count).map(symbol_for_index).collect();
+        assert_eq!(symbols.len(), count);
+        assert_eq!(symbol_for_index(0), "S");
+        assert_eq!(symbol_for_index(SYMBOL_SET.len()), "SS");
+
+        let codes: Vec<String> = (0..200).map(|i| format!("C{}", i)).collect();
+        let code_refs: Vec<&str> = codes.iter().map(String::as_str).collect();
+        let mut big = grid(&code_refs, (0..200).collect());
+        assign_symbols(&mut big, 200);
+        let markers: HashSet<&str> = big.colors.iter().map(|c| c.marker.as_str()).collect();
+        assert_eq!(markers.len(), 200);
+    }
+
+    #[test]
+    fn shared_codes_share_a_symbol_and_reruns_are_stable() {
+        // Labels 0 and 2 are both 310; 321 is the most stitched code.
+        let mut chart = grid(
+            &["310", "321", "310", "White"],
+            vec![0, 1, 1, 1, 2, 3, FABRIC_LABEL],
+        );
+        assign_symbols(&mut chart, 7);
+        assert_eq!(chart.colors[1].marker.chars().count(), 1);
+        assert_eq!(chart.colors[0].marker, chart.colors[2].marker);
+        assert_ne!(chart.colors[0].marker, chart.colors[3].marker);
+
+        // Editing one color to an existing symbol only re-allocates the loser.
+        let before: Vec<String> = chart.colors.iter().map(|c| c.marker.clone()).collect();
+        chart.colors[3].marker = chart.colors[1].marker.clone();
+        assign_symbols(&mut chart, 7);
+        assert_eq!(chart.colors[1].marker, before[1]);
+        assert_eq!(chart.colors[0].marker, before[0]);
+        assert_ne!(chart.colors[3].marker, chart.colors[1].marker);
+        assert_ne!(chart.colors[3].marker, chart.colors[0].marker);
+    }
+
+    #[test]
+    fn bordering_colors_get_contrasting_glyphs_and_tone_sets_weight() {
+        // A 4x4 chart: a near-black block bordering a mid-gray block along a long edge,
+        // with a light block off to one side.
+        let mut chart = grid(&["310", "414", "762"], vec![]);
+        chart.colors[0].hex = "#101010".to_string();
+        chart.colors[1].hex = "#808080".to_string();
+        chart.colors[2].hex = "#F0F0F0".to_string();
+        chart.labels = vec![0, 0, 1, 1, 0, 0, 1, 1, 0, 0, 1, 1, 2, 2, 2, 2];
+        // The user already picked O for 310.
+        chart.colors[0].marker = "O".to_string();
+        assign_symbols(&mut chart, 4);
+
+        let dark = glyph_masks(&chart.colors[0].marker);
+        let gray = glyph_masks(&chart.colors[1].marker);
+        assert_eq!(chart.colors[0].marker, "O");
+        assert!(
+            glyph_similarity(&dark, &gray) < glyph_similarity(&dark, &glyph_masks("0")),
+            "{} looks too much like O",
+            chart.colors[1].marker
+        );
+
+        // With nothing pre-assigned, the darkest thread carries the heaviest glyph.
+        for color in &mut chart.colors {
+            color.marker.clear();
+        }
+        chart.labels = vec![0, 0, 2, 2, 0, 0, 2, 2, 1, 1, 1, 1, 1, 1, 1, 1];
+        assign_symbols(&mut chart, 4);
+        let weight = |idx: usize| glyph_weight(&glyph_masks(&chart.colors[idx].marker));
+        assert!(weight(0) > weight(2));
+    }
+}
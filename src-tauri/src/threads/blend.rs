@@ -0,0 +1,133 @@
+//! Blended-thread ("tweeded") virtual colors.
+//!
+//! Stitching one strand each of two threads reads as their average from normal viewing
+//! distance. Pairs are mixed in LAB and only offered when the components are close enough
+//! that the result looks like a shade rather than speckle.
+
+use super::{ThreadCatalog, ThreadColor};
+use crate::embroidery::{lab_to_rgb, rgb_to_hex};
+use palette::{color_difference::Ciede2000, white_point::D65, Lab};
+use rayon::prelude::*;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, OnceLock};
+
+/// Largest CIEDE2000 distance between two components that still blends convincingly
+pub const DEFAULT_MAX_BLEND_DELTA_E: f32 = 15.0;
+
+/// Blended catalogs keyed by base catalog address and `max_delta_e` bits. The base is held
+/// alongside so its address cannot be reused while the entry exists.
+type BlendCache = HashMap<(usize, u32), (Arc<ThreadCatalog>, Arc<ThreadCatalog>)>;
+
+static BLEND_CACHE: OnceLock<Mutex<BlendCache>> = OnceLock::new();
+
+impl ThreadCatalog {
+    /// This catalog plus one virtual thread per pair of threads within `max_delta_e`.
+    ///
+    /// Blends are coded `"A+B"` in catalog order and carry both component codes in
+    /// [`ThreadColor::blend`].
+    pub fn with_blends(&self, max_delta_e: f32) -> Self {
+        let singles: Vec<&ThreadColor> =
+            self.threads.iter().filter(|t| t.blend.is_none()).collect();
+
+        let blends: Vec<ThreadColor> = (0..singles.len())
+            .into_par_iter()
+            .flat_map_iter(|i| {
+                let singles = &singles;
+                (i + 1..singles.len())
+                    .filter_map(move |j| blend_pair(singles[i], singles[j], max_delta_e))
+            })
+            .collect();
+
+        let mut threads = self.threads.clone();
+        threads.extend(blends);
+        Self::from_threads(self.brand, threads)
+    }
+
+    /// [`Self::with_blends`] of a shared catalog, built once per catalog and `max_delta_e`.
+    pub fn cached_blends(self: &Arc<Self>, max_delta_e: f32) -> Arc<Self> {
+        let key = (Arc::as_ptr(self) as usize, max_delta_e.to_bits());
+        let Ok(mut cache) = BLEND_CACHE
+            .get_or_init(|| Mutex::new(HashMap::new()))
+            .lock()
+        else {
+            return Arc::new(self.with_blends(max_delta_e));
+        };
+        if let Some((_, blended)) = cache.get(&key) {
+            return blended.clone();
+        }
+        // Libraries that were re-imported since are only kept alive by the cache
+        cache.retain(|_, (base, _)| Arc::strong_count(base) > 1);
+        let blended = Arc::new(self.with_blends(max_delta_e));
+        cache.insert(key, (self.clone(), blended.clone()));
+        blended
+    }
+}
+
+fn blend_pair(a: &ThreadColor, b: &ThreadColor, max_delta_e: f32) -> Option<ThreadColor> {
+    let lab_a: Lab<D65, f32> = Lab::new(a.lab[0], a.lab[1], a.lab[2]);
+    let lab_b: Lab<D65, f32> = Lab::new(b.lab[0], b.lab[1], b.lab[2]);
+    let delta_e = lab_a.difference(lab_b);
+    // Identical swatches add nothing but a duplicate entry.
+    if delta_e > max_delta_e || delta_e < 1.0 {
+        return None;
+    }
+
+    let mixed = Lab::new(
+        (a.lab[0] + b.lab[0]) * 0.5,
+        (a.lab[1] + b.lab[1]) * 0.5,
+        (a.lab[2] + b.lab[2]) * 0.5,
+    );
+    let rgb = lab_to_rgb(mixed);
+    Some(ThreadColor {
+        brand: a.brand,
+        code: format!("{}+{}", a.code, b.code),
+        name: format!("{} + {}", a.name, b.name),
+        hex: rgb_to_hex(rgb),
+        rgb,
+        lab: [mixed.l, mixed.a, mixed.b],
+        maker: None,
+        series: None,
+        blend: Some([a.code.clone(), b.code.clone()]),
+        derived_from: a
+            .derived_from
+            .as_ref()
+            .zip(b.derived_from.as_ref())
+            .map(|(a, b)| format!("{}+{}", a, b)),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color_metric::ColorMetric;
+    use crate::threads::ThreadBrand;
+
+    #[test]
+    fn blends_fill_gaps_between_close_threads() {
+        let dmc = ThreadCatalog::for_brand(ThreadBrand::Dmc).expect("DMC is bundled");
+        let blended = dmc.with_blends(DEFAULT_MAX_BLEND_DELTA_E);
+        assert!(blended.threads().len() > dmc.threads().len());
+        let cached = dmc.cached_blends(DEFAULT_MAX_BLEND_DELTA_E);
+        assert!(Arc::ptr_eq(
+            &cached,
+            &dmc.cached_blends(DEFAULT_MAX_BLEND_DELTA_E)
+        ));
+        assert_eq!(cached.threads().len(), blended.threads().len());
+
+        let (_, single_de) =
+            dmc.find_closest_with_distance(Lab::new(50.0, 0.0, 0.0), ColorMetric::Ciede2000);
+        let (thread, blend_de) =
+            blended.find_closest_with_distance(Lab::new(50.0, 0.0, 0.0), ColorMetric::Ciede2000);
+        assert!(blend_de <= single_de);
+
+        let blend = blended
+            .threads()
+            .iter()
+            .find(|t| t.blend.is_some())
+            .expect("at least one blend");
+        let [a, b] = blend.blend.clone().expect("components");
+        assert_eq!(blend.code, format!("{}+{}", a, b));
+        assert!(dmc.contains(&a) && dmc.contains(&b));
+        assert!(thread.code.split('+').all(|code| dmc.contains(code)));
+    }
+}
@@ -0,0 +1,475 @@
+//! Cross-brand thread conversion.
+//!
+//! Re-maps a legend or a whole pattern onto another catalog. Conversions to or from a brand
+//! colored by DMC cross-references follow the cross-reference chart, since many DMC codes
+//! share a swatch; other brands are matched with a CIEDE2000 nearest-thread search and
+//! report the Delta-E of every substitution so charts can flag poor equivalents.
+//! Cross-referenced threads have no colorimetry to measure, so their substitutions carry
+//! no Delta-E.
+
+use super::{ThreadBrand, ThreadCatalog, ThreadColor};
+use crate::color_metric::ColorMetric;
+use crate::confetti::{analyze_confetti, ConfettiReport};
+use crate::embroidery::{
+    hex_to_rgb, rgb_to_lab, GridColor, LabelGrid, LabelUsage, LegendEntry, PatternResult,
+};
+use crate::symbols::assign_symbols;
+use palette::Lab;
+use serde::{Deserialize, Serialize};
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+
+/// One source thread and its nearest equivalent in the target brand
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ThreadSubstitution {
+    pub source_brand: Option<ThreadBrand>,
+    pub source_code: String,
+    pub source_hex: String,
+    pub brand: ThreadBrand,
+    pub code: String,
+    pub name: String,
+    pub hex: String,
+    /// `None` when either side is colored from a DMC cross-reference
+    pub delta_e: Option<f32>,
+    pub stitch_count: u32,
+}
+
+/// Pattern re-mapped onto another brand, with the substitutions that were applied
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PatternConversion {
+    pub pattern: PatternResult,
+    pub substitutions: Vec<ThreadSubstitution>,
+}
+
+/// Find the nearest `catalog` thread for every legend entry.
+pub fn convert_legend(legend: &[LegendEntry], catalog: &ThreadCatalog) -> Vec<ThreadSubstitution> {
+    legend
+        .iter()
+        .map(|entry| {
+            substitute(
+                catalog,
+                entry.brand,
+                &entry.dmc_code,
+                &entry.hex,
+                entry.stitch_count,
+            )
+        })
+        .collect()
+}
+
+/// Re-map every stitch, legend entry and color mapping of `pattern` onto `catalog`.
+///
+/// Source threads that land on the same target thread are merged into one legend entry,
+/// keeping the chart marker of the larger source.
+pub fn convert_pattern(pattern: &PatternResult, catalog: &ThreadCatalog) -> PatternConversion {
+    let mut substitutions = convert_legend(&pattern.legend, catalog);
+    let mut by_source: HashMap<String, usize> = substitutions
+        .iter()
+        .enumerate()
+        .map(|(idx, sub)| (source_key(&sub.source_code, &sub.source_hex), idx))
+        .collect();
+
+    // Stitches whose color is missing from the legend still get converted.
+    let grid = pattern.label_grid();
+    for color in stitched_cells(&grid) {
+        let next_idx = substitutions.len();
+        if let Entry::Vacant(slot) = by_source.entry(source_key(&color.dmc_code, &color.hex)) {
+            slot.insert(next_idx);
+            substitutions.push(substitute(
+                catalog,
+                Some(pattern.brand),
+                &color.dmc_code,
+                &color.hex,
+                0,
+            ));
+        }
+    }
+
+    // Pick one marker per target code: the marker of its largest source color.
+    let mut marker_by_source: HashMap<usize, String> = HashMap::new();
+    for color in stitched_cells(&grid) {
+        if let Some(idx) = by_source.get(&source_key(&color.dmc_code, &color.hex)) {
+            marker_by_source
+                .entry(*idx)
+                .or_insert_with(|| color.marker.clone());
+        }
+    }
+    let mut marker_by_target: HashMap<String, (u32, String)> = HashMap::new();
+    for (idx, sub) in substitutions.iter().enumerate() {
+        let marker = marker_by_source.get(&idx).cloned().unwrap_or_default();
+        let entry = marker_by_target
+            .entry(sub.code.clone())
+            .or_insert((sub.stitch_count, marker.clone()));
+        if sub.stitch_count > entry.0 {
+            *entry = (sub.stitch_count, marker);
+        }
+    }
+
+    let mut converted = pattern.clone();
+    converted.grid = grid.into_owned();
+    for color in &mut converted.grid.colors {
+        let Some(idx) = by_source.get(&source_key(&color.dmc_code, &color.hex)) else {
+            continue;
+        };
+        let sub = &substitutions[*idx];
+        color.dmc_code = sub.code.clone();
+        color.hex = sub.hex.clone();
+        color.blend = None;
+        if let Some((_, marker)) = marker_by_target.get(&sub.code) {
+            color.marker = marker.clone();
+        }
+    }
+    assign_symbols(&mut converted.grid, converted.width);
+    // Threads that merge in the target brand can join up former confetti.
+    converted.confetti = ConfettiReport {
+        reduced: pattern.confetti.reduced,
+        ..analyze_confetti(&converted.grid, converted.width, converted.height)
+    };
+    if !converted.stitches.is_empty() {
+        converted.stitches = converted
+            .grid
+            .to_stitches(converted.width, &converted.fabric_hex);
+    }
+
+    for mapping in &mut converted.color_mappings {
+        let (thread, _) = equivalent(
+            catalog,
+            Some(pattern.brand),
+            &mapping.dmc.code,
+            &mapping.dmc.hex,
+        );
+        mapping.mapped_hex = thread.hex.clone();
+        mapping.dmc.code = thread.code.clone();
+        mapping.dmc.name = thread.name.clone();
+        mapping.dmc.hex = thread.hex.clone();
+    }
+    // The palette lists the mapped thread of each color mapping, in the same order.
+    converted.dmc_palette = pattern
+        .dmc_palette
+        .iter()
+        .enumerate()
+        .map(|(idx, hex)| match pattern.color_mappings.get(idx) {
+            Some(mapping) if mapping.dmc.hex.eq_ignore_ascii_case(hex) => {
+                converted.color_mappings[idx].dmc.hex.clone()
+            }
+            _ => closest_for_hex(catalog, hex).0.hex.clone(),
+        })
+        .collect();
+
+    converted.legend = rebuild_legend(&converted, &substitutions, pattern.legend.len());
+    converted.brand = catalog.brand;
+
+    PatternConversion {
+        pattern: converted,
+        substitutions,
+    }
+}
+
+/// Legend of the converted `pattern`; the first `legend_len` substitutions are the source
+/// legend's, in order.
+fn rebuild_legend(
+    pattern: &PatternResult,
+    substitutions: &[ThreadSubstitution],
+    legend_len: usize,
+) -> Vec<LegendEntry> {
+    // Source legend entries without stitches are locked threads; keep their equivalents.
+    let pinned: Vec<&str> = substitutions
+        .iter()
+        .take(legend_len)
+        .filter(|sub| sub.stitch_count == 0)
+        .map(|sub| sub.code.as_str())
+        .collect();
+    let mut counts: HashMap<&str, LabelUsage> = pinned
+        .iter()
+        .map(|code| (*code, LabelUsage::default()))
+        .collect();
+    for (color, usage) in pattern.grid.colors.iter().zip(pattern.grid.usage()) {
+        if usage.stitches == 0 && !pinned.contains(&color.dmc_code.as_str()) {
+            continue;
+        }
+        let entry = counts.entry(color.dmc_code.as_str()).or_default();
+        entry.stitches += usage.stitches;
+        entry.partial += usage.partial;
+        entry.cells += usage.cells;
+    }
+    let total: f32 = counts.values().map(|usage| usage.cells).sum();
+
+    let mut legend: Vec<LegendEntry> = counts
+        .into_iter()
+        .filter_map(|(code, usage)| {
+            let sub = substitutions.iter().find(|sub| sub.code == code)?;
+            Some(LegendEntry {
+                dmc_code: sub.code.clone(),
+                brand: Some(sub.brand),
+                name: sub.name.clone(),
+                hex: sub.hex.clone(),
+                stitch_count: usage.stitches,
+                coverage: usage.cells / total.max(1.0),
+                partial_count: usage.partial,
+                marker: pattern
+                    .grid
+                    .colors
+                    .iter()
+                    .find(|color| color.dmc_code == sub.code)
+                    .map(|color| color.marker.clone())
+                    .unwrap_or_default(),
+                blend: None,
+            })
+        })
+        .collect();
+    legend.sort_by(|a, b| {
+        b.stitch_count
+            .cmp(&a.stitch_count)
+            .then_with(|| a.dmc_code.cmp(&b.dmc_code))
+    });
+    legend
+}
+
+/// Thread of every stitched cell in row-major order, then of every quarter stitch
+fn stitched_cells(grid: &LabelGrid) -> impl Iterator<Item = &GridColor> {
+    grid.labels
+        .iter()
+        .chain(grid.partials.iter().map(|partial| &partial.secondary))
+        .filter_map(|&label| grid.colors.get(label as usize))
+}
+
+fn substitute(
+    catalog: &ThreadCatalog,
+    source_brand: Option<ThreadBrand>,
+    source_code: &str,
+    source_hex: &str,
+    stitch_count: u32,
+) -> ThreadSubstitution {
+    let (thread, delta_e) = equivalent(catalog, source_brand, source_code, source_hex);
+    let measured = thread.derived_from.is_none()
+        && source_brand
+            .map(ThreadBrand::has_colorimetry)
+            .unwrap_or(true);
+    ThreadSubstitution {
+        source_brand,
+        source_code: source_code.to_string(),
+        source_hex: source_hex.to_string(),
+        brand: thread.brand,
+        code: thread.code.clone(),
+        name: thread.name.clone(),
+        hex: thread.hex.clone(),
+        delta_e: measured.then_some(delta_e),
+        stitch_count,
+    }
+}
+
+/// Target thread for a source thread, with the Delta-E between their colors.
+///
+/// Follows the DMC cross-reference chart when either brand is colored from it, falling
+/// back to the nearest color when the chart has no row for the source thread.
+fn equivalent<'a>(
+    catalog: &'a ThreadCatalog,
+    source_brand: Option<ThreadBrand>,
+    source_code: &str,
+    source_hex: &str,
+) -> (&'a ThreadColor, f32) {
+    match cross_reference(catalog, source_brand.unwrap_or_default(), source_code) {
+        Some(thread) => {
+            let source = rgb_to_lab(hex_to_rgb(source_hex));
+            let target = Lab::new(thread.lab[0], thread.lab[1], thread.lab[2]);
+            (thread, ColorMetric::Ciede2000.distance(source, target))
+        }
+        None => closest_for_hex(catalog, source_hex),
+    }
+}
+
+/// `catalog` thread sharing the source thread's DMC chart code, when either brand has no
+/// colorimetry of its own
+fn cross_reference<'a>(
+    catalog: &'a ThreadCatalog,
+    source_brand: ThreadBrand,
+    source_code: &str,
+) -> Option<&'a ThreadColor> {
+    if source_brand.has_colorimetry() && catalog.brand.has_colorimetry() {
+        return None;
+    }
+    let dmc_code = match source_brand {
+        ThreadBrand::Dmc => source_code.trim().to_string(),
+        brand => ThreadCatalog::for_brand(brand)?
+            .find_code(source_code)?
+            .derived_from
+            .clone()?,
+    };
+    if catalog.brand == ThreadBrand::Dmc {
+        return catalog.find_code(&dmc_code);
+    }
+    catalog.threads().iter().find(|thread| {
+        thread
+            .derived_from
+            .as_deref()
+            .is_some_and(|code| code.eq_ignore_ascii_case(&dmc_code))
+    })
+}
+
+fn closest_for_hex<'a>(catalog: &'a ThreadCatalog, hex: &str) -> (&'a ThreadColor, f32) {
+    catalog.find_closest_with_distance(rgb_to_lab(hex_to_rgb(hex)), ColorMetric::Ciede2000)
+}
+
+fn source_key(code: &str, hex: &str) -> String {
+    format!(
+        "{}|{}",
+        code.trim().to_ascii_uppercase(),
+        hex.trim().to_ascii_uppercase()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::confetti::ConfettiReport;
+    use crate::embroidery::{ColorMapping, DmcMetadata, Stitch, ThreadCountReport, FABRIC_LABEL};
+    use crate::fractional::StitchKind;
+
+    fn stitch(x: u32, code: &str, marker: &str, hex: &str) -> Stitch {
+        Stitch {
+            x,
+            y: 0,
+            dmc_code: code.to_string(),
+            marker: marker.to_string(),
+            hex: hex.to_string(),
+            blend: None,
+            kind: StitchKind::Full,
+            corner: None,
+        }
+    }
+
+    fn legend_entry(code: &str, hex: &str, stitch_count: u32) -> LegendEntry {
+        LegendEntry {
+            dmc_code: code.to_string(),
+            brand: Some(ThreadBrand::Dmc),
+            name: code.to_string(),
+            hex: hex.to_string(),
+            stitch_count,
+            coverage: 0.0,
+            partial_count: 0,
+            marker: String::new(),
+            blend: None,
+        }
+    }
+
+    #[test]
+    fn legend_conversion_reports_chart_equivalents() {
+        let legend = vec![
+            legend_entry("310", "#000000", 4),
+            legend_entry("321", "#CE1938", 2),
+        ];
+        let anchor = ThreadCatalog::for_brand(ThreadBrand::Anchor).expect("Anchor is bundled");
+        let subs = convert_legend(&legend, &anchor);
+
+        assert_eq!(subs[0].code, "403");
+        assert_eq!(subs[1].code, "9046");
+        assert!(subs.iter().all(|sub| sub.brand == ThreadBrand::Anchor));
+        // Anchor is colored from the DMC chart, so there is nothing to measure
+        assert!(subs.iter().all(|sub| sub.delta_e.is_none()));
+
+        let satin = ThreadCatalog::for_brand(ThreadBrand::DmcSatin).expect("Satin is bundled");
+        let subs = convert_legend(&legend, &satin);
+        assert!(subs.iter().all(|sub| sub.delta_e.is_some()));
+
+        let mut from_anchor = legend_entry("403", "#000000", 4);
+        from_anchor.brand = Some(ThreadBrand::Anchor);
+        let subs = convert_legend(&[from_anchor], &satin);
+        assert!(subs[0].delta_e.is_none());
+    }
+
+    /// Two navy DMC threads that share a swatch, on a 4x1 chart ending in fabric
+    fn navy_pattern() -> PatternResult {
+        PatternResult {
+            width: 4,
+            height: 1,
+            stitches: vec![
+                stitch(0, "336", "A", "#13294B"),
+                stitch(1, "336", "A", "#13294B"),
+                stitch(2, "823", "B", "#13294B"),
+                stitch(3, "Fabric", "", "#FFFFFF"),
+            ],
+            grid: LabelGrid::default(),
+            palette: vec!["#13294B".to_string()],
+            dmc_palette: vec!["#13294B".to_string()],
+            brand: ThreadBrand::Dmc,
+            suggested_purchases: Vec::new(),
+            physical_size: None,
+            backstitch: Vec::new(),
+            confetti: ConfettiReport::default(),
+            thread_count: ThreadCountReport::default(),
+            pixel_art: None,
+            fabric_hex: "#FFFFFF".to_string(),
+            legend: vec![
+                legend_entry("336", "#13294B", 2),
+                legend_entry("823", "#13294B", 1),
+            ],
+            color_mappings: vec![ColorMapping {
+                original_hex: "#13294B".to_string(),
+                mapped_hex: "#13294B".to_string(),
+                dmc: DmcMetadata {
+                    code: "336".to_string(),
+                    name: "Navy Blue".to_string(),
+                    hex: "#13294B".to_string(),
+                },
+            }],
+            total_stitches: 3,
+            processing_time_ms: 0,
+        }
+    }
+
+    #[test]
+    fn pattern_conversion_follows_cross_references() {
+        // 336 and 823 share a swatch in the DMC table but have their own Anchor codes.
+        let pattern = navy_pattern();
+
+        let anchor = ThreadCatalog::for_brand(ThreadBrand::Anchor).expect("Anchor is bundled");
+        let converted = convert_pattern(&pattern, &anchor);
+        assert_eq!(converted.pattern.brand, ThreadBrand::Anchor);
+        let codes: Vec<&str> = converted
+            .substitutions
+            .iter()
+            .map(|sub| sub.code.as_str())
+            .collect();
+        assert_eq!(codes, ["150", "152"]);
+        assert_eq!(converted.pattern.legend.len(), 2);
+        assert_eq!(converted.pattern.legend[0].dmc_code, "150");
+        assert_eq!(converted.pattern.legend[0].stitch_count, 2);
+        assert_eq!(converted.pattern.legend[1].dmc_code, "152");
+        assert_eq!(converted.pattern.legend[1].marker, "B");
+        assert!(converted.pattern.stitches[..2]
+            .iter()
+            .all(|s| s.marker == "A" && s.dmc_code == "150"));
+        assert_eq!(converted.pattern.stitches[2].dmc_code, "152");
+        assert_eq!(converted.pattern.stitches[3].dmc_code, "Fabric");
+        assert_eq!(converted.pattern.color_mappings[0].dmc.code, "150");
+        assert_eq!(converted.pattern.dmc_palette, ["#13294B"]);
+        assert_eq!(converted.pattern.grid.labels[3], FABRIC_LABEL);
+        assert_eq!(converted.pattern.grid.colors.len(), 2);
+
+        // Converting back follows the same chart row rather than the first matching swatch
+        let mut from_anchor = legend_entry("152", "#13294B", 1);
+        from_anchor.brand = Some(ThreadBrand::Anchor);
+        let dmc = ThreadCatalog::for_brand(ThreadBrand::Dmc).expect("DMC is bundled");
+        assert_eq!(convert_legend(&[from_anchor], &dmc)[0].code, "823");
+    }
+
+    #[test]
+    fn conversion_keeps_locked_threads_without_stitches() {
+        let mut pattern = navy_pattern();
+        let mut turquoise = legend_entry("597", "#6CB5BD", 0);
+        turquoise.marker = "T".to_string();
+        pattern.legend.push(turquoise);
+
+        let anchor = ThreadCatalog::for_brand(ThreadBrand::Anchor).expect("Anchor is bundled");
+        let converted = convert_pattern(&pattern, &anchor);
+        let locked = converted
+            .pattern
+            .legend
+            .iter()
+            .find(|entry| entry.dmc_code == "168")
+            .expect("locked thread is converted and kept");
+        assert_eq!(locked.stitch_count, 0);
+        assert_eq!(locked.coverage, 0.0);
+        assert_eq!(converted.pattern.legend.len(), 3);
+    }
+}
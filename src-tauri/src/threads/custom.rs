@@ -0,0 +1,332 @@
+//! User-imported thread libraries.
+//!
+//! Hand-dyed and specialty threads are loaded from CSV or JSON, validated row by row and,
+//! when clean, registered as a [`ThreadCatalog`] under a caller-chosen id. Validation never
+//! guesses: malformed hex and duplicate codes are reported with their line numbers instead
+//! of being coerced to black.
+
+use super::{ThreadBrand, ThreadCatalog, ThreadColor};
+use crate::embroidery::{parse_hex, rgb_to_hex};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, OnceLock};
+
+/// Source format of an imported library
+#[derive(Debug, Copy, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum LibraryFormat {
+    Csv,
+    Json,
+}
+
+/// A validation problem tied to a 1-based line of the source text
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct LibraryIssue {
+    pub line: usize,
+    pub message: String,
+}
+
+/// Outcome of an import; the library is only registered when `issues` is empty
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LibraryImportReport {
+    pub library_id: String,
+    pub thread_count: usize,
+    pub issues: Vec<LibraryIssue>,
+}
+
+/// One row of a library file, before validation
+#[derive(Debug, Deserialize)]
+struct LibraryRow {
+    #[serde(default)]
+    code: String,
+    #[serde(default)]
+    name: String,
+    #[serde(default)]
+    hex: String,
+    #[serde(default)]
+    brand: Option<String>,
+    #[serde(default)]
+    series: Option<String>,
+}
+
+static LIBRARY_CACHE: OnceLock<Mutex<HashMap<String, Arc<ThreadCatalog>>>> = OnceLock::new();
+
+fn library_cache() -> &'static Mutex<HashMap<String, Arc<ThreadCatalog>>> {
+    LIBRARY_CACHE.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+/// Look up a previously imported library.
+pub(super) fn library(library_id: &str) -> Option<Arc<ThreadCatalog>> {
+    library_cache().lock().ok()?.get(library_id).cloned()
+}
+
+/// Parse and validate `contents`, registering it as `library_id` when every row is valid.
+///
+/// Re-importing an id replaces the previous library. Row-level problems come back in the
+/// report; only unusable input (no id, unreadable structure) is an `Err`.
+pub fn import_library(
+    library_id: &str,
+    contents: &str,
+    format: LibraryFormat,
+) -> Result<LibraryImportReport, String> {
+    let library_id = library_id.trim();
+    if library_id.is_empty() {
+        return Err("Thread library id must not be empty".to_string());
+    }
+
+    let rows = match format {
+        LibraryFormat::Csv => parse_csv(contents)?,
+        LibraryFormat::Json => parse_json(contents)?,
+    };
+    let (threads, mut issues) = validate_rows(rows);
+    if threads.is_empty() && issues.is_empty() {
+        issues.push(LibraryIssue {
+            line: 1,
+            message: "Library contains no threads".to_string(),
+        });
+    }
+
+    let report = LibraryImportReport {
+        library_id: library_id.to_string(),
+        thread_count: threads.len(),
+        issues,
+    };
+    if report.issues.is_empty() {
+        let catalog = Arc::new(ThreadCatalog::from_threads(ThreadBrand::Custom, threads));
+        library_cache()
+            .lock()
+            .map_err(|_| "Thread library cache is poisoned".to_string())?
+            .insert(report.library_id.clone(), catalog);
+    }
+    Ok(report)
+}
+
+fn validate_rows(rows: Vec<(usize, LibraryRow)>) -> (Vec<ThreadColor>, Vec<LibraryIssue>) {
+    let mut threads = Vec::with_capacity(rows.len());
+    let mut issues = Vec::new();
+    let mut first_line_by_code: HashMap<String, usize> = HashMap::new();
+
+    for (line, row) in rows {
+        let code = row.code.trim();
+        if code.is_empty() {
+            issues.push(LibraryIssue {
+                line,
+                message: "Missing thread code".to_string(),
+            });
+            continue;
+        }
+        if let Some(first_line) = first_line_by_code.get(&code.to_ascii_uppercase()) {
+            issues.push(LibraryIssue {
+                line,
+                message: format!(
+                    "Duplicate code '{}' (first defined on line {})",
+                    code, first_line
+                ),
+            });
+            continue;
+        }
+        first_line_by_code.insert(code.to_ascii_uppercase(), line);
+
+        let Some(rgb) = parse_hex(&row.hex) else {
+            issues.push(LibraryIssue {
+                line,
+                message: format!("Malformed hex '{}' for code '{}'", row.hex.trim(), code),
+            });
+            continue;
+        };
+
+        let name = match row.name.trim() {
+            "" => code,
+            name => name,
+        };
+        let mut thread = ThreadColor::new(ThreadBrand::Custom, code, name, &rgb_to_hex(rgb), rgb);
+        thread.maker = non_empty(row.brand);
+        thread.series = non_empty(row.series);
+        threads.push(thread);
+    }
+
+    (threads, issues)
+}
+
+fn non_empty(value: Option<String>) -> Option<String> {
+    value
+        .map(|v| v.trim().to_string())
+        .filter(|v| !v.is_empty())
+}
+
+/// Parse CSV with a header row naming at least `code` and `hex` columns, in any order.
+fn parse_csv(contents: &str) -> Result<Vec<(usize, LibraryRow)>, String> {
+    let mut lines = contents
+        .lines()
+        .enumerate()
+        .map(|(idx, line)| (idx + 1, line))
+        .filter(|(_, line)| !line.trim().is_empty());
+
+    let (_, header) = lines
+        .next()
+        .ok_or_else(|| "Thread library is empty".to_string())?;
+    let columns: Vec<String> = split_csv_line(header)
+        .into_iter()
+        .map(|c| c.trim().to_ascii_lowercase())
+        .collect();
+    let column = |name: &str| columns.iter().position(|c| c == name);
+    let code_col = column("code").ok_or("CSV header is missing a 'code' column")?;
+    let hex_col = column("hex").ok_or("CSV header is missing a 'hex' column")?;
+    let name_col = column("name");
+    let brand_col = column("brand");
+    let series_col = column("series");
+
+    Ok(lines
+        .map(|(line, text)| {
+            let fields = split_csv_line(text);
+            let field = |col: Option<usize>| col.and_then(|c| fields.get(c)).cloned();
+            let row = LibraryRow {
+                code: field(Some(code_col)).unwrap_or_default(),
+                name: field(name_col).unwrap_or_default(),
+                hex: field(Some(hex_col)).unwrap_or_default(),
+                brand: field(brand_col),
+                series: field(series_col),
+            };
+            (line, row)
+        })
+        .collect())
+}
+
+/// Split one CSV line, honouring double-quoted fields and `""` escapes.
+fn split_csv_line(line: &str) -> Vec<String> {
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut in_quotes = false;
+    let mut chars = line.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if in_quotes && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            '"' => in_quotes = !in_quotes,
+            ',' if !in_quotes => fields.push(std::mem::take(&mut field)),
+            _ => field.push(c),
+        }
+    }
+    fields.push(field);
+    fields
+}
+
+/// Parse a JSON array of thread objects, attributing each object to the line it starts on.
+fn parse_json(contents: &str) -> Result<Vec<(usize, LibraryRow)>, String> {
+    let rows: Vec<LibraryRow> = serde_json::from_str(contents)
+        .map_err(|e| format!("Invalid thread library JSON: {}", e))?;
+    let lines = json_object_lines(contents);
+    Ok(rows
+        .into_iter()
+        .enumerate()
+        .map(|(idx, row)| (lines.get(idx).copied().unwrap_or(1), row))
+        .collect())
+}
+
+/// Line numbers of each object opened directly inside the top-level array.
+fn json_object_lines(contents: &str) -> Vec<usize> {
+    let mut lines = Vec::new();
+    let mut line = 1;
+    let mut depth = 0usize;
+    let mut in_string = false;
+    let mut escaped = false;
+
+    for c in contents.chars() {
+        if c == '\n' {
+            line += 1;
+        }
+        if in_string {
+            match c {
+                _ if escaped => escaped = false,
+                '\\' => escaped = true,
+                '"' => in_string = false,
+                _ => {}
+            }
+            continue;
+        }
+        match c {
+            '"' => in_string = true,
+            '[' | '{' => {
+                if c == '{' && depth == 1 {
+                    lines.push(line);
+                }
+                depth += 1;
+            }
+            ']' | '}' => depth = depth.saturating_sub(1),
+            _ => {}
+        }
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color_metric::ColorMetric;
+    use crate::embroidery::rgb_to_lab;
+
+    #[test]
+    fn csv_import_registers_a_matchable_library() {
+        let csv = "code,name,hex,brand,series\n\
+                   HD-1,\"Rust, Deep\",#8B3A1A,Hand Dyed Co,Autumn\n\
+                   HD-2,Moss,4a6b2f,,\n";
+        let report = import_library("test-csv", csv, LibraryFormat::Csv).expect("import");
+        assert!(report.issues.is_empty(), "{:?}", report.issues);
+        assert_eq!(report.thread_count, 2);
+
+        let catalog = ThreadCatalog::resolve(ThreadBrand::Dmc, Some("test-csv")).expect("cached");
+        assert_eq!(catalog.brand, ThreadBrand::Custom);
+        let rust = catalog.find_closest(rgb_to_lab([0x8B, 0x3A, 0x1A]), ColorMetric::Cie76);
+        assert_eq!(rust.code, "HD-1");
+        assert_eq!(rust.name, "Rust, Deep");
+        assert_eq!(rust.maker.as_deref(), Some("Hand Dyed Co"));
+        assert_eq!(catalog.threads()[1].hex, "#4A6B2F");
+        assert_eq!(catalog.threads()[1].maker, None);
+    }
+
+    #[test]
+    fn csv_issues_carry_line_numbers_and_block_registration() {
+        let csv = "code,hex\n\
+                   A1,#112233\n\
+                   \n\
+                   A2,#12345\n\
+                   a1,#445566\n";
+        let report = import_library("test-bad-csv", csv, LibraryFormat::Csv).expect("import");
+        assert_eq!(report.thread_count, 1);
+        assert_eq!(
+            report.issues,
+            vec![
+                LibraryIssue {
+                    line: 4,
+                    message: "Malformed hex '#12345' for code 'A2'".to_string(),
+                },
+                LibraryIssue {
+                    line: 5,
+                    message: "Duplicate code 'a1' (first defined on line 2)".to_string(),
+                },
+            ]
+        );
+        assert!(ThreadCatalog::resolve(ThreadBrand::Custom, Some("test-bad-csv")).is_err());
+        assert!(ThreadCatalog::resolve(ThreadBrand::Custom, None).is_err());
+    }
+
+    #[test]
+    fn json_issues_point_at_the_offending_object() {
+        let json = r##"[
+  { "code": "J1", "name": "Sky {light}", "hex": "#87CEEB" },
+  {
+    "code": "J2",
+    "hex": "#ZZ0000"
+  }
+]"##;
+        let report = import_library("test-json", json, LibraryFormat::Json).expect("import");
+        assert_eq!(report.issues.len(), 1);
+        assert_eq!(report.issues[0].line, 3);
+
+        let broken = import_library("test-json", "[\n{ \"code\": 1 }\n]", LibraryFormat::Json);
+        assert!(broken.unwrap_err().contains("line 2"));
+    }
+}
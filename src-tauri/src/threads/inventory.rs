@@ -0,0 +1,158 @@
+//! Owned-thread (stash) constraints for palette matching.
+//!
+//! When the caller supplies the codes they own, quantized colors are matched against that
+//! subset only. An optional purchase budget lets the handful of colors the stash covers worst
+//! fall back to the full catalog, ranked by how much stitched area each purchase improves.
+
+use super::{ThreadCatalog, ThreadColor};
+use crate::color_metric::ColorMetric;
+use palette::{white_point::D65, Lab};
+use std::collections::HashMap;
+
+/// Threads chosen for each palette entry, plus any codes the user would need to buy
+pub struct InventoryMatch {
+    pub threads: Vec<ThreadColor>,
+    pub purchases: Vec<String>,
+}
+
+/// Match `targets` to threads, restricted to `owned` codes when that list is non-empty.
+///
+/// `weights` holds the pixel count of each target and ranks purchase candidates:
+/// a non-owned thread is suggested only if it is among the `max_purchases` codes with the
+/// largest total `weight * Delta-E` improvement over the best owned thread.
+pub fn match_inventory(
+    catalog: &ThreadCatalog,
+    targets: &[Lab<D65, f32>],
+    weights: &[u64],
+    owned: &[String],
+    max_purchases: Option<u32>,
+    metric: ColorMetric,
+) -> Result<InventoryMatch, String> {
+    if owned.iter().all(|code| code.trim().is_empty()) {
+        return Ok(InventoryMatch {
+            threads: targets
+                .iter()
+                .map(|lab| catalog.find_closest(*lab, metric).clone())
+                .collect(),
+            purchases: Vec::new(),
+        });
+    }
+
+    let closest: Vec<(&ThreadColor, f32)> = targets
+        .iter()
+        .map(|lab| catalog.find_closest_with_distance(*lab, metric))
+        .collect();
+
+    let stash = catalog.retain_codes(owned).ok_or_else(|| {
+        format!(
+            "None of the owned threads are in the {} catalog",
+            catalog.brand.label()
+        )
+    })?;
+    let from_stash: Vec<(&ThreadColor, f32)> = targets
+        .iter()
+        .map(|lab| stash.find_closest_with_distance(*lab, metric))
+        .collect();
+
+    // Total weighted improvement each non-owned thread would bring.
+    let mut gain_by_code: HashMap<&str, f64> = HashMap::new();
+    for (idx, ((best, best_de), (_, stash_de))) in closest.iter().zip(&from_stash).enumerate() {
+        if stash.contains(&best.code) {
+            continue;
+        }
+        let weight = weights.get(idx).copied().unwrap_or(1) as f64;
+        *gain_by_code.entry(best.code.as_str()).or_insert(0.0) +=
+            (stash_de - best_de).max(0.0) as f64 * weight;
+    }
+    let mut candidates: Vec<(&str, f64)> = gain_by_code
+        .into_iter()
+        .filter(|(_, gain)| *gain > 0.0)
+        .collect();
+    candidates.sort_by(|a, b| {
+        b.1.partial_cmp(&a.1)
+            .unwrap_or(std::cmp::Ordering::Equal)
+            .then_with(|| a.0.cmp(b.0))
+    });
+    candidates.truncate(max_purchases.unwrap_or(0) as usize);
+    let bought: Vec<&str> = candidates.into_iter().map(|(code, _)| code).collect();
+
+    // A bought blend only needs its components that are not already owned.
+    let mut purchases: Vec<String> = Vec::new();
+    for code in &bought {
+        let Some(thread) = catalog.find_code(code) else {
+            continue;
+        };
+        for component in thread.component_codes() {
+            if !stash.contains(component) && !purchases.iter().any(|p| p == component) {
+                purchases.push(component.to_string());
+            }
+        }
+    }
+
+    let threads = closest
+        .iter()
+        .zip(&from_stash)
+        .map(|((best, _), (owned_best, _))| {
+            if bought.contains(&best.code.as_str()) {
+                (*best).clone()
+            } else {
+                (*owned_best).clone()
+            }
+        })
+        .collect();
+
+    Ok(InventoryMatch { threads, purchases })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::embroidery::rgb_to_lab;
+    use crate::threads::ThreadBrand;
+
+    fn owned(codes: &[&str]) -> Vec<String> {
+        codes.iter().map(|c| c.to_string()).collect()
+    }
+
+    #[test]
+    fn stash_limits_matches_and_budget_buys_the_worst_gap() {
+        let dmc = ThreadCatalog::for_brand(ThreadBrand::Dmc).expect("DMC is bundled");
+        let targets = [rgb_to_lab([0, 0, 0]), rgb_to_lab([206, 25, 56])];
+        let stash = owned(&["310", "white"]);
+
+        let strict = match_inventory(
+            &dmc,
+            &targets,
+            &[10, 10],
+            &stash,
+            None,
+            ColorMetric::Ciede2000,
+        )
+        .expect("match");
+        assert_eq!(strict.threads[0].code, "310");
+        assert!(["310", "White"].contains(&strict.threads[1].code.as_str()));
+        assert!(strict.purchases.is_empty());
+
+        let budget = match_inventory(
+            &dmc,
+            &targets,
+            &[10, 10],
+            &stash,
+            Some(1),
+            ColorMetric::Ciede2000,
+        )
+        .expect("match");
+        assert_eq!(budget.purchases, vec!["321".to_string()]);
+        assert_eq!(budget.threads[1].code, "321");
+
+        assert!(match_inventory(
+            &dmc,
+            &targets,
+            &[1, 1],
+            &owned(&["nope"]),
+            None,
+            ColorMetric::Ciede2000
+        )
+        .is_err());
+    }
+}
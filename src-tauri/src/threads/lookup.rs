@@ -0,0 +1,224 @@
+//! Nearest-thread search with a lightness index.
+//!
+//! Every supported metric is bounded below by a scaled lightness difference, so threads
+//! are kept sorted by lightness and a query walks outward from its own lightness, stopping
+//! once the bound alone exceeds the best distance found. That gives exactly the brute-force
+//! answer, ties going to the lower catalog index, while usually computing Delta-E for a
+//! small fraction of the catalog.
+//!
+//! The bounds, with `dL` the lightness difference:
+//! - CIE76 and CIE94 include `dL` unweighted, so the distance is at least `|dL|`.
+//! - CIEDE2000 divides `dL` by `S_L`, which grows with the distance of the mean lightness
+//!   from 50; the cross term `R_T` has magnitude at most 2, so the chroma and hue terms
+//!   together never go negative.
+//! - CMC 2:1 divides `dL` by `2 * S_L` with `S_L` set by the reference lightness.
+//! - OKLab is indexed on its own lightness, scaled by 100 like the distance.
+//!
+//! The tests check the index against the full scan. Timing it is left to
+//! `bench_index_against_scan`, which like the stage4 fixture harness is `#[ignore]`d and
+//! only runs on request, in release mode so the numbers mean something:
+//!
+//! ```text
+//! cargo test --release bench_index_against_scan -- --ignored --nocapture
+//! ```
+
+use crate::color_metric::ColorMetric;
+use palette::{white_point::D65, FromColor, Lab, Oklab};
+
+/// Relative slack on the bound, so float rounding in the metric never prunes a true match
+const BOUND_SLACK: f32 = 1e-3;
+
+/// Catalog positions sorted by lightness, one ordering for LAB and one for OKLab
+pub(super) struct LightnessIndex {
+    lab: Vec<(f32, usize)>,
+    oklab: Vec<(f32, usize)>,
+    /// Largest distance of any thread's LAB lightness from 50
+    max_l_offset: f32,
+}
+
+impl LightnessIndex {
+    pub(super) fn new(labs: &[Lab<D65, f32>]) -> Self {
+        let sorted = |key: &dyn Fn(Lab<D65, f32>) -> f32| {
+            let mut keys: Vec<(f32, usize)> = labs
+                .iter()
+                .enumerate()
+                .map(|(i, lab)| (key(*lab), i))
+                .collect();
+            keys.sort_by(|a, b| a.0.total_cmp(&b.0).then(a.1.cmp(&b.1)));
+            keys
+        };
+        Self {
+            lab: sorted(&|lab| lab.l),
+            oklab: sorted(&|lab| 100.0 * Oklab::from_color(lab).l),
+            max_l_offset: labs
+                .iter()
+                .map(|lab| (lab.l - 50.0).abs())
+                .fold(0.0, f32::max),
+        }
+    }
+
+    /// Index into `labs` of the closest entry to `target`, with its distance.
+    pub(super) fn nearest(
+        &self,
+        labs: &[Lab<D65, f32>],
+        target: Lab<D65, f32>,
+        metric: ColorMetric,
+    ) -> Option<(usize, f32)> {
+        let (keys, key, scale) = match metric {
+            ColorMetric::Oklab => (&self.oklab, 100.0 * Oklab::from_color(target).l, 1.0),
+            ColorMetric::Cie76 | ColorMetric::Cie94 => (&self.lab, target.l, 1.0),
+            ColorMetric::Ciede2000 => {
+                let offset = (target.l - 50.0).abs().max(self.max_l_offset);
+                let s_l = 1.0 + 0.015 * offset * offset / (20.0 + offset * offset).sqrt();
+                (&self.lab, target.l, 1.0 / s_l)
+            }
+            ColorMetric::Cmc => {
+                let s_l = if target.l < 16.0 {
+                    0.511
+                } else {
+                    0.040975 * target.l / (1.0 + 0.01765 * target.l)
+                };
+                (&self.lab, target.l, 1.0 / (2.0 * s_l))
+            }
+        };
+        if !key.is_finite() {
+            return None;
+        }
+        let scale = scale * (1.0 - BOUND_SLACK);
+
+        // Walk outward from the insertion point, always taking the side closer in lightness.
+        let start = keys.partition_point(|(k, _)| *k < key);
+        let (mut below, mut above) = (start, start);
+        let mut best: Option<(usize, f32)> = None;
+        loop {
+            let down = below.checked_sub(1).map(|i| (i, key - keys[i].0));
+            let up = keys.get(above).map(|entry| (above, entry.0 - key));
+            let (pos, gap) = match (down, up) {
+                (Some(d), Some(u)) => {
+                    if d.1 <= u.1 {
+                        d
+                    } else {
+                        u
+                    }
+                }
+                (Some(d), None) => d,
+                (None, Some(u)) => u,
+                (None, None) => break,
+            };
+            if let Some((_, best_distance)) = best {
+                if gap * scale > best_distance {
+                    break;
+                }
+            }
+            if pos < below {
+                below = pos;
+            } else {
+                above = pos + 1;
+            }
+
+            let idx = keys[pos].1;
+            let distance = metric.distance(target, labs[idx]);
+            best = match best {
+                Some((best_idx, best_distance))
+                    if distance > best_distance
+                        || (distance == best_distance && idx > best_idx)
+                        || distance.is_nan() =>
+                {
+                    Some((best_idx, best_distance))
+                }
+                _ => Some((idx, distance)),
+            };
+        }
+        best
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::color_metric::ColorMetric;
+    use crate::embroidery::rgb_to_lab;
+    use crate::threads::{ThreadBrand, ThreadCatalog};
+    use palette::{white_point::D65, Lab};
+    use std::time::Instant;
+
+    const METRICS: [ColorMetric; 5] = [
+        ColorMetric::Cie76,
+        ColorMetric::Cie94,
+        ColorMetric::Cmc,
+        ColorMetric::Ciede2000,
+        ColorMetric::Oklab,
+    ];
+
+    /// Deterministic spread of RGB colors, corners of the cube included.
+    fn sample_colors(count: u32) -> Vec<Lab<D65, f32>> {
+        let mut state = 0x9E37_79B9u32;
+        let mut colors: Vec<Lab<D65, f32>> = [[0, 0, 0], [255, 255, 255], [255, 0, 0], [0, 0, 255]]
+            .into_iter()
+            .map(rgb_to_lab)
+            .collect();
+        for _ in 0..count {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            let [r, g, b, _] = state.to_le_bytes();
+            colors.push(rgb_to_lab([r, g, b]));
+        }
+        colors
+    }
+
+    #[test]
+    fn index_matches_the_brute_force_scan() {
+        let colors = sample_colors(500);
+        for brand in [ThreadBrand::Dmc, ThreadBrand::Anchor] {
+            let catalog = ThreadCatalog::for_brand(brand).expect("bundled brand");
+            let blends = catalog.with_blends(8.0);
+            for catalog in [catalog.as_ref(), &blends] {
+                for metric in METRICS {
+                    for &color in &colors {
+                        let (fast, fast_distance) =
+                            catalog.find_closest_with_distance(color, metric);
+                        let (scan, scan_distance) = catalog.find_closest_scan(color, metric);
+                        assert_eq!(
+                            fast.code, scan.code,
+                            "{:?} {:?} at {:?}",
+                            brand, metric, color
+                        );
+                        assert_eq!(fast_distance, scan_distance);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    #[ignore = "Timing run; see the module docs"]
+    fn bench_index_against_scan() {
+        let colors = sample_colors(20_000);
+        let catalog = ThreadCatalog::for_brand(ThreadBrand::Dmc).expect("DMC is bundled");
+        for metric in METRICS {
+            let start = Instant::now();
+            let scan: Vec<&str> = colors
+                .iter()
+                .map(|&c| catalog.find_closest_scan(c, metric).0.code.as_str())
+                .collect();
+            let scan_time = start.elapsed();
+
+            let start = Instant::now();
+            let fast: Vec<&str> = colors
+                .iter()
+                .map(|&c| catalog.find_closest(c, metric).code.as_str())
+                .collect();
+            let fast_time = start.elapsed();
+
+            assert_eq!(scan, fast);
+            eprintln!(
+                "{:?}: {} lookups, scan {:?}, index {:?} ({:.1}x)",
+                metric,
+                colors.len(),
+                scan_time,
+                fast_time,
+                scan_time.as_secs_f64() / fast_time.as_secs_f64().max(1e-9)
+            );
+        }
+    }
+}
@@ -0,0 +1,384 @@
+//! Thread catalogs for the supported floss brands.
+//!
+//! Every bundled brand is backed by a const table in [`tables`]. Catalogs are built lazily
+//! with precomputed LAB values and a lightness index and cached for the lifetime of the
+//! process, so nearest-thread lookups never re-parse hex strings or scan the whole
+//! catalog. User libraries imported through [`custom`] share the same [`ThreadCatalog`]
+//! type.
+
+mod blend;
+mod convert;
+mod custom;
+mod inventory;
+mod lookup;
+mod tables;
+
+pub use blend::DEFAULT_MAX_BLEND_DELTA_E;
+pub use convert::{convert_legend, convert_pattern, PatternConversion, ThreadSubstitution};
+pub use custom::{import_library, LibraryFormat, LibraryImportReport, LibraryIssue};
+pub use inventory::{match_inventory, InventoryMatch};
+
+use crate::color_metric::ColorMetric;
+use crate::embroidery::{hex_to_rgb, rgb_to_lab};
+use lookup::LightnessIndex;
+use palette::{white_point::D65, Lab};
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, OnceLock};
+
+/// Floss brands known to the pattern pipeline
+#[derive(Debug, Copy, Clone, Default, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum ThreadBrand {
+    #[default]
+    #[serde(alias = "DMC")]
+    Dmc,
+    #[serde(alias = "DMC Satin")]
+    DmcSatin,
+    #[serde(alias = "Anchor")]
+    Anchor,
+    #[serde(alias = "Madeira")]
+    Madeira,
+    #[serde(alias = "Cosmo")]
+    Cosmo,
+    /// A user-imported library; has no bundled table.
+    #[serde(alias = "Custom")]
+    Custom,
+}
+
+impl ThreadBrand {
+    /// Brands that ship with a bundled catalog.
+    pub const BUNDLED: [ThreadBrand; 5] = [
+        ThreadBrand::Dmc,
+        ThreadBrand::DmcSatin,
+        ThreadBrand::Anchor,
+        ThreadBrand::Madeira,
+        ThreadBrand::Cosmo,
+    ];
+
+    /// Human-readable brand name, as stored in project settings.
+    pub fn label(self) -> &'static str {
+        match self {
+            ThreadBrand::Dmc => "DMC",
+            ThreadBrand::DmcSatin => "DMC Satin",
+            ThreadBrand::Anchor => "Anchor",
+            ThreadBrand::Madeira => "Madeira",
+            ThreadBrand::Cosmo => "Cosmo",
+            ThreadBrand::Custom => "Custom",
+        }
+    }
+
+    /// Parse a brand label such as `"DMC"` or `"dmc_satin"`, ignoring case and separators.
+    pub fn from_label(label: &str) -> Option<Self> {
+        let normalized: String = label
+            .chars()
+            .filter(|c| c.is_ascii_alphanumeric())
+            .collect::<String>()
+            .to_ascii_lowercase();
+        Self::BUNDLED
+            .into_iter()
+            .chain([ThreadBrand::Custom])
+            .find(|brand| {
+                brand
+                    .label()
+                    .chars()
+                    .filter(|c| c.is_ascii_alphanumeric())
+                    .collect::<String>()
+                    .eq_ignore_ascii_case(&normalized)
+            })
+    }
+
+    /// Whether the brand's colors are its own rather than taken from DMC cross-references.
+    pub fn has_colorimetry(self) -> bool {
+        !matches!(self.table(), Some(BrandTable::CrossReference(_)))
+    }
+
+    fn table(self) -> Option<BrandTable> {
+        match self {
+            ThreadBrand::Dmc => Some(BrandTable::Swatches(tables::DMC_PALETTE)),
+            ThreadBrand::DmcSatin => Some(BrandTable::Swatches(tables::DMC_SATIN_PALETTE)),
+            ThreadBrand::Anchor => Some(BrandTable::CrossReference(tables::ANCHOR_PALETTE)),
+            ThreadBrand::Madeira => Some(BrandTable::CrossReference(tables::MADEIRA_PALETTE)),
+            ThreadBrand::Cosmo => Some(BrandTable::CrossReference(tables::COSMO_PALETTE)),
+            ThreadBrand::Custom => None,
+        }
+    }
+}
+
+/// Rows of a bundled brand table
+enum BrandTable {
+    /// (code, name, hex) swatches
+    Swatches(&'static [(&'static str, &'static str, &'static str)]),
+    /// (code, DMC code) chart equivalents, colored with the DMC swatch
+    CrossReference(&'static [(&'static str, &'static str)]),
+}
+
+/// Thread color entry with precomputed LAB values
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ThreadColor {
+    pub brand: ThreadBrand,
+    pub code: String,
+    pub name: String,
+    pub hex: String,
+    pub rgb: [u8; 3],
+    pub lab: [f32; 3],
+    /// Maker named by a custom library row (e.g. a hand-dyer)
+    #[serde(default)]
+    pub maker: Option<String>,
+    /// Product line named by a custom library row
+    #[serde(default)]
+    pub series: Option<String>,
+    /// Component codes when this is a blend of two threads, one strand each
+    #[serde(default)]
+    pub blend: Option<[String; 2]>,
+    /// DMC code whose swatch stands in for this thread's color when the brand has no
+    /// colorimetry of its own
+    #[serde(default)]
+    pub derived_from: Option<String>,
+}
+
+impl ThreadColor {
+    fn new(brand: ThreadBrand, code: &str, name: &str, hex: &str, rgb: [u8; 3]) -> Self {
+        let lab = rgb_to_lab(rgb);
+        Self {
+            brand,
+            code: code.to_string(),
+            name: name.to_string(),
+            hex: hex.to_string(),
+            rgb,
+            lab: [lab.l, lab.a, lab.b],
+            maker: None,
+            series: None,
+            blend: None,
+            derived_from: None,
+        }
+    }
+
+    /// Real thread codes this entry is stitched with: its blend components, or itself.
+    pub fn component_codes(&self) -> Vec<&str> {
+        match &self.blend {
+            Some([a, b]) => vec![a.as_str(), b.as_str()],
+            None => vec![self.code.as_str()],
+        }
+    }
+}
+
+/// Brand catalog with precomputed LAB values; never empty
+pub struct ThreadCatalog {
+    pub brand: ThreadBrand,
+    threads: Vec<ThreadColor>,
+    labs: Vec<Lab<D65, f32>>,
+    index: LightnessIndex,
+}
+
+static DMC_CATALOG: OnceLock<Arc<ThreadCatalog>> = OnceLock::new();
+static DMC_SATIN_CATALOG: OnceLock<Arc<ThreadCatalog>> = OnceLock::new();
+static ANCHOR_CATALOG: OnceLock<Arc<ThreadCatalog>> = OnceLock::new();
+static MADEIRA_CATALOG: OnceLock<Arc<ThreadCatalog>> = OnceLock::new();
+static COSMO_CATALOG: OnceLock<Arc<ThreadCatalog>> = OnceLock::new();
+
+impl ThreadCatalog {
+    /// Bundled catalog for `brand`; `None` for [`ThreadBrand::Custom`].
+    pub fn for_brand(brand: ThreadBrand) -> Option<Arc<Self>> {
+        let cell = match brand {
+            ThreadBrand::Dmc => &DMC_CATALOG,
+            ThreadBrand::DmcSatin => &DMC_SATIN_CATALOG,
+            ThreadBrand::Anchor => &ANCHOR_CATALOG,
+            ThreadBrand::Madeira => &MADEIRA_CATALOG,
+            ThreadBrand::Cosmo => &COSMO_CATALOG,
+            ThreadBrand::Custom => return None,
+        };
+        let table = brand.table()?;
+        Some(
+            cell.get_or_init(|| {
+                let threads = match table {
+                    BrandTable::Swatches(rows) => rows
+                        .iter()
+                        .map(|(code, name, hex)| {
+                            ThreadColor::new(brand, code, name, hex, hex_to_rgb(hex))
+                        })
+                        .collect(),
+                    BrandTable::CrossReference(rows) => rows
+                        .iter()
+                        .filter_map(|(code, dmc_code)| {
+                            let (_, _, hex) =
+                                tables::DMC_PALETTE.iter().find(|(c, _, _)| c == dmc_code)?;
+                            let name = format!("{} {}", brand.label(), code);
+                            let mut thread =
+                                ThreadColor::new(brand, code, &name, hex, hex_to_rgb(hex));
+                            thread.derived_from = Some(dmc_code.to_string());
+                            Some(thread)
+                        })
+                        .collect(),
+                };
+                Arc::new(Self::from_threads(brand, threads))
+            })
+            .clone(),
+        )
+    }
+
+    /// Resolve the catalog a request should match against.
+    ///
+    /// A `library_id` always wins over `brand`; [`ThreadBrand::Custom`] without one is an error.
+    pub fn resolve(brand: ThreadBrand, library_id: Option<&str>) -> Result<Arc<Self>, String> {
+        if let Some(id) = library_id.map(str::trim).filter(|id| !id.is_empty()) {
+            return custom::library(id)
+                .ok_or_else(|| format!("Thread library '{}' has not been imported", id));
+        }
+        Self::for_brand(brand).ok_or_else(|| {
+            "A custom thread library id is required for the Custom brand".to_string()
+        })
+    }
+
+    fn from_threads(brand: ThreadBrand, threads: Vec<ThreadColor>) -> Self {
+        let labs: Vec<Lab<D65, f32>> = threads
+            .iter()
+            .map(|t| Lab::new(t.lab[0], t.lab[1], t.lab[2]))
+            .collect();
+
+        Self {
+            brand,
+            threads,
+            index: LightnessIndex::new(&labs),
+            labs,
+        }
+    }
+
+    pub fn threads(&self) -> &[ThreadColor] {
+        &self.threads
+    }
+
+    /// Whether `code` is in this catalog, ignoring case and surrounding whitespace.
+    pub fn contains(&self, code: &str) -> bool {
+        self.find_code(code).is_some()
+    }
+
+    /// Look up a thread by code, ignoring case and surrounding whitespace.
+    pub fn find_code(&self, code: &str) -> Option<&ThreadColor> {
+        self.threads
+            .iter()
+            .find(|t| t.code.eq_ignore_ascii_case(code.trim()))
+    }
+
+    /// Sub-catalog holding only threads whose components all appear in `codes`; `None` if empty.
+    pub fn retain_codes(&self, codes: &[String]) -> Option<Self> {
+        self.filter_codes(codes, true)
+    }
+
+    /// Sub-catalog without threads that use any code in `codes`; `None` if empty.
+    pub fn without_codes(&self, codes: &[String]) -> Option<Self> {
+        self.filter_codes(codes, false)
+    }
+
+    fn filter_codes(&self, codes: &[String], keep_listed: bool) -> Option<Self> {
+        let listed = |code: &str| codes.iter().any(|c| code.eq_ignore_ascii_case(c.trim()));
+        let threads: Vec<ThreadColor> = self
+            .threads
+            .iter()
+            .filter(|t| {
+                t.component_codes()
+                    .into_iter()
+                    .all(|code| listed(code) == keep_listed)
+            })
+            .cloned()
+            .collect();
+        (!threads.is_empty()).then(|| Self::from_threads(self.brand, threads))
+    }
+
+    /// Find the closest thread under `metric`
+    pub fn find_closest(&self, target: Lab<D65, f32>, metric: ColorMetric) -> &ThreadColor {
+        self.find_closest_with_distance(target, metric).0
+    }
+
+    /// Like [`Self::find_closest`], also returning the distance to the match.
+    ///
+    /// Searches the lightness index; the result is identical to a scan of every thread.
+    pub fn find_closest_with_distance(
+        &self,
+        target: Lab<D65, f32>,
+        metric: ColorMetric,
+    ) -> (&ThreadColor, f32) {
+        match self.index.nearest(&self.labs, target, metric) {
+            Some((idx, delta_e)) => (&self.threads[idx], delta_e),
+            None => self.find_closest_scan(target, metric),
+        }
+    }
+
+    /// Closest thread by computing the distance to every thread (parallelized).
+    fn find_closest_scan(&self, target: Lab<D65, f32>, metric: ColorMetric) -> (&ThreadColor, f32) {
+        let (idx, delta_e) = self
+            .labs
+            .par_iter()
+            .enumerate()
+            .map(|(i, lab)| {
+                let delta_e = metric.distance(target, *lab);
+                (i, delta_e)
+            })
+            .min_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(std::cmp::Ordering::Equal))
+            .unwrap_or((0, f32::MAX));
+
+        (&self.threads[idx], delta_e)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_brand_has_a_catalog_with_unique_codes() {
+        for brand in ThreadBrand::BUNDLED {
+            let catalog = ThreadCatalog::for_brand(brand).expect("bundled brand");
+            assert!(!catalog.threads().is_empty(), "{:?} is empty", brand);
+
+            let mut codes: Vec<&str> = catalog.threads().iter().map(|t| t.code.as_str()).collect();
+            codes.sort_unstable();
+            let total = codes.len();
+            codes.dedup();
+            assert_eq!(codes.len(), total, "{:?} has duplicate codes", brand);
+
+            // Cross-referenced brands resolve every row and never borrow DMC names
+            let derived = catalog.threads().iter().all(|t| t.derived_from.is_some());
+            assert_eq!(derived, !brand.has_colorimetry(), "{:?}", brand);
+            if let Some(BrandTable::CrossReference(rows)) = brand.table() {
+                assert_eq!(total, rows.len(), "{:?} has unknown DMC codes", brand);
+                assert!(catalog
+                    .threads()
+                    .iter()
+                    .all(|t| t.name.starts_with(brand.label())));
+            }
+        }
+    }
+
+    #[test]
+    fn black_matches_each_brand_black() {
+        let black = rgb_to_lab([0, 0, 0]);
+        let expected = [
+            (ThreadBrand::Dmc, "310"),
+            (ThreadBrand::DmcSatin, "S310"),
+            (ThreadBrand::Anchor, "403"),
+            (ThreadBrand::Madeira, "2400"),
+            (ThreadBrand::Cosmo, "600"),
+        ];
+        for (brand, code) in expected {
+            let catalog = ThreadCatalog::for_brand(brand).expect("bundled brand");
+            let thread = catalog.find_closest(black, ColorMetric::Ciede2000);
+            assert_eq!(thread.code, code);
+            assert_eq!(thread.brand, brand);
+        }
+    }
+
+    #[test]
+    fn brand_labels_round_trip() {
+        for brand in ThreadBrand::BUNDLED {
+            assert_eq!(ThreadBrand::from_label(brand.label()), Some(brand));
+        }
+        assert_eq!(
+            ThreadBrand::from_label("dmc_satin"),
+            Some(ThreadBrand::DmcSatin)
+        );
+        assert_eq!(ThreadBrand::from_label("Custom"), Some(ThreadBrand::Custom));
+        assert_eq!(ThreadBrand::from_label("Kreinik"), None);
+        assert!(ThreadCatalog::for_brand(ThreadBrand::Custom).is_none());
+    }
+}
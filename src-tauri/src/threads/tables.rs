@@ -0,0 +1,714 @@
+//! Bundled thread color tables.
+//!
+//! Anchor, Madeira and Cosmo have no freely published colorimetry, so their tables are
+//! cross-references to the DMC shade each code replaces on the manufacturer charts rather
+//! than measured swatches.
+
+/// Complete DMC thread palette (~500 official colors)
+/// Each entry: (code, name, hex)
+pub(super) const DMC_PALETTE: &[(&str, &str, &str)] = &[
+    // Whites & Neutrals
+    ("B5200", "Snow White", "#FFFFFF"),
+    ("White", "White", "#FEFEFE"),
+    ("Ecru", "Ecru", "#F0EBD5"),
+    ("822", "Light Beige Gray", "#E7DECC"),
+    ("644", "Medium Beige Gray", "#D9D3C3"),
+    ("642", "Dark Beige Gray", "#C2B9A6"),
+    ("640", "Very Dark Beige Gray", "#9B8F7E"),
+    ("3072", "Very Light Beaver Gray", "#E1E5DE"),
+    ("648", "Light Beaver Gray", "#BCC3BB"),
+    ("647", "Medium Beaver Gray", "#A9B0A8"),
+    ("646", "Dark Beaver Gray", "#8D9691"),
+    ("645", "Very Dark Beaver Gray", "#6C7670"),
+    // Blacks & Grays
+    ("310", "Black", "#000000"),
+    ("3799", "Very Dark Pewter Gray", "#5B5F5F"),
+    ("413", "Dark Pewter Gray", "#656666"),
+    ("3787", "Dark Brown Gray", "#6B675E"),
+    ("762", "Very Light Pearl Gray", "#E6E6E6"),
+    ("415", "Pearl Gray", "#D3D3D3"),
+    ("318", "Light Steel Gray", "#ADB0AE"),
+    ("414", "Dark Steel Gray", "#8A8A8A"),
+    ("317", "Pewter Gray", "#6B6D6D"),
+    ("535", "Very Light Ash Gray", "#696959"),
+    ("3024", "Very Light Brown Gray", "#D0CCBE"),
+    ("3023", "Light Brown Gray", "#B5A588"),
+    // Reds
+    ("666", "Bright Red", "#EC2130"),
+    ("321", "Red", "#CE1938"),
+    ("304", "Medium Red", "#B11731"),
+    ("498", "Dark Red", "#A81428"),
+    ("816", "Garnet", "#91182E"),
+    ("815", "Medium Garnet", "#7C1D2B"),
+    ("814", "Dark Garnet", "#6D1329"),
+    ("760", "Salmon", "#F5BEC2"),
+    ("3712", "Medium Salmon", "#EA9CA3"),
+    ("3328", "Dark Salmon", "#E07681"),
+    ("347", "Very Dark Salmon", "#BF1733"),
+    ("353", "Peach", "#FECDCD"),
+    ("352", "Light Coral", "#FBB9AA"),
+    ("351", "Coral", "#EA8579"),
+    ("350", "Medium Coral", "#E34948"),
+    ("349", "Dark Coral", "#C81732"),
+    ("817", "Very Dark Coral Red", "#BA1730"),
+    // Pinks
+    ("818", "Baby Pink", "#FFD9DB"),
+    ("963", "Ultra Very Light Dusty Rose", "#FFCCD1"),
+    ("3716", "Very Light Dusty Rose", "#FFBAC7"),
+    ("962", "Medium Dusty Rose", "#E97D8B"),
+    ("961", "Dark Dusty Rose", "#CE486E"),
+    ("3833", "Light Raspberry", "#E95077"),
+    ("3832", "Medium Raspberry", "#D13D6F"),
+    ("3831", "Dark Raspberry", "#B0194B"),
+    ("3350", "Ultra Dark Dusty Rose", "#B52D5C"),
+    ("150", "Ultra Very Light Dusty Rose", "#F8D5D8"),
+    ("151", "Very Light Dusty Rose", "#EFB1BA"),
+    ("152", "Medium Light Shell Pink", "#DD88A0"),
+    ("3354", "Light Dusty Rose", "#D887A6"),
+    ("3733", "Dusty Rose", "#CD5E8D"),
+    ("3731", "Very Dark Dusty Rose", "#C0476C"),
+    // Oranges
+    ("3824", "Light Apricot", "#FECABE"),
+    ("3341", "Apricot", "#FFAB8A"),
+    ("3340", "Medium Apricot", "#FF8262"),
+    ("608", "Bright Orange", "#FF6F30"),
+    ("606", "Bright Orange-Red", "#FA3F1B"),
+    ("970", "Light Pumpkin", "#FF901F"),
+    ("971", "Pumpkin", "#FF8600"),
+    ("972", "Deep Canary", "#FFB900"),
+    ("3853", "Dark Autumn Gold", "#F59B5A"),
+    ("3854", "Medium Autumn Gold", "#F68A5C"),
+    ("3855", "Light Autumn Gold", "#FBBF99"),
+    ("722", "Light Orange Spice", "#F6A667"),
+    ("720", "Dark Orange Spice", "#E94A07"),
+    ("721", "Medium Orange Spice", "#F25D3D"),
+    ("947", "Burnt Orange", "#FF5F01"),
+    // Yellows
+    ("445", "Light Lemon", "#FFFDDB"),
+    ("307", "Lemon", "#FFE600"),
+    ("973", "Bright Canary", "#FFE529"),
+    ("444", "Dark Lemon", "#FFE00B"),
+    ("3078", "Very Light Golden Yellow", "#FFF8DC"),
+    ("727", "Very Light Topaz", "#FFF785"),
+    ("726", "Light Topaz", "#FFD747"),
+    ("725", "Topaz", "#FFC723"),
+    ("3820", "Dark Straw", "#DDB900"),
+    ("783", "Medium Topaz", "#D68700"),
+    ("782", "Dark Topaz", "#CB7800"),
+    ("781", "Very Dark Topaz", "#985F00"),
+    ("780", "Ultra Very Dark Topaz", "#8C5400"),
+    ("676", "Light Old Gold", "#ECBB5C"),
+    ("729", "Medium Old Gold", "#D1A140"),
+    ("680", "Dark Old Gold", "#B98C27"),
+    ("3829", "Very Dark Old Gold", "#9F6F00"),
+    ("3822", "Light Straw", "#F0DE9C"),
+    ("3821", "Straw", "#E0C47A"),
+    // Greens
+    ("704", "Bright Chartreuse", "#CCF500"),
+    ("703", "Chartreuse", "#A6D700"),
+    ("702", "Kelly Green", "#86B500"),
+    ("701", "Light Green", "#5D9F00"),
+    ("700", "Bright Green", "#2E7D09"),
+    ("699", "Green", "#136C00"),
+    ("907", "Light Parrot Green", "#D0F200"),
+    ("906", "Medium Parrot Green", "#9DB700"),
+    ("905", "Dark Parrot Green", "#6F9800"),
+    ("904", "Very Dark Parrot Green", "#4B7800"),
+    ("164", "Light Forest Green", "#C7D9AD"),
+    ("989", "Forest Green", "#88A84C"),
+    ("988", "Medium Forest Green", "#77923C"),
+    ("987", "Dark Forest Green", "#5F7D2D"),
+    ("986", "Very Dark Forest Green", "#466B28"),
+    ("3348", "Light Yellow Green", "#D8E79E"),
+    ("3347", "Medium Yellow Green", "#A3C85E"),
+    ("3346", "Hunter Green", "#77A058"),
+    ("3345", "Dark Hunter Green", "#66834A"),
+    ("772", "Very Light Yellow Green", "#E4F3CC"),
+    ("3364", "Pine Green", "#546E4D"),
+    ("320", "Medium Pistachio Green", "#8D9E57"),
+    ("367", "Dark Pistachio Green", "#6B7B3C"),
+    ("319", "Very Dark Pistachio Green", "#40502C"),
+    // Teals & Aquas
+    ("964", "Light Seagreen", "#C1E2DC"),
+    ("959", "Medium Seagreen", "#89C9BC"),
+    ("958", "Dark Seagreen", "#52B5A3"),
+    ("3812", "Very Dark Seagreen", "#2E917F"),
+    ("3811", "Very Light Turquoise", "#C2E3DF"),
+    ("598", "Light Turquoise", "#9FCECE"),
+    ("597", "Turquoise", "#6CB5BD"),
+    ("3810", "Dark Turquoise", "#4D999A"),
+    ("3809", "Very Dark Turquoise", "#328082"),
+    ("928", "Very Light Gray Green", "#E7EDE7"),
+    ("927", "Light Gray Green", "#BFCEC4"),
+    ("926", "Medium Gray Green", "#98B3A6"),
+    ("3768", "Dark Gray Green", "#5B7B6B"),
+    // Blues
+    ("3841", "Pale Baby Blue", "#CEDEED"),
+    ("3840", "Light Baby Blue", "#A8C9E8"),
+    ("3839", "Medium Baby Blue", "#6495C8"),
+    ("3838", "Dark Baby Blue", "#3A75AE"),
+    ("800", "Pale Delft Blue", "#C9E4F2"),
+    ("809", "Delft Blue", "#94B7D5"),
+    ("799", "Medium Delft Blue", "#7393B7"),
+    ("798", "Dark Delft Blue", "#5174A0"),
+    ("797", "Royal Blue", "#13438D"),
+    ("796", "Dark Royal Blue", "#123071"),
+    ("3325", "Light Baby Blue", "#BFD8EB"),
+    ("3755", "Baby Blue", "#8DADD3"),
+    ("334", "Medium Baby Blue", "#5D8AB8"),
+    ("322", "Dark Baby Blue", "#2F5580"),
+    ("312", "Very Dark Baby Blue", "#13416D"),
+    ("311", "Medium Navy Blue", "#1C3A5C"),
+    ("336", "Navy Blue", "#13294B"),
+    ("823", "Dark Navy Blue", "#13294B"),
+    ("939", "Very Dark Navy Blue", "#13213C"),
+    // Purples
+    ("3747", "Very Light Blue Violet", "#E3E5EC"),
+    ("341", "Light Blue Violet", "#B5CAE6"),
+    ("3746", "Dark Blue Violet", "#948FCC"),
+    ("333", "Very Dark Blue Violet", "#6E5B9B"),
+    ("3837", "Ultra Dark Lavender", "#6D417E"),
+    ("211", "Light Lavender", "#E8D8EA"),
+    ("210", "Medium Lavender", "#C68FB9"),
+    ("209", "Dark Lavender", "#9C4E97"),
+    ("208", "Very Dark Lavender", "#7F2A7B"),
+    ("3836", "Light Grape", "#B78BC0"),
+    ("3835", "Medium Grape", "#924C8F"),
+    ("3834", "Dark Grape", "#742A6E"),
+    ("154", "Very Dark Grape", "#551839"),
+    ("153", "Very Light Violet", "#E8CCDF"),
+    ("3743", "Very Light Antique Violet", "#E3D7E2"),
+    ("3042", "Light Antique Violet", "#D7BFD4"),
+    ("3041", "Medium Antique Violet", "#C6A9C1"),
+    ("3740", "Dark Antique Violet", "#A17896"),
+    // Browns
+    ("3865", "Winter White", "#FAF9F4"),
+    ("739", "Ultra Very Light Tan", "#F5EDD3"),
+    ("738", "Very Light Tan", "#EBCBA1"),
+    ("437", "Light Tan", "#D9A964"),
+    ("436", "Tan", "#C68638"),
+    ("435", "Very Light Brown", "#945B25"),
+    ("434", "Light Brown", "#944B14"),
+    ("433", "Medium Brown", "#85511F"),
+    ("801", "Dark Coffee Brown", "#693F17"),
+    ("898", "Very Dark Coffee Brown", "#5C3A1F"),
+    ("938", "Ultra Dark Coffee Brown", "#4A2812"),
+    ("3371", "Black Brown", "#301904"),
+    ("543", "Ultra Very Light Beige Brown", "#F0DBC8"),
+    ("3864", "Light Mocha Beige", "#C9A992"),
+    ("3863", "Medium Mocha Beige", "#A4826A"),
+    ("3862", "Dark Mocha Beige", "#856551"),
+    ("3861", "Light Cocoa", "#A07959"),
+    ("3860", "Cocoa", "#78503B"),
+    ("3031", "Very Dark Mocha Brown", "#54372A"),
+    ("3021", "Very Dark Brown Gray", "#5B4733"),
+    // Terra Cottas & Specialty
+    ("948", "Very Light Peach", "#FED9C7"),
+    ("754", "Light Peach", "#F9CEB9"),
+    ("945", "Tawny", "#F6C199"),
+    ("3778", "Light Terra Cotta", "#DD967F"),
+    ("356", "Medium Terra Cotta", "#C66F5C"),
+    ("3830", "Terra Cotta", "#B85A41"),
+    ("355", "Dark Terra Cotta", "#A44037"),
+    ("3777", "Very Dark Terra Cotta", "#8E3031"),
+];
+
+/// DMC Satin floss (rayon), keyed by the satin `S` codes.
+/// Each entry: (code, name, hex)
+pub(super) const DMC_SATIN_PALETTE: &[(&str, &str, &str)] = &[
+    ("S5200", "Satin Snow White", "#FFFFFF"),
+    ("S310", "Satin Black", "#000000"),
+    ("S415", "Satin Pearl Gray", "#D3D3D3"),
+    ("S762", "Satin Very Light Pearl Gray", "#E6E6E6"),
+    ("S321", "Satin Red", "#CE1938"),
+    ("S666", "Satin Bright Red", "#EC2130"),
+    ("S3350", "Satin Ultra Dark Dusty Rose", "#B52D5C"),
+    ("S815", "Satin Medium Garnet", "#7C1D2B"),
+    ("S352", "Satin Light Coral", "#FBB9AA"),
+    ("S971", "Satin Pumpkin", "#FF8600"),
+    ("S973", "Satin Bright Canary", "#FFE529"),
+    ("S307", "Satin Lemon", "#FFE600"),
+    ("S725", "Satin Topaz", "#FFC723"),
+    ("S3820", "Satin Dark Straw", "#DDB900"),
+    ("S3821", "Satin Straw", "#E0C47A"),
+    ("S699", "Satin Green", "#136C00"),
+    ("S700", "Satin Bright Green", "#2E7D09"),
+    ("S702", "Satin Kelly Green", "#86B500"),
+    ("S907", "Satin Light Parrot Green", "#D0F200"),
+    ("S959", "Satin Medium Seagreen", "#89C9BC"),
+    ("S797", "Satin Royal Blue", "#13438D"),
+    ("S798", "Satin Dark Delft Blue", "#5174A0"),
+    ("S799", "Satin Medium Delft Blue", "#7393B7"),
+    ("S211", "Satin Light Lavender", "#E8D8EA"),
+    ("S208", "Satin Very Dark Lavender", "#7F2A7B"),
+    ("S433", "Satin Medium Brown", "#85511F"),
+    ("S898", "Satin Very Dark Coffee Brown", "#5C3A1F"),
+    ("S938", "Satin Ultra Dark Coffee Brown", "#4A2812"),
+    ("S3865", "Satin Winter White", "#FAF9F4"),
+];
+
+/// Anchor stranded cotton, as the DMC shade each code replaces on the manufacturer chart.
+/// Each entry: (code, DMC code)
+pub(super) const ANCHOR_PALETTE: &[(&str, &str)] = &[
+    ("1", "B5200"),
+    ("2", "White"),
+    ("387", "Ecru"),
+    ("390", "822"),
+    ("830", "644"),
+    ("392", "642"),
+    ("393", "640"),
+    ("847", "3072"),
+    ("900", "648"),
+    ("8581", "647"),
+    ("273", "645"),
+    ("403", "310"),
+    ("236", "3799"),
+    ("401", "413"),
+    ("234", "762"),
+    ("398", "415"),
+    ("399", "318"),
+    ("235", "414"),
+    ("400", "317"),
+    ("388", "3024"),
+    ("899", "3023"),
+    ("46", "666"),
+    ("9046", "321"),
+    ("19", "304"),
+    ("1005", "498"),
+    ("43", "815"),
+    ("45", "814"),
+    ("1022", "760"),
+    ("1023", "3712"),
+    ("1024", "3328"),
+    ("1025", "347"),
+    ("6", "353"),
+    ("9", "352"),
+    ("10", "351"),
+    ("11", "350"),
+    ("13", "349"),
+    ("23", "818"),
+    ("73", "963"),
+    ("25", "3716"),
+    ("75", "962"),
+    ("76", "961"),
+    ("31", "3833"),
+    ("28", "3832"),
+    ("29", "3831"),
+    ("59", "3350"),
+    ("74", "3354"),
+    ("8", "3824"),
+    ("328", "3341"),
+    ("329", "3340"),
+    ("330", "608"),
+    ("335", "606"),
+    ("316", "970"),
+    ("298", "972"),
+    ("1003", "3853"),
+    ("313", "3854"),
+    ("311", "3855"),
+    ("323", "722"),
+    ("326", "720"),
+    ("324", "721"),
+    ("288", "445"),
+    ("289", "307"),
+    ("290", "973"),
+    ("291", "444"),
+    ("292", "3078"),
+    ("293", "727"),
+    ("295", "726"),
+    ("305", "725"),
+    ("306", "3820"),
+    ("307", "783"),
+    ("308", "782"),
+    ("309", "781"),
+    ("310", "780"),
+    ("891", "676"),
+    ("890", "729"),
+    ("901", "680"),
+    ("256", "704"),
+    ("238", "703"),
+    ("226", "702"),
+    ("227", "701"),
+    ("228", "700"),
+    ("923", "699"),
+    ("255", "907"),
+    ("257", "905"),
+    ("258", "904"),
+    ("240", "164"),
+    ("242", "989"),
+    ("243", "988"),
+    ("244", "987"),
+    ("246", "986"),
+    ("264", "3348"),
+    ("266", "3347"),
+    ("267", "3346"),
+    ("268", "3345"),
+    ("259", "772"),
+    ("260", "3364"),
+    ("215", "320"),
+    ("217", "367"),
+    ("218", "319"),
+    ("185", "964"),
+    ("186", "959"),
+    ("187", "958"),
+    ("188", "3812"),
+    ("1060", "3811"),
+    ("167", "598"),
+    ("168", "597"),
+    ("169", "3810"),
+    ("170", "3809"),
+    ("274", "928"),
+    ("848", "927"),
+    ("850", "926"),
+    ("779", "3768"),
+    ("159", "3841"),
+    ("120", "3840"),
+    ("176", "3839"),
+    ("177", "3838"),
+    ("144", "800"),
+    ("130", "809"),
+    ("136", "799"),
+    ("146", "798"),
+    ("132", "797"),
+    ("133", "796"),
+    ("129", "3325"),
+    ("140", "3755"),
+    ("977", "334"),
+    ("978", "322"),
+    ("979", "312"),
+    ("148", "311"),
+    ("150", "336"),
+    ("152", "823"),
+    ("117", "341"),
+    ("1030", "3746"),
+    ("119", "333"),
+    ("342", "211"),
+    ("108", "210"),
+    ("109", "209"),
+    ("110", "208"),
+    ("90", "3836"),
+    ("98", "3835"),
+    ("100", "3834"),
+    ("873", "154"),
+    ("95", "153"),
+    ("869", "3743"),
+    ("870", "3042"),
+    ("871", "3041"),
+    ("872", "3740"),
+    ("361", "738"),
+    ("362", "437"),
+    ("363", "436"),
+    ("1046", "435"),
+    ("358", "433"),
+    ("359", "801"),
+    ("360", "898"),
+    ("381", "938"),
+    ("382", "3371"),
+    ("933", "543"),
+    ("376", "3864"),
+    ("378", "3861"),
+    ("379", "3860"),
+    ("905", "3031"),
+    ("1011", "948"),
+    ("1012", "754"),
+    ("881", "945"),
+    ("1013", "3778"),
+    ("5975", "356"),
+    ("1014", "355"),
+    ("1015", "3777"),
+];
+
+/// Madeira stranded cotton, as the DMC shade each code replaces on the manufacturer chart.
+/// Each entry: (code, DMC code)
+pub(super) const MADEIRA_PALETTE: &[(&str, &str)] = &[
+    ("2400", "310"),
+    ("2401", "B5200"),
+    ("2402", "White"),
+    ("2404", "Ecru"),
+    ("1908", "822"),
+    ("1907", "644"),
+    ("1906", "642"),
+    ("1905", "640"),
+    ("1805", "3072"),
+    ("1814", "648"),
+    ("1813", "647"),
+    ("1812", "646"),
+    ("1811", "645"),
+    ("1810", "3799"),
+    ("1713", "413"),
+    ("1808", "3787"),
+    ("1804", "762"),
+    ("1803", "415"),
+    ("1802", "318"),
+    ("1801", "414"),
+    ("1714", "317"),
+    ("1901", "3024"),
+    ("1902", "3023"),
+    ("0210", "666"),
+    ("0510", "321"),
+    ("0511", "304"),
+    ("0512", "498"),
+    ("0513", "815"),
+    ("0514", "814"),
+    ("0405", "760"),
+    ("0406", "3712"),
+    ("0407", "347"),
+    ("0408", "3328"),
+    ("0304", "353"),
+    ("0303", "352"),
+    ("0214", "351"),
+    ("0213", "350"),
+    ("0212", "349"),
+    ("0211", "817"),
+    ("0502", "818"),
+    ("0608", "963"),
+    ("0606", "3716"),
+    ("0609", "962"),
+    ("0610", "961"),
+    ("0603", "3350"),
+    ("0607", "3354"),
+    ("0605", "3733"),
+    ("0304A", "3824"),
+    ("0302", "3341"),
+    ("0301", "3340"),
+    ("0206", "608"),
+    ("0209", "606"),
+    ("0204", "970"),
+    ("0203", "971"),
+    ("0107", "972"),
+    ("0307", "722"),
+    ("0309", "720"),
+    ("0308", "721"),
+    ("0110", "445"),
+    ("0103", "307"),
+    ("0105", "973"),
+    ("0108", "444"),
+    ("0110A", "727"),
+    ("0109", "726"),
+    ("0106", "725"),
+    ("2211", "783"),
+    ("2212", "782"),
+    ("2213", "781"),
+    ("2214", "780"),
+    ("2208", "676"),
+    ("2209", "729"),
+    ("2210", "680"),
+    ("1308", "704"),
+    ("1307", "703"),
+    ("1306", "702"),
+    ("1305", "701"),
+    ("1304", "700"),
+    ("1303", "699"),
+    ("1410", "907"),
+    ("1411", "906"),
+    ("1412", "905"),
+    ("1413", "904"),
+    ("1401", "989"),
+    ("1402", "988"),
+    ("1403", "987"),
+    ("1404", "986"),
+    ("1409", "3348"),
+    ("1408", "3347"),
+    ("1407", "3346"),
+    ("1406", "3345"),
+    ("1603", "3364"),
+    ("1311", "320"),
+    ("1312", "367"),
+    ("1313", "319"),
+    ("1112", "964"),
+    ("1113", "959"),
+    ("1114", "958"),
+    ("1203", "3812"),
+    ("1111", "598"),
+    ("1110", "597"),
+    ("1108", "3810"),
+    ("1107", "3809"),
+    ("1708", "927"),
+    ("1707", "926"),
+    ("1706", "3768"),
+    ("0907", "3841"),
+    ("0908", "3840"),
+    ("0909", "3839"),
+    ("0910", "3838"),
+    ("1002", "800"),
+    ("0909A", "809"),
+    ("0910A", "799"),
+    ("0911", "798"),
+    ("0913", "797"),
+    ("0913A", "796"),
+    ("1002A", "3325"),
+    ("1013", "3755"),
+    ("1003", "334"),
+    ("1004", "322"),
+    ("1005", "312"),
+    ("1006", "311"),
+    ("1007", "336"),
+    ("1008", "823"),
+    ("1009", "939"),
+    ("0901", "341"),
+    ("0903", "3746"),
+    ("0904", "333"),
+    ("0801", "211"),
+    ("0802", "210"),
+    ("0803", "209"),
+    ("0804", "208"),
+    ("0711", "3836"),
+    ("0712", "3835"),
+    ("0713", "3834"),
+    ("0710", "153"),
+    ("0806", "3042"),
+    ("0805", "3041"),
+    ("0807", "3740"),
+    ("2014", "739"),
+    ("2013", "738"),
+    ("2012", "437"),
+    ("2011", "436"),
+    ("2010", "435"),
+    ("2009", "434"),
+    ("2008", "433"),
+    ("2007", "801"),
+    ("2006", "898"),
+    ("2005", "938"),
+    ("2004", "3371"),
+    ("1912", "3864"),
+    ("1913", "3863"),
+    ("1914", "3862"),
+    ("0306", "948"),
+    ("0305", "754"),
+    ("2313", "945"),
+    ("0403", "3778"),
+    ("0402", "356"),
+    ("0401", "3830"),
+    ("0401A", "355"),
+    ("2502", "3777"),
+];
+
+/// Cosmo (Lecien) embroidery floss, as the DMC shade each code replaces on the manufacturer chart.
+/// Each entry: (code, DMC code)
+pub(super) const COSMO_PALETTE: &[(&str, &str)] = &[
+    ("600", "310"),
+    ("100", "White"),
+    ("2500", "B5200"),
+    ("364", "Ecru"),
+    ("365", "822"),
+    ("366", "644"),
+    ("367", "642"),
+    ("368", "640"),
+    ("151", "762"),
+    ("152", "415"),
+    ("153", "318"),
+    ("154", "414"),
+    ("155", "317"),
+    ("895", "413"),
+    ("896", "3799"),
+    ("800", "666"),
+    ("346", "321"),
+    ("346A", "304"),
+    ("347", "498"),
+    ("242", "815"),
+    ("243", "814"),
+    ("202", "760"),
+    ("203", "3712"),
+    ("204", "3328"),
+    ("205", "347"),
+    ("462", "353"),
+    ("463", "352"),
+    ("464", "351"),
+    ("465", "350"),
+    ("466", "349"),
+    ("101", "818"),
+    ("102", "963"),
+    ("103", "3716"),
+    ("104", "962"),
+    ("105", "961"),
+    ("106", "3833"),
+    ("107", "3832"),
+    ("108", "3831"),
+    ("441", "3824"),
+    ("442", "3341"),
+    ("443", "3340"),
+    ("444", "608"),
+    ("445", "606"),
+    ("446", "970"),
+    ("447", "971"),
+    ("2001", "445"),
+    ("297", "307"),
+    ("298", "973"),
+    ("299", "444"),
+    ("700", "727"),
+    ("701", "726"),
+    ("702", "725"),
+    ("703", "783"),
+    ("704", "782"),
+    ("705", "781"),
+    ("706", "780"),
+    ("572", "676"),
+    ("573", "729"),
+    ("574", "680"),
+    ("2117", "704"),
+    ("2118", "703"),
+    ("2119", "702"),
+    ("2120", "701"),
+    ("2121", "700"),
+    ("2122", "699"),
+    ("271", "907"),
+    ("272", "906"),
+    ("273", "905"),
+    ("274", "904"),
+    ("318", "989"),
+    ("319", "988"),
+    ("320", "987"),
+    ("321", "986"),
+    ("562", "964"),
+    ("563", "959"),
+    ("564", "958"),
+    ("565", "3812"),
+    ("374", "598"),
+    ("375", "597"),
+    ("376", "3810"),
+    ("377", "3809"),
+    ("162", "3841"),
+    ("163", "3840"),
+    ("164", "3839"),
+    ("165", "3838"),
+    ("523", "800"),
+    ("524", "809"),
+    ("525", "799"),
+    ("526", "798"),
+    ("527", "797"),
+    ("528", "796"),
+    ("2169", "336"),
+    ("169", "939"),
+    ("168", "311"),
+    ("171", "211"),
+    ("172", "210"),
+    ("173", "209"),
+    ("174", "208"),
+    ("175", "3837"),
+    ("283", "3836"),
+    ("284", "3835"),
+    ("285", "3834"),
+    ("281", "153"),
+    ("385", "739"),
+    ("386", "738"),
+    ("307", "437"),
+    ("308", "436"),
+    ("309", "435"),
+    ("310", "434"),
+    ("311", "433"),
+    ("312", "801"),
+    ("313", "898"),
+    ("314", "938"),
+    ("315", "3371"),
+    ("2307", "3864"),
+    ("2308", "3863"),
+    ("2309", "3862"),
+    ("2441", "948"),
+    ("2442", "754"),
+    ("2443", "945"),
+    ("2444", "3778"),
+    ("2445", "356"),
+    ("2446", "3830"),
+    ("2447", "355"),
+    ("2448", "3777"),
+];
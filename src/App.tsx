@@ -179,7 +179,9 @@ export default function App() {
         settings: {
           pixel_size: processingConfig.targetSize,
           color_count: processingConfig.colorCount,
-          floss_brand: processingConfig.useDmcPalette ? 'DMC' : 'Custom',
+          floss_brand: processingConfig.useDmcPalette
+            ? (processingConfig.flossBrand ?? 'DMC')
+            : 'Custom',
         },
         state: snapshot,
         thumbnail_path: null,
@@ -71,7 +71,13 @@ export function BuildStage() {
         referencePlacement,
         fabricSetup.hoop
       )
-      const result = await processColoringBookImage(normalizedImage, colorCount, hoopConfig)
+      const result = await processColoringBookImage(
+        normalizedImage,
+        colorCount,
+        hoopConfig,
+        undefined,
+        processingConfig.flossBrand
+      )
       if (requestSeq !== requestSeqRef.current) return
       setColoringBookData(result)
       setColoringBookStatus('ready', null)
@@ -80,7 +86,7 @@ export function BuildStage() {
       const message = error instanceof Error ? error.message : 'Coloring book processing failed.'
       setColoringBookStatus('error', message)
     }
-  }, [compositionLocked, fabricSetup.hoop, normalizedImage, referencePlacement, setColoringBookData, setColoringBookStatus])
+  }, [compositionLocked, fabricSetup.hoop, normalizedImage, processingConfig.flossBrand, referencePlacement, setColoringBookData, setColoringBookStatus])
 
   useEffect(() => {
     if (!normalizedImage || !compositionLocked) return
@@ -11,6 +11,7 @@ import { useCallback, useState } from 'react'
 import { invoke } from '@tauri-apps/api/core'
 import type { ProcessingConfig, SelectionArtifact, Stitch } from '@/types'
 import { Pattern } from '@/model/Pattern'
+import { expandNativeStitches } from '@/processing/native-types'
 import type {
   NativePatternResult,
   NativeProcessingConfig,
@@ -116,7 +117,7 @@ export function useNativeProcessing(): UseNativeProcessingResult {
 
   const convertResultToPattern = useCallback(
     (result: NativePatternResult, selection?: SelectionArtifact | null): Pattern => {
-      const stitches = result.stitches.map(convertStitch)
+      const stitches = expandNativeStitches(result).map(convertStitch)
 
       // Build DMC metadata map
       const dmcMetadataByMappedHex: Record<string, { code: string; name: string; hex: string }> = {}
@@ -1,5 +1,6 @@
 import { invoke } from '@tauri-apps/api/core'
 import type { ColoringBookData, HoopProcessingConfig } from '@/types'
+import type { NativeImageAdjustments, NativeStitchGridConfig } from './native-types'
 
 export const COLORING_BOOK_MIN_COLORS = 4
 export const COLORING_BOOK_MAX_COLORS = 30
@@ -20,7 +21,12 @@ async function imageDataToBytes(imageData: ImageData): Promise<Uint8Array> {
 export async function processColoringBookImage(
   image: ImageData,
   colorCount: number,
-  hoopConfig: HoopProcessingConfig
+  hoopConfig: HoopProcessingConfig,
+  adjustments?: NativeImageAdjustments,
+  /** Project `floss_brand`, e.g. 'DMC' or 'Anchor'; 'Custom' keeps the quantized colors */
+  flossBrand?: string,
+  /** Places a hoop given in inches or millimetres on the finished piece */
+  grid?: NativeStitchGridConfig
 ): Promise<ColoringBookData> {
   if (!isTauriEnvironment()) {
     throw new Error('Coloring book processing requires Tauri desktop runtime.')
@@ -41,5 +47,8 @@ export async function processColoringBookImage(
     colorCount: clampedColorCount,
     detailLevel: normalizedDetail,
     hoopConfig,
+    adjustments: adjustments ?? null,
+    threadBrand: flossBrand ?? null,
+    grid: grid ?? null,
   })
 }
@@ -10,6 +10,9 @@ export interface NativeDmcMetadata {
   hex: string
 }
 
+export type NativeStitchKind = 'full' | 'three_quarter' | 'half' | 'quarter'
+export type NativeStitchCorner = 'top_left' | 'top_right' | 'bottom_left' | 'bottom_right'
+
 /** A single stitch in the pattern grid */
 export interface NativeStitch {
   x: number
@@ -17,6 +20,77 @@ export interface NativeStitch {
   dmc_code: string
   marker: string
   hex: string
+  /** Both component codes when the stitch uses one strand each of two threads */
+  blend?: [string, string] | null
+  kind?: NativeStitchKind
+  /** Corner a partial stitch fills toward */
+  corner?: NativeStitchCorner | null
+}
+
+/** Thread drawn for one label of a NativeLabelGrid */
+export interface NativeGridColor {
+  dmc_code: string
+  marker: string
+  hex: string
+  blend?: [string, string] | null
+}
+
+/** Label value of a cell left as bare fabric */
+export const NATIVE_FABRIC_LABEL = 0xffff
+
+/** Compact stitch grid: one label per cell in row-major order, indexing `colors` */
+export interface NativeLabelGrid {
+  labels: number[]
+  colors: NativeGridColor[]
+  /** Cells split along a diagonal; the cell's label fills the triangle toward `corner` */
+  partials?: NativePartialCell[]
+}
+
+/** `secondary` is a quarter stitch in the opposite corner, or fabric */
+export interface NativePartialCell {
+  cell: number
+  corner: NativeStitchCorner
+  secondary: number
+}
+
+const OPPOSITE_CORNER: Record<NativeStitchCorner, NativeStitchCorner> = {
+  top_left: 'bottom_right',
+  top_right: 'bottom_left',
+  bottom_left: 'top_right',
+  bottom_right: 'top_left',
+}
+
+/** Backstitch line along cell edges; points are grid corners, (0, 0) top-left */
+export interface NativeBackstitchLine {
+  dmc_code: string
+  hex: string
+  points: { x: number; y: number }[]
+}
+
+/** Distinct threads asked for and produced, with the reason when fewer came out */
+export interface NativeThreadCountReport {
+  requested: number
+  achieved: number
+  /** Extra quantization passes spent replacing clusters that matched the same thread */
+  refits: number
+  shortfall: string | null
+}
+
+/** Pixel-art reading: source pixels per art pixel and art colors that share a thread */
+export interface NativePixelArtReport {
+  scale: number
+  /** Exact colors in the stitched art */
+  colors: number
+  collisions: { dmc_code: string; art_colors: string[] }[]
+}
+
+/** Isolated single stitches, per thread and as a density map over `block`-sized squares */
+export interface NativeConfettiReport {
+  total: number
+  by_color: { dmc_code: string; isolated: number }[]
+  density: { block: number; width: number; height: number; values: number[] }
+  /** Stitches recolored by the reduction pass */
+  reduced: number
 }
 
 /** Color mapping from original to DMC */
@@ -29,32 +103,145 @@ export interface NativeColorMapping {
 /** Legend entry with stitch statistics */
 export interface NativeLegendEntry {
   dmc_code: string
+  /** Catalog the code belongs to; null for raw quantized colors */
+  brand?: NativeThreadBrand | null
   name: string
   hex: string
   stitch_count: number
   coverage: number
+  /** Of `stitch_count`, how many are fractional stitches */
+  partial_count?: number
+  /** Chart symbol; unique across the legend */
+  marker?: string
+  /** Both component codes for a blended entry */
+  blend?: [string, string] | null
 }
 
 /** Complete pattern result from native processing */
 export interface NativePatternResult {
   width: number
   height: number
-  stitches: NativeStitch[]
+  /** Only present when `expand_stitches` was requested */
+  stitches?: NativeStitch[]
+  grid: NativeLabelGrid
   palette: string[]
   dmc_palette: string[]
+  /** Catalog `dmc_palette` and the legend codes were matched against */
+  brand?: NativeThreadBrand
+  /** Codes outside the owned threads that the pattern uses */
+  suggested_purchases?: string[]
+  /** Finished size on fabric when a stitch grid was requested */
+  physical_size?: { stitches_per_inch: number; width_inches: number; height_inches: number } | null
+  /** Present when a backstitch layer was requested */
+  backstitch?: NativeBackstitchLine[]
+  confetti?: NativeConfettiReport
+  /** Color unstitched cells show; white when absent */
+  fabric_hex?: string
+  thread_count?: NativeThreadCountReport
+  /** Present when pixel-art mode was on */
+  pixel_art?: NativePixelArtReport | null
   legend: NativeLegendEntry[]
   color_mappings: NativeColorMapping[]
   total_stitches: number
   processing_time_ms: number
 }
 
-/** Processing configuration for native backend */
+/** Bundled thread catalogs, plus `custom` for an imported library */
+export type NativeThreadBrand = 'dmc' | 'dmc_satin' | 'anchor' | 'madeira' | 'cosmo' | 'custom'
+
+/** Catalog for a brand label as stored in project settings, e.g. 'DMC Satin' */
+export function nativeThreadBrand(label: string): NativeThreadBrand {
+  return label.trim().toLowerCase().replace(/\s+/g, '_') as NativeThreadBrand
+}
+
+export type NativeDitherMode = 'none' | 'floyd_steinberg' | 'atkinson' | 'bayer' | 'confetti_limited'
+
+export type NativeColorMetric = 'cie76' | 'cie94' | 'cmc' | 'ciede2000' | 'oklab'
+
+/** Backstitch layer drawn along color boundaries */
+export interface NativeBackstitchConfig {
+  boundaries?: 'all' | 'contrast' | 'mask_outline'
+  /** Threshold for `contrast` boundaries */
+  min_delta_e?: number
+  /** Defaults to the darkest thread in the pattern */
+  thread_code?: string
+}
+
+/** Processing configuration for native backend; omitted fields take the Rust defaults */
 export interface NativeProcessingConfig {
   color_count: number
   use_dmc_palette: boolean
   smoothing_amount: number
   simplify_amount: number
   min_region_size: number
+  /** DMC by default */
+  thread_brand?: NativeThreadBrand
+  /** Imported library to match against instead of `thread_brand` */
+  custom_library_id?: string | null
+  /** When non-empty, matching is restricted to these codes */
+  owned_threads?: string[]
+  /** With `owned_threads`, threads outside the stash the pattern may use */
+  max_purchases?: number | null
+  locked_threads?: string[]
+  excluded_threads?: string[]
+  enable_blends?: boolean
+  max_blend_delta_e?: number | null
+  dither?: NativeDitherMode
+  color_metric?: NativeColorMetric
+  grid?: NativeStitchGridConfig | null
+  /** Also return one `NativeStitch` per cell in `stitches` */
+  expand_stitches?: boolean
+  backstitch?: NativeBackstitchConfig | null
+  fractional_stitches?: boolean
+  /** Recolor confetti within `max_delta_e`; absent only reports it */
+  confetti?: { max_delta_e?: number } | null
+  adjustments?: NativeImageAdjustments | null
+  fabric?: NativeFabricConfig
+  quantizer?: NativeQuantizer
+  training_weight?: NativeTrainingWeight
+  regions?: NativeRegionBudget[]
+  pixel_art?: NativePixelArtConfig | null
+}
+
+/**
+ * Expand a result's label grid into one stitch per cell, in row-major order.
+ * Quarter stitches of split cells follow after the last cell.
+ */
+export function expandNativeStitches(result: NativePatternResult): NativeStitch[] {
+  if (result.stitches && result.stitches.length > 0) return result.stitches
+  const partials = new Map((result.grid.partials ?? []).map((p) => [p.cell, p]))
+  const stitchAt = (
+    i: number,
+    label: number,
+    kind: NativeStitchKind,
+    corner: NativeStitchCorner | null
+  ): NativeStitch => {
+    const color = label === NATIVE_FABRIC_LABEL ? undefined : result.grid.colors[label]
+    return {
+      x: i % result.width,
+      y: Math.floor(i / result.width),
+      dmc_code: color?.dmc_code ?? 'Fabric',
+      marker: color?.marker ?? '',
+      hex: color?.hex ?? result.fabric_hex ?? '#FFFFFF',
+      blend: color?.blend ?? null,
+      kind: color ? kind : 'full',
+      corner: color ? corner : null,
+    }
+  }
+
+  const stitches = result.grid.labels.map((label, i) => {
+    const partial = partials.get(i)
+    if (!partial) return stitchAt(i, label, 'full', null)
+    const kind = partial.secondary === NATIVE_FABRIC_LABEL ? 'half' : 'three_quarter'
+    return stitchAt(i, label, kind, partial.corner)
+  })
+  for (const partial of partials.values()) {
+    if (partial.secondary === NATIVE_FABRIC_LABEL) continue
+    stitches.push(
+      stitchAt(partial.cell, partial.secondary, 'quarter', OPPOSITE_CORNER[partial.corner])
+    )
+  }
+  return stitches
 }
 
 /** Default processing configuration */
@@ -65,6 +252,105 @@ export const DEFAULT_NATIVE_CONFIG: NativeProcessingConfig = {
   simplify_amount: 0.2,
   min_region_size: 4,
 }
+
+/**
+ * Corrections applied to the image before quantization. Signed values run from -1 to 1
+ * with 0 leaving the image alone.
+ */
+export interface NativeImageAdjustments {
+  auto_levels?: boolean
+  brightness?: number
+  contrast?: number
+  saturation?: number
+  /** 1 by default; above 1 lightens midtones */
+  gamma?: number
+  auto_white_balance?: boolean
+  temperature?: number
+  tint?: number
+  /** Unsharp mask strength, 0 to 2 */
+  sharpen?: number
+}
+
+/** Fabric count and finished size of the stitch grid */
+export interface NativeStitchGridConfig {
+  fabric_count: number
+  /** Fabric threads per stitch: 1 for Aida, 2 for evenweave and linen */
+  over?: number
+  /** Wins over a finished size */
+  stitch_width?: number
+  finished_width?: number
+  finished_height?: number
+  unit?: 'inch' | 'millimeter'
+}
+
+/** Fabric a pattern is stitched on */
+export interface NativeFabricConfig {
+  /** `#RRGGBB`; white by default */
+  hex?: string
+  /** Pixels with alpha below this stay unstitched; 128 by default, 0 stitches everything */
+  alpha_threshold?: number
+  /** Leave cells unstitched when their thread is this close to the fabric */
+  match_delta_e?: number
+}
+
+/**
+ * How pixels are reduced to the palette. `thread_direct` snaps pixels to the catalog
+ * and keeps the `color_count` threads whose removal would cost the most.
+ */
+export type NativeQuantizer = 'kmeans' | 'median_cut' | 'wu' | 'octree' | 'thread_direct'
+
+/**
+ * Automatic weighting of palette training samples, multiplied into any mask. Mask values
+ * of 1-255 are stitched and weight training toward heavier pixels; 0 is fabric.
+ */
+export type NativeTrainingWeight = 'uniform' | 'saliency' | 'edge_density'
+
+/**
+ * Palette budget for one masked region; regions are quantized separately and share one
+ * legend. `mask` has one value per source pixel, like the `mask` command argument.
+ */
+export interface NativeRegionBudget {
+  mask: number[]
+  color_count: number
+  /** Defaults to the config's quantizer */
+  quantizer?: NativeQuantizer
+  /** Defaults to the config's dither mode */
+  dither?: NativeDitherMode
+}
+
+/**
+ * Pixel-art mode: one stitch per art pixel and one thread per exact color, without
+ * quantization. Not combined with regions; a stitch grid only sets the fabric.
+ */
+export interface NativePixelArtConfig {
+  /** Source pixels per art pixel; detected from the image when absent */
+  scale?: number
+}
+
+/** Options for the `estimate_pattern_floss` command */
+export interface NativeFlossConfig {
+  /** Defaults to the pattern's stitch grid fabric */
+  fabric_count?: number
+  over?: number
+  strands?: number
+  backstitch_strands?: number
+  technique?: 'cross' | 'half_cross' | 'tent'
+  /** Extra thread as a fraction of the stitched length; 0.15 by default */
+  waste_factor?: number
+  skein_meters?: number
+  stitches_per_hour?: number
+}
+
+/** Floss per thread (blends split into components) and estimated stitching time */
+export interface NativeFlossEstimate {
+  threads: { dmc_code: string; meters: number; skeins: number }[]
+  total_meters: number
+  total_skeins: number
+  stitching_hours: number
+  stitches_per_inch: number
+  strands: number
+}
+
 /** Magic Wand selection parameters */
 export interface NativeMagicWandParams {
   seed_x: number
@@ -8,6 +8,7 @@
 import { invoke } from '@tauri-apps/api/core'
 import type { ProcessingConfig, SelectionArtifact, Stitch } from '@/types'
 import { Pattern } from '@/model/Pattern'
+import { expandNativeStitches, nativeThreadBrand } from './native-types'
 import type { NativePatternResult, NativeProcessingConfig } from './native-types'
 
 /** Check if running in Tauri desktop environment */
@@ -23,6 +24,7 @@ function toNativeConfig(config: ProcessingConfig): NativeProcessingConfig {
     smoothing_amount: config.smoothingAmount,
     simplify_amount: config.simplifyAmount,
     min_region_size: config.minRegionSize,
+    thread_brand: nativeThreadBrand(config.flossBrand ?? 'DMC'),
   }
 }
 
@@ -42,7 +44,7 @@ function nativeResultToPattern(
   result: NativePatternResult,
   selection?: SelectionArtifact | null
 ): Pattern {
-  const stitches: Stitch[] = result.stitches.map((s) => ({
+  const stitches: Stitch[] = expandNativeStitches(result).map((s) => ({
     x: s.x,
     y: s.y,
     dmcCode: s.dmc_code,
@@ -51,7 +51,12 @@ export async function hydrateProjectFromDocument(project: ProjectDocument): Prom
   const sourceBitmap = await createImageBitmap(sourceFile)
 
   const state = project.state
-  const processingConfig = state.processing_config
+  // The saved `floss_brand` wins; 'Custom' only records that thread mapping was off.
+  const flossBrand = project.settings.floss_brand
+  const processingConfig = {
+    ...state.processing_config,
+    flossBrand: flossBrand === 'Custom' ? state.processing_config.flossBrand : flossBrand,
+  }
   const buildImage = normalizeImage(sourceBitmap, processingConfig.targetSize)
   const selectionImage = normalizeImageCapped(
     sourceBitmap,
@@ -72,7 +77,7 @@ export async function hydrateProjectFromDocument(project: ProjectDocument): Prom
       fabricSetup: state.fabric_setup,
       referencePlacement: state.reference_placement,
       compositionLocked: state.composition_locked,
-      processingConfig: state.processing_config,
+      processingConfig,
       maskConfig: state.mask_config,
       magicWandConfig: state.magic_wand_config,
       refinementConfig: state.refinement_config,
@@ -151,6 +151,7 @@ export const usePatternStore = create<PatternState>((set) => ({
     selectionWorkingSize: PROCESSING.DEFAULT_SELECTION_WORKING_SIZE,
     selectionMaxMegapixels: PROCESSING.DEFAULT_SELECTION_MAX_MEGAPIXELS,
     useDmcPalette: false,
+    flossBrand: 'DMC',
     smoothingAmount: 0.25,
     simplifyAmount: 0.15,
     minRegionSize: 3,
@@ -61,6 +61,7 @@ export interface ProcessingConfig {
   selectionWorkingSize: number // shortest side in pixels for SelectStage editing
   selectionMaxMegapixels: number // hard cap to protect memory during selection
   useDmcPalette: boolean
+  flossBrand?: string // thread catalog label, DMC when absent: 'DMC', 'DMC Satin', 'Anchor', 'Madeira' or 'Cosmo'
   smoothingAmount: number // 0..1
   simplifyAmount: number // 0..1
   minRegionSize: number // pixels (connected component size)
@@ -117,6 +118,8 @@ export interface HoopProcessingConfig {
   width: number
   height: number
   rotation: number
+  /** Center and size in this unit of the finished piece; needs a stitch grid */
+  unit?: 'inch' | 'millimeter'
 }
 
 export interface FabricSetup {